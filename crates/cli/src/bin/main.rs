@@ -1,16 +1,103 @@
 #![deny(clippy::all)]
 #![deny(unsafe_code)]
 
-use clap::App;
+use clap::{App, Arg, ArgMatches};
 use lspower::{LspService, Server};
 
-fn cli() {
+fn cli() -> ArgMatches {
     use ddlog_lsp_server::package::metadata;
     App::new(metadata::PKG_NAME)
         .author(metadata::PKG_AUTHORS)
         .version(metadata::PKG_VERSION)
         .about(metadata::PKG_DESCRIPTION)
-        .get_matches();
+        .subcommand(
+            App::new("graph")
+                .about("Writes the relation dependency graph of a program in Graphviz DOT format")
+                .arg(
+                    Arg::new("PROGRAM")
+                        .help("The main \".dl\" module of the program")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Writes the graph to FILE instead of stdout"),
+                ),
+        )
+        .subcommand(
+            App::new("format")
+                .about("Formats \".dl\" and \".dat\" files in place")
+                .arg(
+                    Arg::new("FILES")
+                        .help("The \".dl\" and \".dat\" files to format")
+                        .required(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Lists the files which aren't formatted (and fails if there are any) instead"),
+                )
+                .arg(
+                    Arg::new("sort-commands")
+                        .long("sort-commands")
+                        .help("Sorts the updates within the transactions of \".dat\" files"),
+                ),
+        )
+        .get_matches()
+}
+
+// Write the dependency graph of a program (for the "graph" subcommand).
+async fn graph(matches: &ArgMatches) -> anyhow::Result<()> {
+    use ddlog_lsp_server::analysis::dependency::DependencyGraph;
+    let program = matches.value_of("PROGRAM").expect("PROGRAM is required");
+    let dot = DependencyGraph::of_program_path(std::path::Path::new(program))
+        .await?
+        .to_dot();
+    match matches.value_of("output") {
+        Some(output) => std::fs::write(output, dot)?,
+        None => print!("{}", dot),
+    }
+    Ok(())
+}
+
+// Format files in place, or check that they are formatted (for the "format" subcommand).
+fn format(matches: &ArgMatches) -> anyhow::Result<()> {
+    use ddlog_lsp_server::{core::Language, provider::common::formatting};
+    use std::{convert::TryFrom, path::Path};
+    let mut options = lsp::FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+        ..Default::default()
+    };
+    if matches.is_present("sort-commands") {
+        let property = lsp::FormattingProperty::Bool(true);
+        options.properties.insert(formatting::SORT_COMMANDS.into(), property);
+    }
+    let check = matches.is_present("check");
+    let mut unformatted = 0;
+    for file in matches.values_of("FILES").into_iter().flatten() {
+        let path = Path::new(file);
+        let language = Language::try_from(path)?;
+        let text = std::fs::read_to_string(path)?;
+        let formatted = formatting::format_text(language, &text, &options)?;
+        if formatted == text {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            unformatted += 1;
+        } else {
+            std::fs::write(path, formatted)?;
+        }
+    }
+    if 0 < unformatted {
+        anyhow::bail!("{} file(s) would be reformatted", unformatted);
+    }
+    Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
@@ -21,8 +108,14 @@ fn main() -> anyhow::Result<()> {
 #[cfg(feature = "runtime-async-std")]
 fn run() -> anyhow::Result<()> {
     env_logger::try_init()?;
-    cli();
+    let matches = cli();
+    if let Some(matches) = matches.subcommand_matches("format") {
+        return format(matches);
+    }
     async_std::task::block_on(async {
+        if let Some(matches) = matches.subcommand_matches("graph") {
+            return graph(matches).await;
+        }
         let (service, messages) = LspService::new(|client| ddlog_lsp_server::Server::new(client).unwrap());
         let stdin = async_std::io::stdin();
         let stdout = async_std::io::stdout();
@@ -34,8 +127,14 @@ fn run() -> anyhow::Result<()> {
 #[cfg(feature = "runtime-futures")]
 fn run() -> anyhow::Result<()> {
     env_logger::try_init()?;
-    cli();
+    let matches = cli();
+    if let Some(matches) = matches.subcommand_matches("format") {
+        return format(matches);
+    }
     futures::future::block_on(async {
+        if let Some(matches) = matches.subcommand_matches("graph") {
+            return graph(matches).await;
+        }
         let (service, messages) = LspService::new(|client| ddlog_lsp_server::Server::new(client).unwrap());
         let stdin = blocking::Unblock::new(std::io::stdin());
         let stdout = blocking::Unblock::new(std::io::stdout());
@@ -47,8 +146,14 @@ fn run() -> anyhow::Result<()> {
 #[cfg(feature = "runtime-smol")]
 fn run() -> anyhow::Result<()> {
     env_logger::try_init()?;
-    cli();
+    let matches = cli();
+    if let Some(matches) = matches.subcommand_matches("format") {
+        return format(matches);
+    }
     smol::block_on(async {
+        if let Some(matches) = matches.subcommand_matches("graph") {
+            return graph(matches).await;
+        }
         let (service, messages) = LspService::new(|client| ddlog_lsp_server::Server::new(client).unwrap());
         let stdin = smol::Unblock::new(std::io::stdin());
         let stdout = smol::Unblock::new(std::io::stdout());
@@ -60,8 +165,14 @@ fn run() -> anyhow::Result<()> {
 #[cfg(feature = "runtime-tokio")]
 fn run() -> anyhow::Result<()> {
     env_logger::try_init()?;
-    cli();
+    let matches = cli();
+    if let Some(matches) = matches.subcommand_matches("format") {
+        return format(matches);
+    }
     tokio::runtime::Runtime::new()?.block_on(async {
+        if let Some(matches) = matches.subcommand_matches("graph") {
+            return graph(matches).await;
+        }
         let (service, messages) = LspService::new(|client| ddlog_lsp_server::Server::new(client).unwrap());
         let stdin = tokio::io::stdin();
         let stdout = tokio::io::stdout();
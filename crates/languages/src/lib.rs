@@ -5,6 +5,7 @@
 #![deny(unsafe_code)]
 
 #[cfg(not(target_arch = "wasm32"))]
+#[allow(missing_abi)]
 extern {
     #[allow(dead_code)]
     #[doc(hidden)]
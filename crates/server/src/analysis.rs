@@ -1,3 +1,7 @@
+pub mod dependency;
 pub mod fs;
 pub mod imports;
+pub mod schema;
 pub mod symbol;
+pub mod tree;
+pub mod types;
@@ -0,0 +1,640 @@
+use crate::{
+    analysis::{
+        symbol::{
+            context::{self, Namespace, Role},
+            resolve::Resolver,
+            ScopedName,
+        },
+        types::child_of_kind,
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::Write,
+    path::Path,
+};
+
+/// How a relation in the body of a rule (or the input of a transformer) contributes to the
+/// relations it derives.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Dependency {
+    /// A positive atom.
+    Positive,
+    /// A negated atom, i.e., `not R(..)`.
+    Negative,
+    /// A positive atom which is aggregated by a later `group_by`.
+    Aggregate,
+    /// A positive atom which is flattened by a later `FlatMap`.
+    FlatMap,
+    /// An input of an `apply`d transformer.
+    Transformer,
+}
+
+impl Dependency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dependency::Positive => "positive",
+            Dependency::Negative => "negative",
+            Dependency::Aggregate => "aggregate",
+            Dependency::FlatMap => "flat_map",
+            Dependency::Transformer => "transformer",
+        }
+    }
+}
+
+/// The identity of a relation, i.e., its declaration (or its name as written, if it can't be
+/// resolved). Relations of the same name declared by different modules are distinct.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RelationId {
+    /// The module declaring the relation, if it is known.
+    pub uri: Option<lsp::Url>,
+    pub name: String,
+}
+
+impl RelationId {
+    /// A key identifying the relation uniquely within the graph (e.g., for JSON or DOT).
+    pub fn key(&self) -> String {
+        match &self.uri {
+            Some(uri) => format!("{}#{}", uri, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl From<&str> for RelationId {
+    fn from(name: &str) -> Self {
+        Self {
+            uri: None,
+            name: name.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for RelationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A relation of the dependency graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelationNode {
+    pub id: RelationId,
+    /// The role of the relation (`input` or `output`), if it is declared with one.
+    pub role: Option<String>,
+    /// The declaration of the relation, if it is known.
+    pub location: Option<lsp::Location>,
+}
+
+/// A dependency of the relation `to` on the relation `from`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DependencyEdge {
+    pub from: RelationId,
+    pub to: RelationId,
+    pub dependency: Dependency,
+    /// The (first) rule or `apply` which introduces the dependency.
+    pub location: lsp::Location,
+    /// The atoms (or transformer inputs) which introduce the dependency.
+    pub atoms: Vec<lsp::Location>,
+}
+
+/// The dependencies between the relations of a set of modules, derived from their rules and
+/// transformer applications. Relations are identified by their declarations (see [`RelationId`]).
+#[derive(Clone, Debug, Default)]
+pub struct DependencyGraph {
+    nodes: BTreeMap<RelationId, RelationNode>,
+    // the first rule introducing each dependency, along with all of its atoms
+    edges: BTreeMap<(RelationId, RelationId, Dependency), (lsp::Location, Vec<lsp::Location>)>,
+}
+
+// A relation declaration or dependency of a module, whose names are not yet resolved.
+enum Fact {
+    Declaration {
+        name: String,
+        role: Option<String>,
+        location: lsp::Location,
+    },
+    Dependency {
+        from: ScopedName,
+        to: ScopedName,
+        dependency: Dependency,
+        location: lsp::Location,
+        atom: lsp::Location,
+    },
+}
+
+impl DependencyGraph {
+    /// Compute the dependency graph of the given ".dl" modules (skipping those unknown to the
+    /// session, or which couldn't be read or parsed).
+    pub async fn of_modules(session: &crate::core::Session, uris: &[lsp::Url]) -> anyhow::Result<Self> {
+        let mut graph = Self::default();
+        let mut resolver = Resolver::new(session);
+        for uri in uris.iter().filter(|uri| uri.path().ends_with(".dl")) {
+            let text = match session.get_text(uri).await {
+                Ok(text) => text.clone(),
+                Err(_) => continue,
+            };
+            let content = match text.get_content().await {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            let tree = match session.get_tree(uri).await {
+                Ok(tree) => tree.clone(),
+                Err(_) => continue,
+            };
+            let tree = match tree.await {
+                Some(tree) => tree,
+                None => continue,
+            };
+            let facts = {
+                let tree = tree.lock().await;
+                collect(uri, &content, &tree)
+            };
+            for fact in facts {
+                match fact {
+                    Fact::Declaration { name, role, location } => {
+                        let id = RelationId {
+                            uri: Some(uri.clone()),
+                            name,
+                        };
+                        let location = Some(location);
+                        graph.nodes.insert(id.clone(), RelationNode { id, role, location });
+                    },
+                    Fact::Dependency {
+                        from,
+                        to,
+                        dependency,
+                        location,
+                        atom,
+                    } => {
+                        let from = relation_id(&mut resolver, uri, from).await;
+                        let to = relation_id(&mut resolver, uri, to).await;
+                        graph.insert_edge(from, to, dependency, location, atom);
+                    },
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Compute the dependency graph of the program with the given main module.
+    pub async fn of_program(session: &crate::core::Session, main: &lsp::Url) -> anyhow::Result<Self> {
+        let modules = Resolver::new(session).program(main).await;
+        Self::of_modules(session, &modules).await
+    }
+
+    /// Compute the dependency graph of the program with the main module at `path`, opening its
+    /// modules from the file system.
+    pub async fn of_program_path(path: &Path) -> anyhow::Result<Self> {
+        let session = crate::core::Session::new(None)?;
+        let path = std::fs::canonicalize(path)?;
+        let main = lsp::Url::from_file_path(&path)
+            .map_err(|_| anyhow::anyhow!("could not convert path to uri: {:#?}", path))?;
+        let modules = session.insert_program(&main).await?;
+        Self::of_modules(&session, &modules).await
+    }
+
+    // Insert an edge, keeping the location of the first rule which introduces it.
+    fn insert_edge(
+        &mut self,
+        from: RelationId,
+        to: RelationId,
+        dependency: Dependency,
+        location: lsp::Location,
+        atom: lsp::Location,
+    ) {
+        let (_, atoms) = self.edges.entry((from, to, dependency)).or_insert((location, vec![]));
+        atoms.push(atom);
+    }
+
+    /// The relations of the graph (including those which are used but not declared).
+    pub fn nodes(&self) -> Vec<RelationNode> {
+        let mut nodes = self.nodes.clone();
+        for (from, to, _) in self.edges.keys() {
+            for id in [from, to] {
+                nodes.entry(id.clone()).or_insert_with(|| RelationNode {
+                    id: id.clone(),
+                    role: None,
+                    location: None,
+                });
+            }
+        }
+        nodes.into_values().collect()
+    }
+
+    /// The dependencies of the graph, ordered by their relations.
+    pub fn edges(&self) -> Vec<DependencyEdge> {
+        let edges = self.edges.iter();
+        edges
+            .map(|((from, to, dependency), (location, atoms))| DependencyEdge {
+                from: from.clone(),
+                to: to.clone(),
+                dependency: *dependency,
+                location: location.clone(),
+                atoms: atoms.clone(),
+            })
+            .collect()
+    }
+
+    /// The strongly connected components of the graph, i.e., the sets of mutually recursive
+    /// relations (in reverse topological order).
+    pub fn components(&self) -> Vec<Vec<RelationId>> {
+        let names = self.nodes().into_iter().map(|node| node.id).collect::<Vec<_>>();
+        let indices = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name, index))
+            .collect::<HashMap<_, _>>();
+        let mut successors = vec![vec![]; names.len()];
+        for (from, to, _) in self.edges.keys() {
+            successors[indices[from]].push(indices[to]);
+        }
+
+        // Tarjan's algorithm, with an explicit stack of (node, next successor) pairs
+        let mut index = vec![None; names.len()];
+        let mut lowlink = vec![0; names.len()];
+        let mut on_stack = vec![false; names.len()];
+        let mut stack = vec![];
+        let mut next = 0;
+        let mut components = vec![];
+        for root in 0 .. names.len() {
+            if index[root].is_some() {
+                continue;
+            }
+            let mut work = vec![(root, 0)];
+            while let Some((node, successor)) = work.pop() {
+                if 0 == successor {
+                    index[node] = Some(next);
+                    lowlink[node] = next;
+                    next += 1;
+                    stack.push(node);
+                    on_stack[node] = true;
+                }
+                if let Some(&other) = successors[node].get(successor) {
+                    work.push((node, successor + 1));
+                    match index[other] {
+                        None => work.push((other, 0)),
+                        Some(other_index) if on_stack[other] => lowlink[node] = lowlink[node].min(other_index),
+                        Some(_) => {},
+                    }
+                    continue;
+                }
+                if Some(lowlink[node]) == index[node] {
+                    let mut component = vec![];
+                    while let Some(other) = stack.pop() {
+                        on_stack[other] = false;
+                        component.push(names[other].clone());
+                        if other == node {
+                            break;
+                        }
+                    }
+                    component.sort();
+                    components.push(component);
+                }
+                if let Some(&(parent, _)) = work.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[node]);
+                }
+            }
+        }
+        components
+    }
+
+    /// A shortest path of dependencies from one relation to another, only passing through the
+    /// given relations.
+    pub fn path(&self, from: &RelationId, to: &RelationId, within: &[RelationId]) -> Option<Vec<DependencyEdge>> {
+        let edges = self.edges();
+        let mut previous: HashMap<&RelationId, Option<&DependencyEdge>> = HashMap::new();
+        previous.insert(from, None);
+        let mut queue = VecDeque::from(vec![from]);
+        while let Some(name) = queue.pop_front() {
+            if name == to {
+                let mut path = vec![];
+                let mut current = name;
+                while let Some(Some(edge)) = previous.get(current) {
+                    path.push((*edge).clone());
+                    current = &edge.from;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let outgoing = edges
+                .iter()
+                .filter(|edge| edge.from == *name && within.contains(&edge.to));
+            for edge in outgoing {
+                if !previous.contains_key(&edge.to) {
+                    previous.insert(&edge.to, Some(edge));
+                    queue.push_back(&edge.to);
+                }
+            }
+        }
+        None
+    }
+
+    /// Render the graph as JSON, i.e., `{ "nodes": [..], "edges": [..] }`. Nodes are identified by
+    /// their (unique) `id`, to which the `from` and `to` of the edges refer.
+    pub fn to_json(&self) -> serde_json::Value {
+        let nodes = self.nodes().into_iter().map(|node| {
+            serde_json::json!({
+                "id": node.id.key(),
+                "name": node.id.name,
+                "module": node.id.uri,
+                "role": node.role,
+                "location": node.location,
+            })
+        });
+        let edges = self.edges().into_iter().map(|edge| {
+            serde_json::json!({
+                "from": edge.from.key(),
+                "to": edge.to.key(),
+                "kind": edge.dependency.as_str(),
+                "location": edge.location,
+            })
+        });
+        serde_json::json!({
+            "nodes": nodes.collect::<Vec<_>>(),
+            "edges": edges.collect::<Vec<_>>(),
+        })
+    }
+
+    /// Render the graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("digraph dependencies {\n");
+        for node in self.nodes() {
+            let shape = match node.role.as_deref() {
+                Some("input") => "box",
+                Some("output") => "doubleoctagon",
+                _ => "ellipse",
+            };
+            // relations are labeled by their names, unless those identify them already
+            let label = if node.id.uri.is_some() {
+                format!("label={}, ", quote(&node.id.name))
+            } else {
+                String::new()
+            };
+            writeln!(dot, "    {} [{}shape={}];", quote(&node.id.key()), label, shape).ok();
+        }
+        for edge in self.edges() {
+            let style = match edge.dependency {
+                Dependency::Positive => "",
+                Dependency::Negative => " [style=dashed, color=red, label=\"not\"]",
+                Dependency::Aggregate => " [style=bold, label=\"group_by\"]",
+                Dependency::FlatMap => " [style=dotted, label=\"FlatMap\"]",
+                Dependency::Transformer => " [arrowhead=diamond, label=\"apply\"]",
+            };
+            writeln!(
+                dot,
+                "    {} -> {}{};",
+                quote(&edge.from.key()),
+                quote(&edge.to.key()),
+                style
+            )
+            .ok();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// Collect the relation declarations and dependencies of a ".dl" module.
+fn collect(uri: &lsp::Url, content: &ropey::Rope, tree: &tree_sitter::Tree) -> Vec<Fact> {
+    let mut facts = vec![];
+    let location = |node: &tree_sitter::Node| lsp::Location {
+        uri: uri.clone(),
+        range: content.tree_sitter_range_to_lsp_range(node.range()),
+    };
+    let name = |node: &tree_sitter::Node| ScopedName::parse(&content.utf8_text_for_tree_sitter_node(node));
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let items = root
+        .named_children(&mut cursor)
+        .filter(|node| dl::kind::ANNOTATED_ITEM == node.kind_id())
+        .filter_map(|node| child_of_kind(node, &[dl::kind::ITEM]))
+        .collect::<Vec<_>>();
+    for item in items {
+        let node = match item.named_child(0) {
+            Some(node) => node,
+            None => continue,
+        };
+        match node.kind_id() {
+            dl::kind::REL => {
+                let mut cursor = node.walk();
+                let declarations = node
+                    .named_children(&mut cursor)
+                    .filter(|child| [dl::kind::REL_ARGS, dl::kind::REL_ELEM].contains(&child.kind_id()));
+                for declaration in declarations {
+                    let rel = match child_of_kind(declaration, &[dl::kind::NAME_REL]) {
+                        Some(rel) => rel,
+                        None => continue,
+                    };
+                    let role = child_of_kind(declaration, &[dl::kind::REL_ROLE])
+                        .map(|role| content.utf8_text_for_tree_sitter_node(&role).into_owned());
+                    facts.push(Fact::Declaration {
+                        name: name(&rel).name,
+                        role,
+                        location: location(&rel),
+                    });
+                }
+            },
+            dl::kind::RULE => {
+                let mut heads = vec![];
+                let mut body = vec![];
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    match child.kind_id() {
+                        dl::kind::ATOM => heads.extend(atom_relation(child)),
+                        dl::kind::RHS => body.extend(child.named_child(0)),
+                        _ => {},
+                    }
+                }
+                // aggregation (and flattening) applies to every atom preceding it
+                let mut dependencies = vec![];
+                let mut positive = Dependency::Positive;
+                for rhs in body.iter().rev() {
+                    match rhs.kind_id() {
+                        dl::kind::ATOM => {
+                            let rel = atom_relation(*rhs);
+                            dependencies.extend(rel.map(|rel| (rel, positive, *rhs)));
+                        },
+                        dl::kind::RHS_ATOM_NEG => {
+                            let atom = child_of_kind(*rhs, &[dl::kind::ATOM]);
+                            let rel = atom.and_then(atom_relation);
+                            dependencies.extend(rel.map(|rel| (rel, Dependency::Negative, *rhs)));
+                        },
+                        dl::kind::RHS_GROUPING => positive = Dependency::Aggregate,
+                        dl::kind::RHS_FLAT_MAP if Dependency::Positive == positive => positive = Dependency::FlatMap,
+                        _ => {},
+                    }
+                }
+                for head in &heads {
+                    for (rel, dependency, atom) in &dependencies {
+                        facts.push(Fact::Dependency {
+                            from: name(rel),
+                            to: name(head),
+                            dependency: *dependency,
+                            location: location(&node),
+                            atom: location(atom),
+                        });
+                    }
+                }
+            },
+            dl::kind::APPLY => {
+                let mut inputs = vec![];
+                let mut outputs = vec![];
+                let mut cursor = node.walk();
+                let rels = node
+                    .named_children(&mut cursor)
+                    .filter(|child| dl::kind::NAME_REL == child.kind_id());
+                for rel in rels {
+                    match context::classify_dl(&rel) {
+                        Some((Namespace::Relation, Role::Declaration)) => outputs.push(rel),
+                        _ => inputs.push(rel),
+                    }
+                }
+                for output in &outputs {
+                    for input in &inputs {
+                        facts.push(Fact::Dependency {
+                            from: name(input),
+                            to: name(output),
+                            dependency: Dependency::Transformer,
+                            location: location(&node),
+                            atom: location(input),
+                        });
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    facts
+}
+
+// Resolve a relation name occurring in the module at `uri` to its declaration. Names which can't
+// be resolved are identified as written, and ambiguous ones by their first declaration.
+async fn relation_id(resolver: &mut Resolver<'_>, uri: &lsp::Url, name: ScopedName) -> RelationId {
+    let text = name.to_string();
+    let declarations = resolver.resolve_name(uri, Namespace::Relation, name).await;
+    match declarations.into_iter().next() {
+        Some(declaration) => RelationId {
+            uri: Some(declaration.uri),
+            name: declaration.name,
+        },
+        None => RelationId { uri: None, name: text },
+    }
+}
+
+// The relation name of an atom, i.e., `R` for `R(..)`, `R{..}`, or `R[..]`.
+fn atom_relation(atom: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    let atom = atom.named_child(0)?;
+    child_of_kind(atom, &[dl::kind::NAME_REL])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(line: u32) -> lsp::Location {
+        let uri = lsp::Url::parse("file:///main.dl").unwrap();
+        let position = lsp::Position::new(line, 0);
+        lsp::Location::new(uri, lsp::Range::new(position, position))
+    }
+
+    fn graph(edges: &[(&str, &str, Dependency)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::default();
+        for (line, (from, to, dependency)) in edges.iter().enumerate() {
+            let location = location(line as u32);
+            graph.insert_edge(
+                RelationId::from(*from),
+                RelationId::from(*to),
+                *dependency,
+                location.clone(),
+                location,
+            );
+        }
+        graph
+    }
+
+    #[test]
+    fn undeclared_relations_are_nodes_and_edges_are_styled() {
+        let mut graph = graph(&[
+            ("A", "B", Dependency::Positive),
+            ("C", "B", Dependency::Negative),
+            ("A", "B", Dependency::Positive),
+        ]);
+        graph.nodes.insert("A".into(), RelationNode {
+            id: "A".into(),
+            role: Some("input".into()),
+            location: Some(location(0)),
+        });
+
+        let names = graph.nodes().into_iter().map(|node| node.id.name).collect::<Vec<_>>();
+        assert_eq!(vec!["A", "B", "C"], names);
+        let edges = graph.edges();
+        assert_eq!(2, edges.len());
+        assert_eq!(location(0), edges[0].location);
+        assert_eq!(vec![location(0), location(2)], edges[0].atoms);
+        assert_eq!(
+            "digraph dependencies {\n    \"A\" [shape=box];\n    \"B\" [shape=ellipse];\n    \"C\" \
+             [shape=ellipse];\n    \"A\" -> \"B\";\n    \"C\" -> \"B\" [style=dashed, color=red, \
+             label=\"not\"];\n}\n",
+            graph.to_dot()
+        );
+        assert_eq!("negative", graph.to_json()["edges"][1]["kind"]);
+    }
+
+    #[test]
+    fn components_and_paths_follow_recursion() {
+        let graph = graph(&[
+            ("A", "B", Dependency::Positive),
+            ("B", "C", Dependency::Positive),
+            ("C", "B", Dependency::Negative),
+            ("C", "D", Dependency::Positive),
+            ("D", "D", Dependency::Aggregate),
+        ]);
+        let components = graph.components();
+        let names = components
+            .iter()
+            .map(|component| component.iter().map(|id| id.name.as_str()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![vec!["D"], vec!["B", "C"], vec!["A"]], names);
+
+        let path = graph.path(&"C".into(), &"B".into(), &components[1]).unwrap();
+        let path = path.iter().map(|edge| (edge.from.name.as_str(), edge.to.name.as_str()));
+        assert_eq!(vec![("C", "B")], path.collect::<Vec<_>>());
+        assert!(graph.path(&"B".into(), &"A".into(), &components[1]).is_none());
+        assert_eq!(Some(vec![]), graph.path(&"D".into(), &"D".into(), &components[0]));
+    }
+
+    #[test]
+    fn relations_of_the_same_name_in_different_modules_are_distinct() {
+        let relation = |module: &str| RelationId {
+            uri: Some(lsp::Url::parse(&format!("file:///{}.dl", module)).unwrap()),
+            name: "Edge".into(),
+        };
+        let mut graph = DependencyGraph::default();
+        graph.insert_edge(
+            relation("a"),
+            "Path".into(),
+            Dependency::Positive,
+            location(0),
+            location(0),
+        );
+        graph.insert_edge(
+            relation("b"),
+            "Path".into(),
+            Dependency::Negative,
+            location(1),
+            location(1),
+        );
+
+        assert_eq!(3, graph.nodes().len());
+        assert_eq!(3, graph.components().len());
+        assert_eq!("file:///a.dl#Edge", graph.to_json()["edges"][0]["from"]);
+        assert!(graph
+            .to_dot()
+            .contains("\"file:///b.dl#Edge\" [label=\"Edge\", shape=ellipse];"));
+    }
+}
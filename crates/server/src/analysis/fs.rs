@@ -3,7 +3,7 @@ use futures::{
     stream::{self, StreamExt},
     Stream,
 };
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
 
 pub fn documents_within_folder(folder: PathBuf) -> impl Stream<Item = std::path::PathBuf> {
     let init = vec![folder];
@@ -38,3 +38,72 @@ pub fn documents_within_folder(folder: PathBuf) -> impl Stream<Item = std::path:
     };
     Box::pin(stream::unfold(init, f))
 }
+
+/// Collect the modification times of the documents within the given folders.
+pub async fn modification_times(folders: Vec<PathBuf>) -> HashMap<PathBuf, SystemTime> {
+    let mut times = HashMap::new();
+    for folder in folders {
+        let mut documents = documents_within_folder(folder);
+        while let Some(path) = documents.next().await {
+            if let Ok(time) = tokio::fs::metadata(&path)
+                .await
+                .and_then(|metadata| metadata.modified())
+            {
+                times.insert(path, time);
+            }
+        }
+    }
+    times
+}
+
+/// Compare the modification times of documents at two points in time, as file events.
+pub fn file_events(old: &HashMap<PathBuf, SystemTime>, new: &HashMap<PathBuf, SystemTime>) -> Vec<lsp::FileEvent> {
+    let mut events = vec![];
+    for (path, time) in new {
+        match old.get(path) {
+            None => events.push((path, lsp::FileChangeType::CREATED)),
+            Some(old_time) if old_time != time => events.push((path, lsp::FileChangeType::CHANGED)),
+            _ => {},
+        }
+    }
+    for path in old.keys().filter(|path| !new.contains_key(*path)) {
+        events.push((path, lsp::FileChangeType::DELETED));
+    }
+    events
+        .into_iter()
+        .filter_map(|(path, typ)| {
+            let uri = lsp::Url::from_file_path(path).ok()?;
+            Some(lsp::FileEvent { uri, typ })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn file_events_compare_modification_times() {
+        let time = SystemTime::UNIX_EPOCH;
+        let later = time + Duration::from_secs(1);
+        let old = vec![("/a.dl", time), ("/b.dl", time), ("/c.dat", time)]
+            .into_iter()
+            .map(|(path, time)| (PathBuf::from(path), time))
+            .collect();
+        let new = vec![("/a.dl", time), ("/b.dl", later), ("/d.dl", time)]
+            .into_iter()
+            .map(|(path, time)| (PathBuf::from(path), time))
+            .collect();
+        let mut events = file_events(&old, &new)
+            .into_iter()
+            .map(|event| (event.uri.path().to_string(), event.typ))
+            .collect::<Vec<_>>();
+        events.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        assert_eq!(events, vec![
+            (String::from("/b.dl"), lsp::FileChangeType::CHANGED),
+            (String::from("/c.dat"), lsp::FileChangeType::DELETED),
+            (String::from("/d.dl"), lsp::FileChangeType::CREATED),
+        ]);
+    }
+}
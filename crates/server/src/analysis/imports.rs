@@ -1,16 +1,34 @@
-use crate::core::language::dl;
+use crate::{
+    analysis::symbol::{
+        resolve::{Resolver, STD_MODULE},
+        ScopedName,
+    },
+    core::language::dl,
+};
 use lsp_text::RopeExt;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::PathBuf,
+};
 
 #[derive(Clone, Debug)]
 pub struct ModulePath {
     components: Vec<String>,
 }
 
+impl ModulePath {
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct Import {
     pub module_path: ModulePath,
     pub module_alias: Option<String>,
+    /// The range of the import statement.
+    pub range: lsp::Range,
 }
 
 impl Import {
@@ -30,9 +48,21 @@ impl Import {
             let node = node.child_by_field_id(dl::field::MODULE_ALIAS);
             node.map(|inner| content.utf8_text_for_tree_sitter_node(&inner).into_owned())
         };
+        let range = content.tree_sitter_range_to_lsp_range(node.range());
         Self {
             module_path,
             module_alias,
+            range,
+        }
+    }
+
+    /// Whether a name qualified by `qualifier` refers to this import. Aliased imports are only
+    /// reachable through their alias, others through their full module path.
+    pub fn is_qualified_by(&self, qualifier: &[String]) -> bool {
+        if let Some(alias) = &self.module_alias {
+            qualifier.len() == 1 && qualifier[0] == *alias
+        } else {
+            self.module_path.components == qualifier
         }
     }
 }
@@ -67,14 +97,259 @@ pub fn collect_imports<'a>(content: &'a ropey::Rope, tree: &'a tree_sitter::Tree
         .map(|node| Import::new(content, node))
 }
 
-pub fn resolve_import(base: lsp::Url) -> impl Fn(Import) -> ResolvedImport {
-    move |import| {
-        let suffix = format!("{}.dl", import.module_path.components.join("/"));
-        let uri = base
-            .join(&suffix)
-            .unwrap_or_else(|err| panic!("error parsing import path component: {}", err));
-        ResolvedImport { import, uri }
+/// Resolve an import against the given roots (see [`resolve_module`]), or return it if its
+/// module can't be found.
+pub fn resolve_import<'a>(
+    roots: &'a [PathBuf],
+    known: &'a dyn Fn(&lsp::Url) -> bool,
+) -> impl Fn(Import) -> Result<ResolvedImport, Import> + 'a {
+    move |import| match resolve_module(roots, import.module_path.components(), known) {
+        Some(uri) => Ok(ResolvedImport { import, uri }),
+        None => Err(import),
+    }
+}
+
+/// Resolve a module path like `a::b` to the first module "a/b.dl" within the given roots (in order)
+/// which is either known to the session or exists on disk.
+pub fn resolve_module(roots: &[PathBuf], components: &[String], known: &dyn Fn(&lsp::Url) -> bool) -> Option<lsp::Url> {
+    let mut relative = components.iter().collect::<PathBuf>();
+    relative.set_extension("dl");
+    roots
+        .iter()
+        .map(|root| root.join(&relative))
+        .filter_map(|path| Some((lsp::Url::from_file_path(&path).ok()?, path)))
+        .find(|(uri, path)| known(uri) || path.is_file())
+        .map(|(uri, _)| uri)
+}
+
+/// The modules visible from a given (origin) module.
+#[derive(Clone, Debug)]
+pub struct ModuleScope {
+    pub origin: lsp::Url,
+    pub imports: Vec<ResolvedImport>,
+    /// The imports whose module couldn't be found.
+    pub unresolved: Vec<Import>,
+    /// The standard library module, which is implicitly imported (unless it is the origin).
+    pub std: Option<lsp::Url>,
+}
+
+impl ModuleScope {
+    /// Collect the imports of a module, resolving them against the given roots, i.e., the program
+    /// root followed by the library directories (see
+    /// [`import_roots`](crate::core::Session::import_roots)).
+    pub fn new(
+        origin: lsp::Url,
+        content: &ropey::Rope,
+        tree: &tree_sitter::Tree,
+        roots: &[PathBuf],
+        known: &dyn Fn(&lsp::Url) -> bool,
+    ) -> Self {
+        let mut imports = vec![];
+        let mut unresolved = vec![];
+        let resolve = resolve_import(roots, known);
+        for import in collect_imports(content, tree) {
+            match resolve(import) {
+                Ok(resolved) => imports.push(resolved),
+                Err(import) => unresolved.push(import),
+            }
+        }
+        let std = resolve_module(roots, &[String::from(STD_MODULE)], known).filter(|uri| *uri != origin);
+        Self {
+            origin,
+            imports,
+            unresolved,
+            std,
+        }
+    }
+
+    /// Compute the modules which may define `name`, grouped into tiers ordered by preference.
+    ///
+    /// Unqualified names are looked up first in the origin module, then in every imported module,
+    /// and finally in the standard library. Qualified names are only looked up in the imports
+    /// matching the qualifier (or the standard library, for `ddlog_std::...`).
+    pub fn candidate_modules(&self, name: &ScopedName) -> Vec<Vec<lsp::Url>> {
+        if name.is_qualified() {
+            let mut matching = self
+                .imports
+                .iter()
+                .filter(|resolved| resolved.import.is_qualified_by(&name.qualifier))
+                .map(|resolved| resolved.uri.clone())
+                .collect::<Vec<_>>();
+            match &self.std {
+                Some(std) if name.qualifier == [STD_MODULE] && !matching.contains(std) => matching.push(std.clone()),
+                _ => {},
+            }
+            vec![matching]
+        } else {
+            let imported = self.imports.iter().map(|resolved| resolved.uri.clone()).collect();
+            let mut tiers = vec![vec![self.origin.clone()], imported];
+            tiers.extend(self.std.clone().map(|std| vec![std]));
+            tiers
+        }
+    }
+}
+
+/// The (resolved) imports between a set of modules.
+#[derive(Clone, Debug, Default)]
+pub struct ImportGraph {
+    imports: BTreeMap<lsp::Url, Vec<ResolvedImport>>,
+}
+
+impl ImportGraph {
+    /// Compute the import graph of the given ".dl" modules (skipping those unknown to the session).
+    pub async fn of_modules(resolver: &mut Resolver<'_>, uris: &[lsp::Url]) -> Self {
+        let mut graph = Self::default();
+        for uri in uris.iter().filter(|uri| uri.path().ends_with(".dl")) {
+            if let Some(scope) = resolver.index(uri).await.and_then(|index| index.scope.clone()) {
+                graph.insert(uri.clone(), scope.imports);
+            }
+        }
+        graph
+    }
+
+    /// Record the imports of a module (replacing any previous ones).
+    pub fn insert(&mut self, uri: lsp::Url, imports: Vec<ResolvedImport>) {
+        self.imports.insert(uri, imports);
+    }
+
+    /// The imports of a module.
+    pub fn imports(&self, uri: &lsp::Url) -> &[ResolvedImport] {
+        self.imports.get(uri).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The imports of a module by other modules, along with the importing module.
+    pub fn importers<'a>(&'a self, uri: &'a lsp::Url) -> impl Iterator<Item = (&'a lsp::Url, &'a ResolvedImport)> + 'a {
+        self.imports.iter().flat_map(move |(importer, imports)| {
+            let imports = imports.iter().filter(move |resolved| resolved.uri == *uri);
+            imports.map(move |resolved| (importer, resolved))
+        })
+    }
+
+    /// Find the imports of a module which close a cycle, i.e., those whose module (transitively)
+    /// imports it back. Each cycle is given as a shortest chain of imports (along with the
+    /// importing module), starting with the import of the module itself.
+    pub fn cycles(&self, uri: &lsp::Url) -> Vec<Vec<(lsp::Url, ResolvedImport)>> {
+        let mut cycles = vec![];
+        for import in self.imports(uri) {
+            // breadth-first search for the module, remembering the import by which each module
+            // was reached first
+            let mut reached_by = HashMap::<&lsp::Url, (&lsp::Url, &ResolvedImport)>::new();
+            let mut queue = VecDeque::from(vec![&import.uri]);
+            while let Some(module) = queue.pop_front() {
+                if module == uri {
+                    break;
+                }
+                for next in self.imports(module) {
+                    if next.uri != import.uri && !reached_by.contains_key(&next.uri) {
+                        reached_by.insert(&next.uri, (module, next));
+                        queue.push_back(&next.uri);
+                    }
+                }
+            }
+            if import.uri != *uri && !reached_by.contains_key(uri) {
+                continue;
+            }
+            let mut chain = vec![];
+            let mut module = uri;
+            while module != &import.uri {
+                let (importer, next) = reached_by[module];
+                chain.push((importer.clone(), next.clone()));
+                module = importer;
+            }
+            chain.push((uri.clone(), import.clone()));
+            chain.reverse();
+            cycles.push(chain);
+        }
+        cycles
+    }
+
+    /// Convert the graph into JSON (for the custom "ddlog/importGraph" request), as
+    /// `{ "nodes": [..], "edges": [..] }`. If a module is given, only its imports and the imports
+    /// of it are included.
+    pub fn to_json(&self, module: Option<&lsp::Url>) -> serde_json::Value {
+        let edges = self
+            .imports
+            .iter()
+            .flat_map(|(importer, imports)| imports.iter().map(move |resolved| (importer, resolved)))
+            .filter(|(importer, resolved)| match module {
+                Some(module) => *importer == module || resolved.uri == *module,
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        let mut nodes = edges
+            .iter()
+            .flat_map(|(importer, resolved)| vec![*importer, &resolved.uri])
+            .chain(module)
+            .collect::<Vec<_>>();
+        if module.is_none() {
+            nodes.extend(self.imports.keys());
+        }
+        nodes.sort();
+        nodes.dedup();
+        let edges = edges.into_iter().map(|(importer, resolved)| {
+            serde_json::json!({
+                "from": importer,
+                "to": resolved.uri,
+                "module": resolved.import.module_path.components().join("::"),
+                "alias": resolved.import.module_alias,
+                "location": lsp::Location::new(importer.clone(), resolved.import.range),
+            })
+        });
+        serde_json::json!({
+            "nodes": nodes,
+            "edges": edges.collect::<Vec<_>>(),
+        })
     }
 }
 
-// FIXME: function to convert resolve imports to uris
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modules_resolve_against_the_first_root_containing_them() {
+        let roots = [PathBuf::from("/program"), PathBuf::from("/lib")];
+        let known = |uri: &lsp::Url| ["/program/a/b.dl", "/lib/a/b.dl", "/lib/c.dl"].contains(&uri.path());
+        let resolve = |path: &str| {
+            let components = path.split("::").map(String::from).collect::<Vec<_>>();
+            resolve_module(&roots, &components, &known).map(|uri| uri.path().to_owned())
+        };
+        assert_eq!(Some(String::from("/program/a/b.dl")), resolve("a::b"));
+        assert_eq!(Some(String::from("/lib/c.dl")), resolve("c"));
+        assert_eq!(None, resolve("d"));
+    }
+
+    fn import(uri: &str, module: &str) -> ResolvedImport {
+        let import = Import {
+            module_path: ModulePath {
+                components: vec![module.into()],
+            },
+            module_alias: None,
+            range: lsp::Range::default(),
+        };
+        let uri = lsp::Url::parse(&format!("file:///{}.dl", uri)).unwrap();
+        ResolvedImport { import, uri }
+    }
+
+    #[test]
+    fn cycles_are_found_along_shortest_chains() {
+        let uri = |module: &str| lsp::Url::parse(&format!("file:///{}.dl", module)).unwrap();
+        let mut graph = ImportGraph::default();
+        graph.insert(uri("a"), vec![import("b", "b"), import("d", "d")]);
+        graph.insert(uri("b"), vec![import("c", "c"), import("a", "a")]);
+        graph.insert(uri("c"), vec![import("a", "a")]);
+        graph.insert(uri("d"), vec![import("d", "d")]);
+
+        let cycles = graph.cycles(&uri("a"));
+        assert_eq!(1, cycles.len());
+        let chain = cycles[0]
+            .iter()
+            .map(|(importer, resolved)| (importer.path(), resolved.uri.path()));
+        assert_eq!(vec![("/a.dl", "/b.dl"), ("/b.dl", "/a.dl")], chain.collect::<Vec<_>>());
+
+        // modules importing themselves close a cycle on their own
+        assert_eq!(1, graph.cycles(&uri("d"))[0].len());
+        assert_eq!(2, graph.importers(&uri("a")).count());
+        assert_eq!(4, graph.to_json(Some(&uri("a")))["edges"].as_array().unwrap().len());
+    }
+}
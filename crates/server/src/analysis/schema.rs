@@ -0,0 +1,58 @@
+//! Reading the schema of relations and indexes from their declarations in ".dl" files.
+
+use crate::{
+    analysis::{tree::named_children, types::child_of_kind},
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+
+/// The declaration of a relation or index, as far as it is relevant for the records given in
+/// ".dat" files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schema {
+    /// Whether the relation is declared as an `input` relation.
+    pub input: bool,
+    /// The names and types of the fields of the relation or of the key of the index (empty for
+    /// `relation R[T]`).
+    pub fields: Vec<(String, Option<String>)>,
+    /// The element type of a relation declared with `relation R[T]`.
+    pub element: Option<String>,
+}
+
+impl Schema {
+    /// Read the schema from a declaration (a `REL_ARGS`, `REL_ELEM`, or `INDEX` node).
+    pub fn read(content: &ropey::Rope, node: tree_sitter::Node) -> Self {
+        let text = |node: tree_sitter::Node| content.utf8_text_for_tree_sitter_node(&node).into_owned();
+        let input = named_children(&node)
+            .into_iter()
+            .any(|child| dl::kind::REL_ROLE == child.kind_id() && "input" == text(child));
+        let element = if dl::kind::REL_ELEM == node.kind_id() {
+            child_of_kind(node, &[dl::kind::TYPE_ATOM]).map(text)
+        } else {
+            None
+        };
+        Schema {
+            input,
+            fields: read_fields(content, node),
+            element,
+        }
+    }
+}
+
+/// Read the names and types of the arguments or fields of a relation, index, or constructor
+/// declaration.
+pub fn read_fields(content: &ropey::Rope, node: tree_sitter::Node) -> Vec<(String, Option<String>)> {
+    let text = |node: &tree_sitter::Node| content.utf8_text_for_tree_sitter_node(node).into_owned();
+    named_children(&node)
+        .iter()
+        .filter(|child| [dl::kind::ARG, dl::kind::FIELD].contains(&child.kind_id()))
+        .filter_map(|field| {
+            let children = named_children(field);
+            let name = children.first()?;
+            let r#type = children
+                .last()
+                .filter(|last| ![dl::kind::NAME_ARG, dl::kind::NAME_FIELD].contains(&last.kind_id()));
+            Some((text(name), r#type.map(text)))
+        })
+        .collect()
+}
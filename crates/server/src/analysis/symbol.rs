@@ -1 +1,65 @@
 pub mod context;
+pub mod occurrence;
+pub mod resolve;
+
+/// A possibly qualified name, e.g., `Name`, `alias::Name`, or `a::b::Name`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ScopedName {
+    /// The qualifying module path components (empty for unqualified names).
+    pub qualifier: Vec<String>,
+    /// The final (unqualified) component of the name.
+    pub name: String,
+}
+
+impl ScopedName {
+    /// Parse a name written as, e.g., `a::b::Name`. Malformed names (e.g., empty ones, or ones
+    /// ending in `::`) have an empty final component, which doesn't resolve to any declaration.
+    pub fn parse(text: &str) -> Self {
+        let component = |component: &str| String::from(component.trim());
+        match text.rsplit_once("::") {
+            Some((qualifier, name)) => Self {
+                qualifier: qualifier.split("::").map(component).collect(),
+                name: component(name),
+            },
+            None => Self {
+                qualifier: vec![],
+                name: component(text),
+            },
+        }
+    }
+
+    pub fn is_qualified(&self) -> bool {
+        !self.qualifier.is_empty()
+    }
+}
+
+impl std::fmt::Display for ScopedName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for component in &self.qualifier {
+            write!(f, "{}::", component)?;
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_parsed_into_their_components() {
+        let name = ScopedName::parse("a::b::Name");
+        assert_eq!(vec![String::from("a"), String::from("b")], name.qualifier);
+        assert_eq!("Name", name.name);
+        assert_eq!("a::b::Name", name.to_string());
+        assert!(!ScopedName::parse("Name").is_qualified());
+    }
+
+    #[test]
+    fn malformed_names_have_an_empty_final_component() {
+        assert_eq!("", ScopedName::parse("").name);
+        let name = ScopedName::parse("a::");
+        assert_eq!(vec![String::from("a")], name.qualifier);
+        assert_eq!("", name.name);
+    }
+}
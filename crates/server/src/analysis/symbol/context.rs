@@ -1 +1,113 @@
+use crate::core::language::{dat, dl};
 
+/// The namespace a named DDlog entity is declared in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Namespace {
+    Constructor,
+    Function,
+    Index,
+    Relation,
+    Transformer,
+    Type,
+}
+
+/// Whether a name occurrence declares an entity or refers to one.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Role {
+    Declaration,
+    Reference,
+}
+
+/// Classify the syntactic context of a `NAME_*` node from a ".dl" file.
+pub fn classify_dl(name: &tree_sitter::Node) -> Option<(Namespace, Role)> {
+    let parent = name.parent()?.kind_id();
+    let (namespace, declarations): (Namespace, &[u16]) = match name.kind_id() {
+        dl::kind::NAME_CONS => (Namespace::Constructor, &[dl::kind::CONS_POS, dl::kind::CONS_REC]),
+        dl::kind::NAME_FUNC => (Namespace::Function, &[
+            dl::kind::FUNCTION_EXTERN,
+            dl::kind::FUNCTION_NORMAL,
+        ]),
+        dl::kind::NAME_INDEX => (Namespace::Index, &[dl::kind::INDEX]),
+        // relations listed after `->` in `apply` are declared as outputs of the transformer
+        dl::kind::NAME_REL if dl::kind::APPLY == parent && follows_arrow(name) => {
+            return Some((Namespace::Relation, Role::Declaration))
+        },
+        dl::kind::NAME_REL => (Namespace::Relation, &[dl::kind::REL_ARGS, dl::kind::REL_ELEM]),
+        // transformer parameters are named with `name_trans` but are not transformers themselves
+        dl::kind::NAME_TRANS if dl::kind::ARG_TRANS == parent => return None,
+        dl::kind::NAME_TRANS => (Namespace::Transformer, &[dl::kind::TRANSFORMER]),
+        dl::kind::NAME_TYPE => (Namespace::Type, &[dl::kind::TYPEDEF_EXTERN, dl::kind::TYPEDEF_NORMAL]),
+        dl::kind::NAME_VAR_TERM if is_callee(name, dl::kind::EXP_DECL_VAR, dl::kind::EXP, dl::kind::EXP_FUN_CALL) => {
+            (Namespace::Function, &[])
+        },
+        _ => return None,
+    };
+    let role = if declarations.contains(&parent) {
+        Role::Declaration
+    } else {
+        Role::Reference
+    };
+    Some((namespace, role))
+}
+
+/// Classify the syntactic context of a `NAME_*` node from a ".dat" file.
+///
+/// Command files never declare anything, so every classified occurrence is a reference.
+pub fn classify_dat(name: &tree_sitter::Node) -> Option<(Namespace, Role)> {
+    let parent = name.parent()?.kind_id();
+    let namespace = match name.kind_id() {
+        // `name_cons` is also used for field names in records and constructor declarations
+        dat::kind::NAME_CONS
+            if [
+                dat::kind::CONS_POS,
+                dat::kind::CONS_REC,
+                dat::kind::FIELD,
+                dat::kind::RECORD_NAMED,
+            ]
+            .contains(&parent) =>
+        {
+            return None
+        },
+        dat::kind::NAME_CONS => Namespace::Constructor,
+        dat::kind::NAME_FUNC => Namespace::Function,
+        dat::kind::NAME_INDEX => Namespace::Index,
+        // struct values are written with the constructor name in `name_rel` position
+        dat::kind::NAME_REL if dat::kind::VAL_STRUCT == parent => Namespace::Constructor,
+        dat::kind::NAME_REL => Namespace::Relation,
+        dat::kind::NAME_TYPE => Namespace::Type,
+        dat::kind::NAME_VAR_TERM
+            if is_callee(name, dat::kind::EXP_DECL_VAR, dat::kind::EXP, dat::kind::EXP_FUN_CALL) =>
+        {
+            Namespace::Function
+        },
+        _ => return None,
+    };
+    Some((namespace, Role::Reference))
+}
+
+// Whether a node is preceded (at the same level) by a `->` token.
+fn follows_arrow(node: &tree_sitter::Node) -> bool {
+    let mut prev = node.prev_sibling();
+    while let Some(sibling) = prev {
+        if dl::symbol::RIGHTWARDS_ARROW == sibling.kind_id() {
+            return true;
+        }
+        prev = sibling.prev_sibling();
+    }
+    false
+}
+
+// Whether a `name_var_term` node is the callee of a function call, i.e., `name(..)`.
+fn is_callee(name: &tree_sitter::Node, decl_var_kind: u16, exp_kind: u16, fun_call_kind: u16) -> bool {
+    let decl_var = name.parent().filter(|node| decl_var_kind == node.kind_id());
+    let exp = decl_var
+        .and_then(|node| node.parent())
+        .filter(|node| exp_kind == node.kind_id());
+    if let Some(exp) = exp {
+        if let Some(call) = exp.parent().filter(|node| fun_call_kind == node.kind_id()) {
+            // the callee is the first child of the call expression
+            return call.start_byte() == exp.start_byte();
+        }
+    }
+    false
+}
@@ -0,0 +1,88 @@
+use crate::{
+    analysis::symbol::{
+        context::{self, Namespace, Role},
+        ScopedName,
+    },
+    core::language::{dat, dl},
+};
+use lsp_text::RopeExt;
+
+// Classifies the syntactic context of a `NAME_*` node (see `analysis::symbol::context`).
+type Classifier = fn(&tree_sitter::Node) -> Option<(Namespace, Role)>;
+
+/// An occurrence of a (possibly qualified) name of a DDlog entity.
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+    pub name: ScopedName,
+    pub namespace: Namespace,
+    pub role: Role,
+    /// The range of the (possibly qualified) name.
+    pub range: lsp::Range,
+}
+
+impl Occurrence {
+    /// Whether the occurrence range contains the given position (inclusive of the end, so that a
+    /// cursor placed immediately after a name still refers to it).
+    pub fn contains(&self, position: lsp::Position) -> bool {
+        self.range.start <= position && position <= self.range.end
+    }
+
+    /// The range of the final (unqualified) component of the name, e.g., `Name` in `a::b::Name`.
+    pub fn name_range(&self) -> lsp::Range {
+        let mut range = self.range;
+        let len = self.name.name.encode_utf16().count() as u32;
+        range.start = lsp::Position::new(range.end.line, range.end.character.saturating_sub(len));
+        range
+    }
+}
+
+/// Collect the occurrences of all entity names within a document.
+pub fn collect(language: crate::core::Language, content: &ropey::Rope, tree: &tree_sitter::Tree) -> Vec<Occurrence> {
+    let (name_kinds, classify): (&[u16], Classifier) = match language {
+        crate::core::Language::DDlogDat => (
+            &[
+                dat::kind::NAME_CONS,
+                dat::kind::NAME_FUNC,
+                dat::kind::NAME_INDEX,
+                dat::kind::NAME_REL,
+                dat::kind::NAME_TYPE,
+                dat::kind::NAME_VAR_TERM,
+            ],
+            context::classify_dat,
+        ),
+        crate::core::Language::DDlogDl => (
+            &[
+                dl::kind::NAME_CONS,
+                dl::kind::NAME_FUNC,
+                dl::kind::NAME_INDEX,
+                dl::kind::NAME_REL,
+                dl::kind::NAME_TRANS,
+                dl::kind::NAME_TYPE,
+                dl::kind::NAME_VAR_TERM,
+            ],
+            context::classify_dl,
+        ),
+    };
+
+    let mut occurrences = vec![];
+    let mut work = vec![tree.root_node()];
+    while let Some(node) = work.pop() {
+        if name_kinds.contains(&node.kind_id()) {
+            if let Some((namespace, role)) = classify(&node) {
+                let name = ScopedName::parse(&content.utf8_text_for_tree_sitter_node(&node));
+                let range = content.tree_sitter_range_to_lsp_range(node.range());
+                occurrences.push(Occurrence {
+                    name,
+                    namespace,
+                    role,
+                    range,
+                });
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        work.extend(node.named_children(&mut cursor));
+    }
+    occurrences.sort_by_key(|occurrence| occurrence.range.start);
+    occurrences
+}
@@ -0,0 +1,285 @@
+use crate::analysis::{
+    imports::ModuleScope,
+    symbol::{
+        context::{Namespace, Role},
+        occurrence::{self, Occurrence},
+        ScopedName,
+    },
+};
+use lsp_text::RopeExt;
+use std::{collections::HashMap, sync::Arc};
+
+/// The name of the standard library module, which is implicitly imported by every module.
+pub const STD_MODULE: &str = "ddlog_std";
+
+/// The name occurrences (and visible modules) of a single document.
+#[derive(Clone, Debug)]
+pub struct DocumentIndex {
+    pub uri: lsp::Url,
+    pub language: crate::core::Language,
+    pub occurrences: Vec<Occurrence>,
+    /// The modules visible from the document (only for ".dl" files).
+    pub scope: Option<ModuleScope>,
+}
+
+impl DocumentIndex {
+    pub async fn new(session: &crate::core::Session, uri: &lsp::Url) -> anyhow::Result<Self> {
+        let roots = session.import_roots(uri).await;
+        let known = |uri: &lsp::Url| session.document_states.contains_key(uri);
+        let text = session.get_text(uri).await?.clone();
+        let content = text.get_content().await?;
+        let tree = session
+            .get_tree(uri)
+            .await?
+            .clone()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+        let tree = tree.lock().await;
+        let occurrences = occurrence::collect(text.language, &content, &tree);
+        let scope = match text.language {
+            crate::core::Language::DDlogDat => None,
+            crate::core::Language::DDlogDl => Some(ModuleScope::new(uri.clone(), &content, &tree, &roots, &known)),
+        };
+        Ok(Self {
+            uri: uri.clone(),
+            language: text.language,
+            occurrences,
+            scope,
+        })
+    }
+
+    /// Find the occurrence (if any) at the given position.
+    pub fn occurrence_at(&self, position: lsp::Position) -> Option<&Occurrence> {
+        self.occurrences.iter().find(|occurrence| occurrence.contains(position))
+    }
+
+    /// Iterate over the declarations of `name` in the given namespace.
+    pub fn declarations<'a>(
+        &'a self,
+        namespace: Namespace,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Occurrence> + 'a {
+        self.occurrences.iter().filter(move |occurrence| {
+            Role::Declaration == occurrence.role && namespace == occurrence.namespace && name == occurrence.name.name
+        })
+    }
+}
+
+/// The declaration site of a DDlog entity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+    pub uri: lsp::Url,
+    pub namespace: Namespace,
+    pub name: String,
+    pub range: lsp::Range,
+}
+
+impl Declaration {
+    fn new(uri: &lsp::Url, occurrence: &Occurrence) -> Self {
+        Self {
+            uri: uri.clone(),
+            namespace: occurrence.namespace,
+            name: occurrence.name.name.clone(),
+            range: occurrence.range,
+        }
+    }
+
+    /// Run a function on the node (of one of the given kinds) which encloses the declared name.
+    pub async fn with_node<T>(
+        &self,
+        session: &crate::core::Session,
+        kinds: &[u16],
+        f: impl FnOnce(&ropey::Rope, tree_sitter::Node) -> T,
+    ) -> anyhow::Result<Option<T>> {
+        let uri = &self.uri;
+        let text = session.get_text(uri).await?.clone();
+        let content = text.get_content().await?;
+        let tree = session
+            .get_tree(uri)
+            .await?
+            .clone()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+        let tree = tree.lock().await;
+        let range = content.lsp_range_to_tree_sitter_range(self.range)?;
+        let name = tree
+            .root_node()
+            .named_descendant_for_byte_range(range.start_byte(), range.end_byte());
+        let node = std::iter::successors(name, |node| node.parent()).find(|node| kinds.contains(&node.kind_id()));
+        Ok(node.map(|node| f(&content, node)))
+    }
+}
+
+/// Resolves name occurrences to their declarations, caching document indices along the way.
+pub struct Resolver<'a> {
+    session: &'a crate::core::Session,
+    indices: HashMap<lsp::Url, Option<Arc<DocumentIndex>>>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(session: &'a crate::core::Session) -> Self {
+        let indices = HashMap::default();
+        Self { session, indices }
+    }
+
+    /// Get the (cached) index for a document, if the document is known to the session.
+    pub async fn index(&mut self, uri: &lsp::Url) -> Option<Arc<DocumentIndex>> {
+        if let Some(index) = self.indices.get(uri) {
+            return index.clone();
+        }
+        let index = DocumentIndex::new(self.session, uri).await.ok().map(Arc::new);
+        self.indices.insert(uri.clone(), index.clone());
+        index
+    }
+
+    /// Resolve an occurrence within the document at `uri` to its declarations.
+    ///
+    /// More than one declaration is returned only if the name is genuinely ambiguous.
+    pub async fn resolve(&mut self, uri: &lsp::Url, occurrence: &Occurrence) -> Vec<Declaration> {
+        if Role::Declaration == occurrence.role {
+            return vec![Declaration::new(uri, occurrence)];
+        }
+
+        let index = if let Some(index) = self.index(uri).await {
+            index
+        } else {
+            return vec![];
+        };
+
+        let mut tiers = if let Some(scope) = &index.scope {
+            scope.candidate_modules(&occurrence.name)
+        } else {
+            vec![self.program_modules(uri, &occurrence.name.qualifier).await]
+        };
+
+        // The standard library is implicitly imported by every module. Unless the scope of the
+        // module already includes it (i.e., it was found in a library directory), any known
+        // standard library module is used.
+        let has_std = matches!(&index.scope, Some(scope) if scope.std.is_some());
+        if !has_std && (!occurrence.name.is_qualified() || occurrence.name.qualifier == [STD_MODULE]) {
+            tiers.push(self.std_modules());
+        }
+
+        for uris in tiers {
+            let mut declarations = vec![];
+            for uri in uris {
+                if let Some(index) = self.index(&uri).await {
+                    let matches = index.declarations(occurrence.namespace, &occurrence.name.name);
+                    declarations.extend(matches.map(|declaration| Declaration::new(&uri, declaration)));
+                }
+            }
+            if !declarations.is_empty() {
+                return declarations;
+            }
+        }
+
+        vec![]
+    }
+
+    /// Resolve a name as if it occurred (as a reference) within the document at `uri`.
+    pub async fn resolve_name(&mut self, uri: &lsp::Url, namespace: Namespace, name: ScopedName) -> Vec<Declaration> {
+        let occurrence = Occurrence {
+            name,
+            namespace,
+            role: Role::Reference,
+            range: lsp::Range::default(),
+        };
+        self.resolve(uri, &occurrence).await
+    }
+
+    /// Find every occurrence (within the documents known to the session) which resolves to one of
+    /// the given declarations.
+    pub async fn references(
+        &mut self,
+        declarations: &[Declaration],
+        include_declaration: bool,
+    ) -> Vec<(lsp::Url, Occurrence)> {
+        let mut references = vec![];
+        for uri in self.session.document_uris() {
+            if let Some(index) = self.index(&uri).await {
+                // Only occurrences with a matching (unqualified) name and namespace are candidates.
+                let candidates = index.occurrences.iter().filter(|occurrence| {
+                    declarations.iter().any(|declaration| {
+                        declaration.namespace == occurrence.namespace && declaration.name == occurrence.name.name
+                    })
+                });
+                for occurrence in candidates {
+                    if !include_declaration && Role::Declaration == occurrence.role {
+                        continue;
+                    }
+                    let resolved = self.resolve(&uri, occurrence).await;
+                    if resolved.iter().any(|declaration| declarations.contains(declaration)) {
+                        references.push((uri.clone(), occurrence.clone()));
+                    }
+                }
+            }
+        }
+        references
+    }
+
+    /// Whether names within the document at `dependent` may resolve to declarations of the module
+    /// at `uri`, i.e., whether it imports the module (or the module belongs to the standard
+    /// library), or it is a ".dat" file driving a program which includes the module.
+    pub async fn depends_on(&mut self, dependent: &lsp::Url, uri: &lsp::Url) -> bool {
+        let index = match self.index(dependent).await {
+            Some(index) => index,
+            None => return false,
+        };
+        match &index.scope {
+            Some(scope) => {
+                scope.std.as_ref() == Some(uri)
+                    || self.std_modules().contains(uri)
+                    || scope.imports.iter().any(|resolved| resolved.uri == *uri)
+            },
+            None => match self.session.program(dependent).await {
+                Some(main) => self.program(&main).await.contains(uri),
+                // without an associated program, names may refer to any known module
+                None => true,
+            },
+        }
+    }
+
+    /// The known standard library modules (see [`STD_MODULE`]).
+    pub fn std_modules(&self) -> Vec<lsp::Url> {
+        let file_name = format!("/{}.dl", STD_MODULE);
+        self.session
+            .document_uris()
+            .into_iter()
+            .filter(|uri| uri.path().ends_with(&file_name))
+            .collect()
+    }
+
+    /// Collect the modules of the program with the given main module, i.e., the main module along
+    /// with every (transitively and implicitly) imported module.
+    pub async fn program(&mut self, main: &lsp::Url) -> Vec<lsp::Url> {
+        let mut modules = vec![main.clone()];
+        let mut next = 0;
+        while let Some(uri) = modules.get(next).cloned() {
+            if let Some(scope) = self.index(&uri).await.and_then(|index| index.scope.clone()) {
+                for module in scope.imports.into_iter().map(|resolved| resolved.uri).chain(scope.std) {
+                    if !modules.contains(&module) {
+                        modules.push(module);
+                    }
+                }
+            }
+            next += 1;
+        }
+        modules
+    }
+
+    // The ".dl" modules which names in the ".dat" file at `uri` may refer to, i.e., those of the
+    // associated program (or any known module if there is none). Qualified names only refer to
+    // modules whose path ends with the qualifier, e.g., `a::b::R` refers to `.../a/b.dl`.
+    async fn program_modules(&mut self, uri: &lsp::Url, qualifier: &[String]) -> Vec<lsp::Url> {
+        let modules = match self.session.program(uri).await {
+            Some(main) => self.program(&main).await,
+            None => self.session.document_uris(),
+        };
+        let suffix = format!("/{}.dl", qualifier.join("/"));
+        modules
+            .into_iter()
+            .filter(|uri| uri.path().ends_with(".dl"))
+            .filter(|uri| qualifier.is_empty() || uri.path().ends_with(&suffix))
+            .collect()
+    }
+}
@@ -0,0 +1,25 @@
+//! Functions for navigating the syntax trees of documents.
+
+/// Iterate over a node and its ancestors.
+pub fn ancestors<'tree>(node: tree_sitter::Node<'tree>) -> impl Iterator<Item = tree_sitter::Node<'tree>> {
+    std::iter::successors(Some(node), |node| node.parent())
+}
+
+/// Collect the named children of a node.
+pub fn named_children<'tree>(node: &tree_sitter::Node<'tree>) -> Vec<tree_sitter::Node<'tree>> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor).collect()
+}
+
+/// Find the first named descendant of a node (including itself, in order of appearance) with a
+/// given kind.
+pub fn first_of_kind(node: tree_sitter::Node, kind: u16) -> Option<tree_sitter::Node> {
+    let mut work = vec![node];
+    while let Some(node) = work.pop() {
+        if kind == node.kind_id() {
+            return Some(node);
+        }
+        work.extend(named_children(&node).into_iter().rev());
+    }
+    None
+}
@@ -0,0 +1,68 @@
+//! Syntactic type information, i.e., the types written out in declarations.
+
+use crate::{analysis::symbol::ScopedName, core::language::dl};
+use lsp_text::RopeExt;
+
+/// A member of a declaration whose type is written out in the declaration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Member {
+    /// A named argument (of a relation or function) or field (of a record constructor).
+    Name(String),
+    /// A positional argument (of a relation or function) or field (of a record constructor).
+    Position(usize),
+    /// The element of a relation declared as `relation R[T]`.
+    Element,
+}
+
+/// Collect the names of the user-defined types within a type (or type atom) node, in order of
+/// appearance, e.g., `Option` and `T` for `Option<T>`.
+pub fn type_names(content: &ropey::Rope, node: tree_sitter::Node) -> Vec<ScopedName> {
+    let mut names = vec![];
+    let mut work = vec![node];
+    while let Some(node) = work.pop() {
+        if dl::kind::NAME_TYPE == node.kind_id() {
+            if matches!(node.parent(), Some(parent) if dl::kind::TYPE_USER == parent.kind_id()) {
+                names.push(ScopedName::parse(&content.utf8_text_for_tree_sitter_node(&node)));
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+        work.extend(children.into_iter().rev());
+    }
+    names
+}
+
+/// Find the type node of a member of a declaration, i.e., of an argument of a relation (`REL_ARGS`)
+/// or function (`FUNCTION_*`), a field of a record constructor (`CONS_REC`), or the element of a
+/// relation (`REL_ELEM`).
+pub fn member_type<'tree>(
+    content: &ropey::Rope,
+    declaration: tree_sitter::Node<'tree>,
+    member: &Member,
+) -> Option<tree_sitter::Node<'tree>> {
+    let mut cursor = declaration.walk();
+    let children = declaration.named_children(&mut cursor).collect::<Vec<_>>();
+    let mut members = children
+        .iter()
+        .filter(|child| [dl::kind::ARG, dl::kind::FIELD].contains(&child.kind_id()));
+    let member = match member {
+        Member::Name(name) => members.find(|child| {
+            let name_node = child_of_kind(**child, &[dl::kind::NAME_ARG, dl::kind::NAME_FIELD]);
+            matches!(name_node, Some(node) if *name == content.utf8_text_for_tree_sitter_node(&node))
+        }),
+        Member::Position(position) => members.nth(*position),
+        Member::Element if dl::kind::REL_ELEM == declaration.kind_id() => {
+            return child_of_kind(declaration, &[dl::kind::TYPE_ATOM]);
+        },
+        Member::Element => None,
+    };
+    member.and_then(|member| child_of_kind(*member, &[dl::kind::TYPE_ATOM]))
+}
+
+/// Find the first named child of a node with one of the given kinds.
+pub fn child_of_kind<'tree>(node: tree_sitter::Node<'tree>, kinds: &[u16]) -> Option<tree_sitter::Node<'tree>> {
+    let mut cursor = node.walk();
+    let mut children = node.named_children(&mut cursor);
+    children.find(|child| kinds.contains(&child.kind_id()))
+}
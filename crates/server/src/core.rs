@@ -1,5 +1,6 @@
 #![allow(unused)]
 
+mod config;
 mod document;
 mod error;
 mod future;
@@ -7,6 +8,7 @@ mod session;
 mod text;
 mod workspace_folder;
 
+pub use config::*;
 pub use ddlog_lsp_languages::{language::Language, parser};
 pub use ddlog_lsp_syntax::{language, node, range};
 pub use document::*;
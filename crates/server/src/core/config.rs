@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+/// The server settings, read from the `ddlog` section of the client configuration.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// Associations of ".dat" files with the programs they drive.
+    pub programs: Vec<ProgramAssociation>,
+    /// Whether the updates within the transactions of ".dat" files are sorted when formatting.
+    pub sort_commands: bool,
+    /// Additional directories to search for imported modules (relative to the workspace folder).
+    pub library_paths: Vec<PathBuf>,
+    /// A differential-datalog checkout, whose "lib" directory holds the standard library.
+    pub ddlog_home: Option<PathBuf>,
+}
+
+/// An association of ".dat" files with the program they drive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramAssociation {
+    /// A ".dat" file, or a directory of them (relative to the workspace folder).
+    pub commands: PathBuf,
+    /// The main ".dl" module of the program (relative to the workspace folder).
+    pub program: PathBuf,
+}
+
+impl Config {
+    /// Read the settings from a JSON value of the form:
+    ///
+    /// ```json
+    /// {
+    ///     "ddlog": {
+    ///         "programs": { "tests/": "src/main.dl", "data/init.dat": "src/main.dl" },
+    ///         "libraryPaths": ["lib", "/opt/ddlog-libs"],
+    ///         "home": "/opt/differential-datalog",
+    ///         "format": { "sortCommands": true }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// The outer `ddlog` section is optional. Unrecognized settings are ignored.
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        let value = value.get("ddlog").unwrap_or(value);
+        let programs = value
+            .get("programs")
+            .and_then(serde_json::Value::as_object)
+            .map(|programs| {
+                programs
+                    .iter()
+                    .filter_map(|(commands, program)| {
+                        let commands = PathBuf::from(commands);
+                        let program = PathBuf::from(program.as_str()?);
+                        Some(ProgramAssociation { commands, program })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let sort_commands = value
+            .pointer("/format/sortCommands")
+            .and_then(serde_json::Value::as_bool)
+            .unwrap_or_default();
+        let library_paths = value
+            .get("libraryPaths")
+            .and_then(serde_json::Value::as_array)
+            .map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(serde_json::Value::as_str)
+                    .map(PathBuf::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let ddlog_home = value.get("home").and_then(serde_json::Value::as_str).map(PathBuf::from);
+        Self {
+            programs,
+            sort_commands,
+            library_paths,
+            ddlog_home,
+        }
+    }
+
+    /// Find the main module of the program associated with a ".dat" file, resolving relative
+    /// paths against `base`. The most specific association takes precedence.
+    pub fn program(&self, base: &Path, commands: &Path) -> Option<PathBuf> {
+        self.programs
+            .iter()
+            .filter(|association| commands.starts_with(base.join(&association.commands)))
+            .max_by_key(|association| base.join(&association.commands).components().count())
+            .map(|association| base.join(&association.program))
+    }
+
+    /// The library directories against which imports are resolved (after the program root), i.e.,
+    /// the configured library paths (resolving relative paths against `base`) followed by the
+    /// "lib" directory of the differential-datalog checkout. Unless configured, the checkout is
+    /// given by the `DDLOG_HOME` environment variable (if set).
+    pub fn library_roots(&self, base: &Path) -> Vec<PathBuf> {
+        let home = self.ddlog_home.clone().or_else(default_ddlog_home);
+        self.library_paths
+            .iter()
+            .map(|path| base.join(path))
+            .chain(home.map(|home| home.join("lib")))
+            .collect()
+    }
+}
+
+// The differential-datalog checkout used unless one is configured.
+fn default_ddlog_home() -> Option<PathBuf> {
+    std::env::var_os("DDLOG_HOME").map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_prefers_most_specific_association() {
+        let config = Config::from_json(&serde_json::json!({
+            "ddlog": {
+                "programs": {
+                    "tests": "main.dl",
+                    "tests/other.dat": "other/main.dl",
+                    "ignored": 42,
+                },
+                "format": {
+                    "sortCommands": true,
+                },
+            },
+        }));
+        assert_eq!(2, config.programs.len());
+        assert!(config.sort_commands);
+        assert!(config.library_paths.is_empty());
+
+        let base = Path::new("/workspace");
+        let program = |path: &str| config.program(base, Path::new(path));
+        assert_eq!(
+            Some(PathBuf::from("/workspace/main.dl")),
+            program("/workspace/tests/a.dat")
+        );
+        assert_eq!(
+            Some(PathBuf::from("/workspace/other/main.dl")),
+            program("/workspace/tests/other.dat")
+        );
+        assert_eq!(None, program("/workspace/data/a.dat"));
+    }
+
+    #[test]
+    fn library_roots_precede_ddlog_home() {
+        let config = Config::from_json(&serde_json::json!({
+            "libraryPaths": ["lib", "/opt/libs", 42],
+            "home": "/opt/ddlog",
+        }));
+        let roots = config.library_roots(Path::new("/workspace"));
+        assert_eq!(
+            vec![
+                PathBuf::from("/workspace/lib"),
+                PathBuf::from("/opt/libs"),
+                PathBuf::from("/opt/ddlog/lib"),
+            ],
+            roots
+        );
+    }
+}
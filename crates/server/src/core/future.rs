@@ -26,7 +26,7 @@ impl<T> EagerFuture<T> {
     }
 }
 
-impl<'a, T: Clone + Send + 'static> EagerFuture<Option<Option<T>>> {
+impl<T: Clone + Send + 'static> EagerFuture<Option<Option<T>>> {
     pub fn flatten(self) -> EagerFuture<Option<T>>
     where
         Self: Send,
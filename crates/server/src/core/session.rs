@@ -16,7 +16,7 @@ use futures::{
 };
 use std::{
     pin::Pin,
-    sync::Arc,
+    sync::{atomic::AtomicBool, Arc},
     task::{Context, Poll},
 };
 
@@ -35,6 +35,7 @@ pub enum SessionResourceKind {
 pub struct Session {
     pub server_capabilities: RwLock<lsp::ServerCapabilities>,
     pub client_capabilities: RwLock<Option<lsp::ClientCapabilities>>,
+    pub config: RwLock<crate::core::Config>,
     client: Option<lspower::Client>,
     pub workspace_documents: DashMap<crate::core::WorkspaceFolder, DashSet<lsp::Url>>,
     pub document_workspaces: DashMap<lsp::Url, crate::core::WorkspaceFolder>,
@@ -43,12 +44,19 @@ pub struct Session {
     pub document_parsers: DashMap<lsp::Url, Arc<Mutex<tree_sitter::Parser>>>,
     pub document_trees: DashMap<lsp::Url, EagerFuture<Option<Arc<Mutex<tree_sitter::Tree>>>>>,
     pub document_symbols: DashMap<lsp::Url, EagerFuture<Option<Arc<Vec<lsp::SymbolInformation>>>>>,
+    pub document_semantic_tokens: DashMap<lsp::Url, lsp::SemanticTokens>,
+    /// The roots (i.e., the directories of the main modules) of the programs which the modules
+    /// belong to, as far as they are known (see `insert_program`).
+    pub program_roots: DashMap<lsp::Url, std::path::PathBuf>,
+    /// Whether the user was told that the standard library couldn't be found.
+    pub missing_std_reported: AtomicBool,
 }
 
 impl Session {
     pub fn new(client: Option<lspower::Client>) -> anyhow::Result<Self> {
         let server_capabilities = RwLock::new(crate::server::capabilities());
         let client_capabilities = RwLock::new(Default::default());
+        let config = RwLock::new(Default::default());
         let workspace_documents = DashMap::default();
         let document_workspaces = DashMap::default();
         let document_states = DashMap::default();
@@ -56,9 +64,13 @@ impl Session {
         let document_parsers = DashMap::default();
         let document_trees = DashMap::default();
         let document_symbols = DashMap::default();
+        let document_semantic_tokens = DashMap::default();
+        let program_roots = DashMap::default();
+        let missing_std_reported = AtomicBool::new(false);
         Ok(Session {
             server_capabilities,
             client_capabilities,
+            config,
             client,
             workspace_documents,
             document_workspaces,
@@ -67,6 +79,9 @@ impl Session {
             document_parsers,
             document_trees,
             document_symbols,
+            document_semantic_tokens,
+            program_roots,
+            missing_std_reported,
         })
     }
 }
@@ -140,6 +155,37 @@ impl Session {
         Ok(())
     }
 
+    /// Update the symbols of a document after it was edited, given its previous content and tree
+    /// (see [`document_symbol_incremental`](crate::provider::common::document_symbol_incremental)).
+    pub async fn update_document_symbols(
+        &self,
+        uri: &lsp::Url,
+        text: crate::core::Text,
+        tree: &tree_sitter::Tree,
+        previous: (&ropey::Rope, Option<&tree_sitter::Tree>),
+        edits: &[lsp_text::TextEdit<'_>],
+    ) -> anyhow::Result<()> {
+        let symbols = self.document_symbols.get(uri).map(|symbols| symbols.clone());
+        let symbols = match symbols {
+            Some(symbols) => symbols.await,
+            None => None,
+        };
+        let symbols = match (previous, symbols) {
+            ((content, Some(old_tree)), Some(symbols)) => {
+                let previous = crate::provider::common::document_symbol::Previous {
+                    content,
+                    tree: old_tree,
+                    symbols: &symbols,
+                };
+                crate::provider::common::document_symbol_incremental(text, tree, uri.clone(), previous, edits).await?
+            },
+            _ => crate::provider::common::document_symbol_from_uri(text, tree, uri.clone()).await?,
+        };
+        let symbols = future::ready(Arc::new(symbols)).eager();
+        self.document_symbols.insert(uri.clone(), symbols);
+        Ok(())
+    }
+
     pub fn remove_document(&self, uri: &lsp::Url) -> anyhow::Result<()> {
         // delete document_workspaces entry
         self.document_workspaces.remove(uri);
@@ -164,10 +210,127 @@ impl Session {
         let result = self.document_symbols.remove(uri);
         debug_assert!(result.is_some());
 
+        // delete document_semantic_tokens entry (if tokens were ever requested)
+        self.document_semantic_tokens.remove(uri);
+
         Ok(())
     }
 }
 
+impl Session {
+    /// Collect the uris of all documents known to the session, i.e., those indexed for the
+    /// workspace folders along with any other open documents.
+    pub fn document_uris(&self) -> Vec<lsp::Url> {
+        let mut uris = self
+            .workspace_documents
+            .iter()
+            .flat_map(|entry| entry.value().iter().map(|uri| uri.key().clone()).collect::<Vec<_>>())
+            .chain(self.document_states.iter().map(|entry| entry.key().clone()))
+            .collect::<Vec<_>>();
+        uris.sort();
+        uris.dedup();
+        uris
+    }
+}
+
+impl Session {
+    /// Find the main module of the program associated (by the [`Config`](crate::core::Config)) with
+    /// a ".dat" document. Relative paths are resolved against the enclosing workspace folder.
+    pub async fn program(&self, uri: &lsp::Url) -> Option<lsp::Url> {
+        let path = uri.to_file_path().ok()?;
+        let base = self
+            .workspace_documents
+            .iter()
+            .filter_map(|entry| entry.key().uri().to_file_path().ok())
+            .filter(|folder| path.starts_with(folder))
+            .max_by_key(|folder| folder.components().count())
+            .or_else(|| path.parent().map(Into::into))?;
+        let program = self.config.read().await.program(&base, &path)?;
+        lsp::Url::from_file_path(program).ok()
+    }
+}
+
+impl Session {
+    /// Open the main module of a program from the file system (unless it is known already), along
+    /// with every module it (transitively) imports, and return the uris of the modules of the
+    /// program. The imports of its modules are resolved against the directory of the main module.
+    pub async fn insert_program(&self, main: &lsp::Url) -> anyhow::Result<Vec<lsp::Url>> {
+        let path = main
+            .to_file_path()
+            .map_err(|()| anyhow::anyhow!("could not convert uri to path: {:#?}", main))?;
+        if let Some(root) = path.parent() {
+            self.program_roots.insert(main.clone(), root.into());
+        }
+        if !self.document_texts.contains_key(main) {
+            let document = crate::core::Document::open_from_uri(main.clone())?;
+            self.insert_document(None, document).await?;
+        }
+        self.insert_imports(main).await
+    }
+
+    /// Open the modules (transitively) imported by a module which aren't known yet, e.g., those of
+    /// the library directories, from the file system and return the uris of the modules of the
+    /// program rooted at the module. If the module belongs to a known program, so do its imports.
+    pub async fn insert_imports(&self, uri: &lsp::Url) -> anyhow::Result<Vec<lsp::Url>> {
+        let root = self.program_roots.get(uri).map(|root| root.value().clone());
+        loop {
+            let modules = crate::analysis::symbol::resolve::Resolver::new(self).program(uri).await;
+            // the imports of the modules are resolved again (against the program root) if it changed
+            let mut changed = false;
+            if let Some(root) = &root {
+                for module in modules
+                    .iter()
+                    .filter(|module| !self.program_roots.contains_key(*module))
+                {
+                    self.program_roots.insert(module.clone(), root.clone());
+                    changed = true;
+                }
+            }
+            for module in modules
+                .iter()
+                .filter(|module| !self.document_texts.contains_key(*module))
+            {
+                // modules which can't be read are skipped (and reported as unresolved imports)
+                if let Ok(document) = crate::core::Document::open_from_uri(module.clone()) {
+                    self.insert_document(None, document).await?;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Ok(modules);
+            }
+        }
+    }
+
+    /// The directories against which the imports of the module at `uri` are resolved, in order of
+    /// precedence: the program root, followed by the library directories (see
+    /// [`library_roots`](crate::core::Config::library_roots)). Unless the module belongs to a known
+    /// program (see `insert_program`), the directory of the module and each enclosing one within
+    /// its workspace folder are candidates for the program root (innermost first).
+    pub async fn import_roots(&self, uri: &lsp::Url) -> Vec<std::path::PathBuf> {
+        let path = match uri.to_file_path() {
+            Ok(path) => path,
+            Err(()) => return vec![],
+        };
+        let folder = self
+            .workspace_folder(uri)
+            .and_then(|folder| folder.uri().to_file_path().ok());
+        let directories = path.ancestors().skip(1);
+        let program_root = self.program_roots.get(uri).map(|root| root.value().clone());
+        let mut roots = match (program_root, &folder) {
+            (Some(root), _) => vec![root],
+            (None, Some(folder)) => directories
+                .take_while(|directory| directory.starts_with(folder))
+                .map(Into::into)
+                .collect(),
+            (None, None) => directories.take(1).map(Into::into).collect::<Vec<_>>(),
+        };
+        let base = folder.or_else(|| roots.first().cloned()).unwrap_or_default();
+        roots.extend(self.config.read().await.library_roots(&base));
+        roots
+    }
+}
+
 impl Session {
     pub async fn semantic_tokens_legend(&self) -> Option<lsp::SemanticTokensLegend> {
         let capabilities = self.server_capabilities.read().await;
@@ -256,11 +419,100 @@ impl Session {
         Ok(())
     }
 
-    // FIXME: needs to do additional clean up work to reverse insert_workspace_folders
-    pub fn remove_workspace_folders(&self, workspace_folders: Vec<lsp::WorkspaceFolder>) {
+    /// Re-read a (closed) document from disk, or open it if it isn't known yet, associating it with
+    /// the innermost workspace folder which contains it (if any). The contents of open documents
+    /// are managed by the client, so only their association is updated.
+    pub async fn reload_document(&self, uri: &lsp::Url) -> anyhow::Result<()> {
+        let workspace_folder = self.workspace_folder(uri);
+        if let Some(workspace_folder) = &workspace_folder {
+            if let Some(documents) = self.workspace_documents.get(workspace_folder) {
+                documents.insert(uri.clone());
+            }
+        }
+
+        let state = self.document_states.get(uri).map(|state| *state.value());
+        if Some(crate::core::DocumentState::Opened) == state {
+            if let Some(workspace_folder) = workspace_folder {
+                self.document_workspaces.insert(uri.clone(), workspace_folder);
+            }
+            return Ok(());
+        }
+        if state.is_some() {
+            self.remove_document(uri)?;
+        }
+        let document = crate::core::Document::open_from_uri(uri.clone())?;
+        self.insert_document(workspace_folder, document).await
+    }
+
+    /// Forget a document which was deleted from disk. Open documents are kept (until they are
+    /// closed), but are no longer associated with a workspace folder.
+    pub fn forget_document(&self, uri: &lsp::Url) -> anyhow::Result<()> {
+        for documents in self.workspace_documents.iter() {
+            documents.value().remove(uri);
+        }
+        let state = self.document_states.get(uri).map(|state| *state.value());
+        match state {
+            Some(crate::core::DocumentState::Opened) => {
+                self.document_workspaces.remove(uri);
+            },
+            Some(crate::core::DocumentState::Closed) => self.remove_document(uri)?,
+            None => {},
+        }
+        Ok(())
+    }
+
+    // The innermost workspace folder which contains a document (if any).
+    fn workspace_folder(&self, uri: &lsp::Url) -> Option<crate::core::WorkspaceFolder> {
+        let path = uri.to_file_path().ok()?;
+        self.workspace_documents
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|folder| matches!(folder.uri().to_file_path(), Ok(folder) if path.starts_with(&folder)))
+            .max_by_key(|folder| folder.uri().path().len())
+    }
+
+    /// Remove workspace folders (reversing `insert_workspace_folders`), i.e., release every
+    /// resource of their documents and clear their diagnostics. Open documents are kept (until they
+    /// are closed), and documents within another workspace folder are associated with that one.
+    ///
+    /// Returns the uris of the documents which were removed, whose dependents need to be diagnosed
+    /// again (see `handler::text_document::publish_dependent_diagnostics`).
+    pub async fn remove_workspace_folders(
+        &self,
+        workspace_folders: Vec<lsp::WorkspaceFolder>,
+    ) -> anyhow::Result<Vec<lsp::Url>> {
+        let mut removed = vec![];
         for folder in workspace_folders {
-            self.workspace_documents.remove(&crate::core::WorkspaceFolder(folder));
+            let documents = match self.workspace_documents.remove(&crate::core::WorkspaceFolder(folder)) {
+                Some((_, documents)) => documents,
+                None => continue,
+            };
+            let uris = documents.iter().map(|uri| uri.key().clone()).collect::<Vec<_>>();
+            for uri in uris {
+                if let Some(workspace_folder) = self.workspace_folder(&uri) {
+                    if let Some(documents) = self.workspace_documents.get(&workspace_folder) {
+                        documents.insert(uri.clone());
+                    }
+                    self.document_workspaces.insert(uri, workspace_folder);
+                    continue;
+                }
+                let state = self.document_states.get(&uri).map(|state| *state.value());
+                match state {
+                    Some(crate::core::DocumentState::Opened) => {
+                        self.document_workspaces.remove(&uri);
+                    },
+                    Some(crate::core::DocumentState::Closed) => {
+                        self.remove_document(&uri)?;
+                        if let Ok(client) = self.client() {
+                            client.publish_diagnostics(uri.clone(), vec![], None).await;
+                        }
+                        removed.push(uri);
+                    },
+                    None => {},
+                }
+            }
         }
+        Ok(removed)
     }
 
     async fn collect_workspace_document_uris(&self, workspace_folder: &lsp::WorkspaceFolder) -> DashSet<lsp::Url> {
@@ -274,3 +526,76 @@ impl Session {
             .await
     }
 }
+
+#[cfg(test)]
+impl Session {
+    /// Insert a document with the given text at the given (absolute) path.
+    pub(crate) async fn insert_text(&self, path: &str, text: &str) -> lsp::Url {
+        let uri = lsp::Url::from_file_path(path).unwrap();
+        let language_id = if path.ends_with(".dat") {
+            "ddlog.dat"
+        } else {
+            "ddlog.dl"
+        };
+        let params = lsp::DidOpenTextDocumentParams {
+            text_document: lsp::TextDocumentItem::new(uri.clone(), language_id.into(), 0, text.into()),
+        };
+        let document = crate::core::Document::open_from_lsp(params).unwrap();
+        self.insert_document(None, document).await.unwrap();
+        uri
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn imports_resolve_against_the_program_root() {
+        let session = Session::new(None).unwrap();
+        session.config.write().await.ddlog_home = Some(PathBuf::from("/ddlog"));
+
+        let module = lsp::Url::from_file_path("/program/a/b.dl").unwrap();
+        session.program_roots.insert(module.clone(), PathBuf::from("/program"));
+        let roots = session.import_roots(&module).await;
+        assert_eq!(vec![PathBuf::from("/program"), PathBuf::from("/ddlog/lib")], roots);
+
+        // without a known program (or workspace folder), only the directory of the module is tried
+        let other = lsp::Url::from_file_path("/other/a/b.dl").unwrap();
+        let roots = session.import_roots(&other).await;
+        assert_eq!(vec![PathBuf::from("/other/a"), PathBuf::from("/ddlog/lib")], roots);
+    }
+
+    #[tokio::test]
+    async fn removing_a_workspace_folder_removes_its_closed_documents() {
+        let session = Session::new(None).unwrap();
+        let folder = lsp::WorkspaceFolder {
+            uri: lsp::Url::from_directory_path("/workspace").unwrap(),
+            name: String::from("workspace"),
+        };
+        session
+            .workspace_documents
+            .insert(crate::core::WorkspaceFolder(folder.clone()), DashSet::default());
+
+        let closed = session
+            .insert_text("/workspace/lib.dl", "input relation R(x: u32)\n")
+            .await;
+        let opened = session.insert_text("/workspace/main.dl", "import lib\n").await;
+        session
+            .document_states
+            .insert(opened.clone(), crate::core::DocumentState::Opened);
+        for uri in [&closed, &opened] {
+            let documents = session
+                .workspace_documents
+                .get(&crate::core::WorkspaceFolder(folder.clone()));
+            documents.unwrap().insert(uri.clone());
+        }
+
+        let removed = session.remove_workspace_folders(vec![folder]).await.unwrap();
+        assert_eq!(vec![closed.clone()], removed);
+        assert!(!session.document_states.contains_key(&closed));
+        assert!(session.document_states.contains_key(&opened));
+        assert!(session.workspace_documents.is_empty());
+    }
+}
@@ -49,9 +49,7 @@ impl std::cmp::PartialEq for WorkspaceFolder {
 
 impl std::cmp::PartialOrd for WorkspaceFolder {
     fn partial_cmp(&self, that: &Self) -> Option<std::cmp::Ordering> {
-        let this = self.0.uri.as_str();
-        let that = that.0.uri.as_str();
-        std::cmp::PartialOrd::partial_cmp(this, that)
+        Some(std::cmp::Ord::cmp(self, that))
     }
 }
 
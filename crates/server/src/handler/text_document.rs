@@ -3,6 +3,13 @@ use futures::future;
 use lsp_text::RopeExt;
 use std::sync::Arc;
 
+pub async fn incoming_calls(
+    session: Arc<crate::core::Session>,
+    params: lsp::CallHierarchyIncomingCallsParams,
+) -> anyhow::Result<Option<Vec<lsp::CallHierarchyIncomingCall>>> {
+    crate::provider::text_document::incoming_calls(session, params).await
+}
+
 pub async fn definition(
     session: Arc<crate::core::Session>,
     params: lsp::GotoDefinitionParams,
@@ -15,29 +22,54 @@ pub async fn did_change(
     params: lsp::DidChangeTextDocumentParams,
 ) -> anyhow::Result<()> {
     let uri = &params.text_document.uri;
-    let mut text = session.get_mut_text(uri).await?;
-    let mut content = text.get_content().await?;
 
-    let edits = params
-        .content_changes
-        .iter()
-        .map(|change| content.build_edit(change))
-        .collect::<Result<Vec<_>, _>>()?;
+    let (text, content, tree, previous, edits) = {
+        let mut text = session.get_mut_text(uri).await?;
+        let mut content = text.get_content().await?;
 
-    for edit in &edits {
-        content.apply_edit(edit);
-    }
+        // the previous content and tree, from which the symbols are updated
+        let old_content = content.clone();
+        let old_tree = match session.get_tree(uri).await?.clone().await {
+            Some(tree) => Some(tree.lock().await.clone()),
+            None => None,
+        };
+
+        let edits = params
+            .content_changes
+            .iter()
+            .map(|change| content.build_edit(change))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for edit in &edits {
+            content.apply_edit(edit);
+        }
+
+        let tree = crate::core::Document::change(session.clone(), uri, &content, &edits).await?;
+        text.set_content(future::ready(content.clone()).eager());
+
+        // NOTE: the text must be released before computing diagnostics (which read it again)
+        (text.clone(), content, tree, (old_content, old_tree), edits)
+    };
+
+    if let Some(tree) = tree {
+        let previous = (&previous.0, previous.1.as_ref());
+        session
+            .update_document_symbols(uri, text.clone(), &tree, previous, &edits)
+            .await?;
+        insert_modules(&session, uri, text.language).await?;
 
-    if let Some(tree) = crate::core::Document::change(session.clone(), uri, &content, &edits).await? {
-        let diagnostics = crate::provider::text_document::diagnostics(&tree, uri, text.language, &content);
+        let mut diagnostics = crate::provider::text_document::diagnostics(&tree, uri, text.language, &content);
+        diagnostics.extend(crate::provider::text_document::semantic_diagnostics(&session, uri).await?);
         let version = Default::default();
         session
             .client()?
             .publish_diagnostics(uri.clone(), diagnostics, version)
             .await;
-    }
 
-    text.set_content(future::ready(content).eager());
+        if crate::core::Language::DDlogDl == text.language {
+            publish_dependent_diagnostics(session.clone(), std::slice::from_ref(uri)).await?;
+        }
+    }
 
     Ok(())
 }
@@ -68,9 +100,12 @@ pub async fn did_open(
 ) -> anyhow::Result<()> {
     if session.document_trees.contains_key(&params.text_document.uri) {
         let uri = params.text_document.uri.clone();
-        let result = session.document_states.insert(uri, crate::core::DocumentState::Opened);
+        let result = session
+            .document_states
+            .insert(uri.clone(), crate::core::DocumentState::Opened);
         debug_assert!(matches!(result, Some(crate::core::DocumentState::Closed)));
-        return Ok(());
+        let language = session.get_text(&uri).await?.language;
+        return insert_modules(&session, &uri, language).await;
     }
 
     let workspace_folder = None;
@@ -87,19 +122,205 @@ pub async fn did_open(
         .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
 
     session.insert_document(workspace_folder, document).await?;
-    let diagnostics = {
+    insert_modules(&session, &uri, text.language).await?;
+    let mut diagnostics = {
         let tree = tree.lock().await;
         crate::provider::text_document::diagnostics(&tree, &uri, text.language, &content)
     };
+    diagnostics.extend(crate::provider::text_document::semantic_diagnostics(&session, &uri).await?);
     let version = Default::default();
     session.client()?.publish_diagnostics(uri, diagnostics, version).await;
 
     Ok(())
 }
 
+/// Open the modules which names in a document may refer to (unless they are known already), i.e.,
+/// the imports of a ".dl" module, or the program associated with a ".dat" file.
+pub async fn insert_modules(
+    session: &crate::core::Session,
+    uri: &lsp::Url,
+    language: crate::core::Language,
+) -> anyhow::Result<()> {
+    match language {
+        crate::core::Language::DDlogDl => {
+            session.insert_imports(uri).await?;
+        },
+        crate::core::Language::DDlogDat => {
+            if let Some(main) = session.program(uri).await {
+                // a program whose main module can't be read is treated like an unknown one
+                session.insert_program(&main).await.ok();
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Compute the (syntactic and semantic) diagnostics for a document and publish them.
+pub async fn publish_diagnostics(session: Arc<crate::core::Session>, uri: &lsp::Url) -> anyhow::Result<()> {
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let tree = session
+        .get_tree(uri)
+        .await?
+        .clone()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+    let mut diagnostics = {
+        let tree = tree.lock().await;
+        crate::provider::text_document::diagnostics(&tree, uri, text.language, &content)
+    };
+    diagnostics.extend(crate::provider::text_document::semantic_diagnostics(&session, uri).await?);
+    let version = Default::default();
+    session
+        .client()?
+        .publish_diagnostics(uri.clone(), diagnostics, version)
+        .await;
+    Ok(())
+}
+
+/// Compute and publish the diagnostics of the open documents (other than the given ones) which may
+/// refer to the declarations of the given modules.
+pub async fn publish_dependent_diagnostics(
+    session: Arc<crate::core::Session>,
+    uris: &[lsp::Url],
+) -> anyhow::Result<()> {
+    let opened = session
+        .document_states
+        .iter()
+        .filter(|entry| crate::core::DocumentState::Opened == *entry.value() && !uris.contains(entry.key()))
+        .map(|entry| entry.key().clone())
+        .collect::<Vec<_>>();
+    let mut dependents = vec![];
+    let mut resolver = crate::analysis::symbol::resolve::Resolver::new(&session);
+    for other in opened {
+        for uri in uris {
+            if resolver.depends_on(&other, uri).await {
+                dependents.push(other);
+                break;
+            }
+        }
+    }
+    for dependent in dependents {
+        publish_diagnostics(session.clone(), &dependent).await?;
+    }
+    Ok(())
+}
+
+pub async fn did_save(
+    session: Arc<crate::core::Session>,
+    params: lsp::DidSaveTextDocumentParams,
+) -> anyhow::Result<()> {
+    let uri = params.text_document.uri;
+    // the document may have been created by saving it, i.e., it may be new to its workspace folder
+    session.reload_document(&uri).await?;
+    publish_dependent_diagnostics(session, &[uri]).await
+}
+
 pub async fn document_symbol(
     session: Arc<crate::core::Session>,
     params: lsp::DocumentSymbolParams,
 ) -> anyhow::Result<Option<lsp::DocumentSymbolResponse>> {
     crate::provider::text_document::document_symbol(session, params).await
 }
+
+pub async fn formatting(
+    session: Arc<crate::core::Session>,
+    params: lsp::DocumentFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp::TextEdit>>> {
+    crate::provider::text_document::formatting(session, params).await
+}
+
+pub async fn range_formatting(
+    session: Arc<crate::core::Session>,
+    params: lsp::DocumentRangeFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp::TextEdit>>> {
+    crate::provider::text_document::range_formatting(session, params).await
+}
+
+pub async fn completion(
+    session: Arc<crate::core::Session>,
+    params: lsp::CompletionParams,
+) -> anyhow::Result<Option<lsp::CompletionResponse>> {
+    crate::provider::text_document::completion(session, params).await
+}
+
+pub async fn hover(session: Arc<crate::core::Session>, params: lsp::HoverParams) -> anyhow::Result<Option<lsp::Hover>> {
+    crate::provider::text_document::hover(session, params).await
+}
+
+pub async fn outgoing_calls(
+    session: Arc<crate::core::Session>,
+    params: lsp::CallHierarchyOutgoingCallsParams,
+) -> anyhow::Result<Option<Vec<lsp::CallHierarchyOutgoingCall>>> {
+    crate::provider::text_document::outgoing_calls(session, params).await
+}
+
+pub async fn prepare_type_hierarchy(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    crate::provider::text_document::prepare_type_hierarchy(session, params).await
+}
+
+pub async fn prepare_call_hierarchy(
+    session: Arc<crate::core::Session>,
+    params: lsp::CallHierarchyPrepareParams,
+) -> anyhow::Result<Option<Vec<lsp::CallHierarchyItem>>> {
+    crate::provider::text_document::prepare_call_hierarchy(session, params).await
+}
+
+pub async fn references(
+    session: Arc<crate::core::Session>,
+    params: lsp::ReferenceParams,
+) -> anyhow::Result<Option<Vec<lsp::Location>>> {
+    crate::provider::text_document::references(session, params).await
+}
+
+pub async fn prepare_rename(
+    session: Arc<crate::core::Session>,
+    params: lsp::TextDocumentPositionParams,
+) -> anyhow::Result<Option<lsp::PrepareRenameResponse>> {
+    crate::provider::text_document::prepare_rename(session, params).await
+}
+
+pub async fn rename(
+    session: Arc<crate::core::Session>,
+    params: lsp::RenameParams,
+) -> anyhow::Result<Option<lsp::WorkspaceEdit>> {
+    crate::provider::text_document::rename(session, params).await
+}
+
+pub async fn semantic_tokens_full(
+    session: Arc<crate::core::Session>,
+    params: lsp::SemanticTokensParams,
+) -> anyhow::Result<Option<lsp::SemanticTokensResult>> {
+    crate::provider::text_document::semantic_tokens_full(session, params).await
+}
+
+pub async fn semantic_tokens_full_delta(
+    session: Arc<crate::core::Session>,
+    params: lsp::SemanticTokensDeltaParams,
+) -> anyhow::Result<Option<lsp::SemanticTokensFullDeltaResult>> {
+    crate::provider::text_document::semantic_tokens_full_delta(session, params).await
+}
+
+pub async fn subtypes(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    crate::provider::text_document::subtypes(session, params).await
+}
+
+pub async fn supertypes(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    crate::provider::text_document::supertypes(session, params).await
+}
+
+pub async fn type_definition(
+    session: Arc<crate::core::Session>,
+    params: lsp::request::GotoTypeDefinitionParams,
+) -> anyhow::Result<Option<lsp::request::GotoTypeDefinitionResponse>> {
+    crate::provider::text_document::type_definition(session, params).await
+}
@@ -1,12 +1,86 @@
-use std::sync::Arc;
+use std::{convert::TryFrom, sync::Arc};
+
+// How often the workspace folders are scanned for changes, if the client can't watch them.
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+pub async fn dependency_graph(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    crate::provider::workspace::dependency_graph(session, params).await
+}
+
+pub async fn did_change_configuration(
+    session: Arc<crate::core::Session>,
+    params: lsp::DidChangeConfigurationParams,
+) -> anyhow::Result<()> {
+    let config = crate::core::Config::from_json(&params.settings);
+    *session.config.write().await = config;
+    // the program associations determine the diagnostics of ".dat" documents, and the library
+    // directories those of ".dl" documents
+    let uris = session
+        .document_states
+        .iter()
+        .filter(|entry| crate::core::DocumentState::Opened == *entry.value())
+        .map(|entry| entry.key().clone())
+        .collect::<Vec<_>>();
+    for uri in uris {
+        let language = session.get_text(&uri).await?.language;
+        crate::handler::text_document::insert_modules(&session, &uri, language).await?;
+        crate::handler::text_document::publish_diagnostics(session.clone(), &uri).await?;
+    }
+    Ok(())
+}
+
+pub async fn did_change_watched_files(
+    session: Arc<crate::core::Session>,
+    params: lsp::DidChangeWatchedFilesParams,
+) -> anyhow::Result<()> {
+    let mut uris = vec![];
+    for event in params.changes {
+        // only ".dl" and ".dat" files are documents
+        let path = match event.uri.to_file_path() {
+            Ok(path) => path,
+            Err(()) => continue,
+        };
+        if crate::core::Language::try_from(path.as_path()).is_err() {
+            continue;
+        }
+        let result = if lsp::FileChangeType::DELETED == event.typ {
+            session.forget_document(&event.uri)
+        } else if path.is_file() {
+            session.reload_document(&event.uri).await
+        } else {
+            Ok(())
+        };
+        // a document that failed to update shouldn't hold back the rest of the changes
+        if let Err(error) = result {
+            if let Ok(client) = session.client() {
+                let message = format!("failed to update document {}: {:#}", event.uri, error);
+                client.log_message(lsp::MessageType::ERROR, message).await;
+            }
+            continue;
+        }
+        uris.push(event.uri);
+    }
+    crate::handler::text_document::publish_dependent_diagnostics(session, &uris).await
+}
 
 pub async fn did_change_workspace_folders(
     session: Arc<crate::core::Session>,
     params: lsp::DidChangeWorkspaceFoldersParams,
 ) -> anyhow::Result<()> {
-    session.remove_workspace_folders(params.event.removed);
+    let removed = session.remove_workspace_folders(params.event.removed).await?;
     session.insert_workspace_folders(params.event.added).await?;
-    Ok(())
+    // open documents elsewhere may have referred to the declarations of the removed documents
+    crate::handler::text_document::publish_dependent_diagnostics(session, &removed).await
+}
+
+pub async fn import_graph(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    crate::provider::workspace::import_graph(session, params).await
 }
 
 pub async fn symbol(
@@ -15,3 +89,69 @@ pub async fn symbol(
 ) -> anyhow::Result<Option<Vec<lsp::SymbolInformation>>> {
     crate::provider::workspace::symbol(session, params).await
 }
+
+/// Watch the documents of the workspace folders for changes on disk, i.e., register for
+/// "workspace/didChangeWatchedFiles" if the client supports it, and otherwise scan the workspace
+/// folders periodically.
+pub async fn watch_files(session: Arc<crate::core::Session>) -> anyhow::Result<()> {
+    let dynamic_registration = session
+        .client_capabilities
+        .read()
+        .await
+        .as_ref()
+        .and_then(|capabilities| capabilities.workspace.as_ref())
+        .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+        .and_then(|capability| capability.dynamic_registration)
+        .unwrap_or_default();
+    if dynamic_registration {
+        let registration = lsp::Registration {
+            id: String::from("ddlog/watchedFiles"),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: Some(serde_json::json!({
+                "watchers": [{ "globPattern": "**/*.{dl,dat}" }],
+            })),
+        };
+        if session.client()?.register_capability(vec![registration]).await.is_ok() {
+            return Ok(());
+        }
+    }
+    tokio::spawn(scan_files(session));
+    Ok(())
+}
+
+// Scan the workspace folders for changes to their documents periodically (see `watch_files`).
+async fn scan_files(session: Arc<crate::core::Session>) {
+    let folders = |session: &crate::core::Session| {
+        let folders = session.workspace_documents.iter();
+        let folders = folders.filter_map(|entry| entry.key().uri().to_file_path().ok());
+        folders.collect::<Vec<_>>()
+    };
+    let mut scanned = folders(&session);
+    let mut times = crate::analysis::fs::modification_times(scanned.clone()).await;
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        // workspace folders may have been added or removed since the last scan, and their documents
+        // were already loaded or forgotten along with them
+        let current = folders(&session);
+        let new_times = crate::analysis::fs::modification_times(current.clone()).await;
+        times.retain(|path, _| current.iter().any(|folder| path.starts_with(folder)));
+        for (path, time) in &new_times {
+            if !scanned.iter().any(|folder| path.starts_with(folder)) {
+                times.insert(path.clone(), *time);
+            }
+        }
+        scanned = current;
+        let changes = crate::analysis::fs::file_events(&times, &new_times);
+        times = new_times;
+        if changes.is_empty() {
+            continue;
+        }
+        let params = lsp::DidChangeWatchedFilesParams { changes };
+        if let Err(error) = did_change_watched_files(session.clone(), params).await {
+            if let Ok(client) = session.client() {
+                let message = format!("failed to update changed documents: {:#}", error);
+                client.log_message(lsp::MessageType::ERROR, message).await;
+            }
+        }
+    }
+}
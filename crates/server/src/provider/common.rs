@@ -1,3 +1,4 @@
 pub mod document_symbol;
+pub mod formatting;
 
-pub use document_symbol::{document_symbol, document_symbol_from_uri};
+pub use document_symbol::{document_symbol, document_symbol_from_uri, document_symbol_incremental};
@@ -1,15 +1,17 @@
 pub mod dat;
 pub mod dl;
 
+/// Compute the symbols of a document which are indexed by the session (for workspace symbols and
+/// definitions). The commands of ".dat" documents don't declare anything, so their outline is only
+/// provided by "textDocument/documentSymbol" rather than indexed.
 pub async fn document_symbol(
     text: crate::core::Text,
     tree: &tree_sitter::Tree,
     params: lsp::DocumentSymbolParams,
 ) -> anyhow::Result<Vec<lsp::SymbolInformation>> {
-    // let text = session.get_text(&params.text_document.uri).await?;
     let content = text.get_content().await?;
     let response = match text.language {
-        crate::core::Language::DDlogDat => self::dat::document_symbol(&content, tree, params).await?,
+        crate::core::Language::DDlogDat => vec![],
         crate::core::Language::DDlogDl => self::dl::document_symbol(&content, tree, params).await?,
     };
     Ok(response)
@@ -27,3 +29,28 @@ pub async fn document_symbol_from_uri(
     };
     document_symbol(text, tree, params).await
 }
+
+/// The state of a document before it was edited, from which its symbols are updated incrementally.
+pub struct Previous<'a> {
+    pub content: &'a ropey::Rope,
+    pub tree: &'a tree_sitter::Tree,
+    pub symbols: &'a [lsp::SymbolInformation],
+}
+
+/// Update the symbols of a document after it was edited, recomputing only those of the items
+/// affected by the edits (for ".dl" documents, whereas ".dat" documents have none).
+pub async fn document_symbol_incremental(
+    text: crate::core::Text,
+    tree: &tree_sitter::Tree,
+    uri: lsp::Url,
+    previous: Previous<'_>,
+    edits: &[lsp_text::TextEdit<'_>],
+) -> anyhow::Result<Vec<lsp::SymbolInformation>> {
+    match text.language {
+        crate::core::Language::DDlogDat => document_symbol_from_uri(text, tree, uri).await,
+        crate::core::Language::DDlogDl => {
+            let content = text.get_content().await?;
+            self::dl::document_symbol_incremental(&content, tree, &uri, previous, edits).await
+        },
+    }
+}
@@ -1,7 +1,254 @@
+use crate::{analysis::tree::first_of_kind, core::language::dat};
+use lsp_text::RopeExt;
+
+// Document symbol provider definitions for ".dat" files.
 pub async fn document_symbol(
-    _content: &ropey::Rope,
-    _tree: &tree_sitter::Tree,
-    _params: lsp::DocumentSymbolParams,
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+    params: lsp::DocumentSymbolParams,
 ) -> anyhow::Result<Vec<lsp::SymbolInformation>> {
-    Ok(vec![])
+    // The flat symbols describe the transactions and the commands, contained in their transaction.
+    let uri = &params.text_document.uri;
+    let mut syms = vec![];
+    for symbol in document_symbol_nested(content, tree).await? {
+        let container_name = Some(symbol.name.clone());
+        let children = symbol.children.clone().unwrap_or_default();
+        syms.push(information(uri, symbol, None));
+        for child in children {
+            syms.push(information(uri, child, container_name.clone()));
+        }
+    }
+    Ok(syms)
+}
+
+// Hierarchical document symbol provider definitions for ".dat" files.
+pub async fn document_symbol_nested(
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+) -> anyhow::Result<Vec<lsp::DocumentSymbol>> {
+    let mut syms = vec![];
+    // The runs of commands outside of any transaction (since the last one).
+    let mut runs = vec![];
+    // The transaction which has been started but not yet committed or rolled back.
+    let mut transaction: Option<Transaction> = None;
+    let mut transactions = 0;
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let commands = root
+        .named_children(&mut cursor)
+        .filter(|node| dat::kind::COMMAND == node.kind_id());
+    for command in commands {
+        let node = match command.named_child(0) {
+            Some(node) => node,
+            None => continue,
+        };
+        let range = content.tree_sitter_range_to_lsp_range(command.range());
+        match node.kind_id() {
+            dat::kind::START => {
+                // a `start` within a transaction is an error, so just close the current one
+                syms.extend(transaction.take().map(|transaction| transaction.symbol(None)));
+                syms.extend(runs.drain(..).map(Run::symbol));
+                transactions += 1;
+                transaction = Some(Transaction {
+                    number: transactions,
+                    range,
+                    selection_range: range,
+                    runs: vec![],
+                });
+            },
+            dat::kind::COMMIT | dat::kind::ROLLBACK => {
+                if let Some(mut transaction) = transaction.take() {
+                    transaction.range.end = range.end;
+                    syms.push(transaction.symbol(Some(&node.kind())));
+                }
+            },
+            dat::kind::UPDATES => {
+                let mut cursor = node.walk();
+                let updates = node
+                    .named_children(&mut cursor)
+                    .filter(|update| dat::kind::UPDATE == update.kind_id())
+                    .filter_map(|update| update.named_child(0));
+                for update in updates {
+                    let run = Run::new(content, update, dat::kind::NAME_REL, lsp::SymbolKind::EVENT);
+                    push_run(&mut transaction, &mut runs, run);
+                }
+            },
+            dat::kind::DUMP => {
+                let run = Run::new(content, node, dat::kind::NAME_REL, lsp::SymbolKind::FUNCTION);
+                push_run(&mut transaction, &mut runs, run);
+            },
+            dat::kind::DUMP_INDEX | dat::kind::QUERY_INDEX => {
+                let run = Run::new(content, node, dat::kind::NAME_INDEX, lsp::SymbolKind::FUNCTION);
+                push_run(&mut transaction, &mut runs, run);
+            },
+            _ => {},
+        }
+    }
+    syms.extend(transaction.map(|transaction| transaction.symbol(None)));
+    syms.extend(runs.into_iter().map(Run::symbol));
+
+    Ok(syms)
+}
+
+// A transaction, i.e., the commands between `start` and `commit` (or `rollback`).
+struct Transaction {
+    number: usize,
+    range: lsp::Range,
+    selection_range: lsp::Range,
+    runs: Vec<Run>,
+}
+
+impl Transaction {
+    // Construct the symbol for the transaction, given the command which ends it (if any).
+    fn symbol(self, end: Option<&str>) -> lsp::DocumentSymbol {
+        let children = self.runs.into_iter().map(Run::symbol).collect::<Vec<_>>();
+        #[allow(deprecated)]
+        lsp::DocumentSymbol {
+            name: format!("transaction {}", self.number),
+            detail: Some(end.unwrap_or("uncommitted").into()),
+            kind: lsp::SymbolKind::NAMESPACE,
+            tags: Default::default(),
+            deprecated: Default::default(),
+            range: self.range,
+            selection_range: self.selection_range,
+            children: Some(children).filter(|children| !children.is_empty()),
+        }
+    }
+}
+
+// A run of consecutive commands with the same operation and target, e.g., `insert R(..)` lines.
+struct Run {
+    label: String,
+    count: usize,
+    kind: lsp::SymbolKind,
+    range: lsp::Range,
+    selection_range: lsp::Range,
+}
+
+impl Run {
+    // Construct a run for a single command, targeting the first node of the given kind.
+    fn new(content: &ropey::Rope, node: tree_sitter::Node, target_kind: u16, kind: lsp::SymbolKind) -> Self {
+        let range = content.tree_sitter_range_to_lsp_range(node.range());
+        let label;
+        let selection_range;
+        if let Some(target) = first_of_kind(node, target_kind) {
+            label = format!("{} {}", node.kind(), content.utf8_text_for_tree_sitter_node(&target));
+            selection_range = content.tree_sitter_range_to_lsp_range(target.range());
+        } else {
+            label = node.kind().into();
+            selection_range = range;
+        }
+        Self {
+            label,
+            count: 1,
+            kind,
+            range,
+            selection_range,
+        }
+    }
+
+    fn symbol(self) -> lsp::DocumentSymbol {
+        let name = if 1 == self.count {
+            self.label
+        } else {
+            format!("{} ({})", self.label, self.count)
+        };
+        #[allow(deprecated)]
+        lsp::DocumentSymbol {
+            name,
+            detail: Default::default(),
+            kind: self.kind,
+            tags: Default::default(),
+            deprecated: Default::default(),
+            range: self.range,
+            selection_range: self.selection_range,
+            children: Default::default(),
+        }
+    }
+}
+
+// Push a run into the current transaction (or the top-level runs), merging it with the previous
+// run if they have the same operation and target.
+fn push_run(transaction: &mut Option<Transaction>, runs: &mut Vec<Run>, run: Run) {
+    let runs = match transaction {
+        Some(transaction) => {
+            transaction.range.end = run.range.end;
+            &mut transaction.runs
+        },
+        None => runs,
+    };
+    match runs.last_mut() {
+        Some(last) if last.label == run.label => {
+            last.count += 1;
+            last.range.end = run.range.end;
+        },
+        _ => runs.push(run),
+    }
+}
+
+// Convert a document symbol to the (flat) symbol information.
+fn information(uri: &lsp::Url, symbol: lsp::DocumentSymbol, container_name: Option<String>) -> lsp::SymbolInformation {
+    #[allow(deprecated)]
+    lsp::SymbolInformation {
+        name: symbol.name,
+        kind: symbol.kind,
+        tags: Default::default(),
+        deprecated: Default::default(),
+        location: lsp::Location {
+            uri: uri.clone(),
+            range: symbol.range,
+        },
+        container_name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> lsp::Range {
+        lsp::Range::new(lsp::Position::new(start.0, start.1), lsp::Position::new(end.0, end.1))
+    }
+
+    // The outline of a document, by the names, ranges, and selection ranges of its symbols.
+    fn outline(symbols: Vec<lsp::DocumentSymbol>) -> Vec<(String, lsp::Range, lsp::Range, usize)> {
+        let mut outline = vec![];
+        let mut work = symbols.into_iter().map(|symbol| (symbol, 0)).rev().collect::<Vec<_>>();
+        while let Some((symbol, depth)) = work.pop() {
+            let children = symbol.children.unwrap_or_default().into_iter().rev();
+            work.extend(children.map(|child| (child, depth + 1)));
+            outline.push((symbol.name, symbol.range, symbol.selection_range, depth));
+        }
+        outline
+    }
+
+    #[tokio::test]
+    async fn commands_are_nested_within_their_transaction() {
+        let text = indoc::indoc! {"
+            insert R(0);
+            start;
+            insert R(1),
+            insert R(2);
+            dump R;
+            commit;
+            insert S(3);
+        "};
+        let mut parser = tree_sitter::Parser::try_from(crate::core::Language::DDlogDat).unwrap();
+        let tree = parser.parse(text, None).unwrap().unwrap();
+        let content = ropey::Rope::from(text);
+        let symbols = document_symbol_nested(&content, &tree).await.unwrap();
+        // the transaction is described by the command ending it
+        assert_eq!(Some("commit"), symbols[1].detail.as_deref());
+
+        let symbol = |name: &str, range, selection_range, depth| (String::from(name), range, selection_range, depth);
+        let expected = vec![
+            symbol("insert R", range((0, 0), (0, 11)), range((0, 7), (0, 8)), 0),
+            symbol("transaction 1", range((1, 0), (5, 7)), range((1, 0), (1, 6)), 0),
+            symbol("insert R (2)", range((2, 0), (3, 11)), range((2, 7), (2, 8)), 1),
+            symbol("dump R", range((4, 0), (4, 7)), range((4, 5), (4, 6)), 1),
+            symbol("insert S", range((6, 0), (6, 11)), range((6, 7), (6, 8)), 0),
+        ];
+        assert_eq!(expected, outline(symbols));
+    }
 }
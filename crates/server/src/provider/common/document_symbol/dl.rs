@@ -1,7 +1,12 @@
 use crate::{
-    core::language::dl,
-    provider::text_document::document_symbol::{symbol_range, Data, SymbolRange, Work},
+    core::language::{dl, dl::symbol},
+    provider::{
+        common::document_symbol::Previous,
+        text_document::document_symbol::{symbol_range, Data, SymbolRange, Work},
+    },
 };
+use lsp_text::{RopeExt, TextEdit};
+use std::collections::HashMap;
 
 // Document symbol provider definitions for ".dl" files.
 pub async fn document_symbol(
@@ -9,36 +14,226 @@ pub async fn document_symbol(
     tree: &tree_sitter::Tree,
     params: lsp::DocumentSymbolParams,
 ) -> anyhow::Result<Vec<lsp::SymbolInformation>> {
+    // The flat symbols only describe the top-level declarations. They are indexed for workspace
+    // symbols and definitions, so rules are only part of the hierarchical symbols.
+    let uri = &params.text_document.uri;
+    let syms = symbols(content, tree.root_node())?
+        .into_iter()
+        .filter_map(|symbol| information(uri, symbol))
+        .collect();
+    Ok(syms)
+}
+
+// Update the (flat) symbols of a ".dl" document after it was edited, where only the symbols of the
+// items affected by the edits are recomputed, and the symbols of the other items are moved along.
+pub async fn document_symbol_incremental(
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+    uri: &lsp::Url,
+    previous: Previous<'_>,
+    edits: &[TextEdit<'_>],
+) -> anyhow::Result<Vec<lsp::SymbolInformation>> {
+    // the previous items, keyed by their (character) extent
+    let old_root = previous.tree.root_node();
+    let mut cursor = old_root.walk();
+    let old_items = old_root
+        .children(&mut cursor)
+        .map(|item| {
+            let start = previous.content.byte_to_char(item.start_byte() as usize);
+            let end = previous.content.byte_to_char(item.end_byte() as usize);
+            ((start, end), item)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut syms = vec![];
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for item in root.children(&mut cursor) {
+        let start = content.byte_to_char(item.start_byte() as usize);
+        let end = content.byte_to_char(item.end_byte() as usize);
+        let old_item = unedited(edits, start, end)
+            .and_then(|extent| old_items.get(&extent))
+            .filter(|old_item| old_item.kind_id() == item.kind_id() && !item.has_error() && !old_item.has_error());
+        if let Some(old_item) = old_item {
+            let old_range = previous.content.tree_sitter_range_to_lsp_range(old_item.range());
+            let new_start = content.byte_to_lsp_position(item.start_byte() as usize);
+            let moved = previous
+                .symbols
+                .iter()
+                .filter(|symbol| contains(&old_range, &symbol.location.range))
+                .map(|symbol| {
+                    let mut symbol = symbol.clone();
+                    symbol.location.uri = uri.clone();
+                    symbol.location.range.start = moved(old_range.start, new_start, symbol.location.range.start);
+                    symbol.location.range.end = moved(old_range.start, new_start, symbol.location.range.end);
+                    symbol
+                });
+            syms.extend(moved);
+        } else {
+            let fresh = symbols(content, item)?
+                .into_iter()
+                .filter_map(|symbol| information(uri, symbol));
+            syms.extend(fresh);
+        }
+    }
+    Ok(syms)
+}
+
+// Map the (character) extent of an item after a sequence of edits back to its extent before the
+// edits, unless the item overlaps (or adjoins) an edit.
+fn unedited(edits: &[TextEdit<'_>], mut start: usize, mut end: usize) -> Option<(usize, usize)> {
+    for edit in edits.iter().rev() {
+        let inserted = edit.text.chars().count();
+        let removed = edit.end_char_idx - edit.start_char_idx;
+        if end < edit.start_char_idx {
+            continue;
+        }
+        if edit.start_char_idx + inserted < start {
+            start = start - inserted + removed;
+            end = end - inserted + removed;
+            continue;
+        }
+        return None;
+    }
+    Some((start, end))
+}
+
+fn contains(outer: &lsp::Range, inner: &lsp::Range) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+// Move a position within an (unchanged) item from the previous start of the item to its new start.
+fn moved(old_start: lsp::Position, new_start: lsp::Position, position: lsp::Position) -> lsp::Position {
+    if position.line == old_start.line {
+        lsp::Position::new(
+            new_start.line,
+            position.character - old_start.character + new_start.character,
+        )
+    } else {
+        lsp::Position::new(position.line - old_start.line + new_start.line, position.character)
+    }
+}
+
+// Construct the flat symbol for a top-level symbol, unless it is a rule or an import (which don't
+// declare anything).
+fn information(uri: &lsp::Url, symbol: lsp::DocumentSymbol) -> Option<lsp::SymbolInformation> {
+    if [lsp::SymbolKind::EVENT, lsp::SymbolKind::MODULE].contains(&symbol.kind) {
+        return None;
+    }
+    #[allow(deprecated)]
+    Some(lsp::SymbolInformation {
+        name: symbol.name,
+        kind: symbol.kind,
+        tags: Default::default(),
+        deprecated: Default::default(),
+        location: lsp::Location {
+            uri: uri.clone(),
+            range: symbol.range,
+        },
+        container_name: None,
+    })
+}
+
+// Hierarchical document symbol provider definitions for ".dl" files.
+pub async fn document_symbol_nested(
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+) -> anyhow::Result<Vec<lsp::DocumentSymbol>> {
+    Ok(group_rules(symbols(content, tree.root_node())?))
+}
+
+// Group the consecutive (top-level) rule symbols with the same head relation, so that the range of
+// each group covers only its own rules (rather than overlapping the items in between).
+fn group_rules(syms: Vec<lsp::DocumentSymbol>) -> Vec<lsp::DocumentSymbol> {
+    let mut result: Vec<lsp::DocumentSymbol> = vec![];
+    // the indices of the groups within the result
+    let mut groups: Vec<usize> = vec![];
+    for symbol in syms {
+        if lsp::SymbolKind::EVENT != symbol.kind {
+            result.push(symbol);
+            continue;
+        }
+        let relation = rule_relation(&symbol.name).to_owned();
+        let last_is_group = matches!(groups.last(), Some(&index) if index + 1 == result.len());
+        match result.last_mut() {
+            Some(group) if last_is_group && group.name == relation => {
+                group.range.end = symbol.range.end;
+                group.children.get_or_insert_with(Vec::new).push(symbol);
+                continue;
+            },
+            _ => {},
+        }
+        groups.push(result.len());
+        #[allow(deprecated)]
+        let group = lsp::DocumentSymbol {
+            name: relation,
+            detail: None,
+            kind: lsp::SymbolKind::EVENT,
+            tags: Default::default(),
+            deprecated: Default::default(),
+            range: symbol.range,
+            selection_range: symbol.selection_range,
+            children: Some(vec![symbol]),
+        };
+        result.push(group);
+    }
+    for index in groups {
+        let group = &mut result[index];
+        let count = group.children.as_ref().map_or(0, Vec::len);
+        group.detail = Some(format!("{} rule{}", count, if 1 == count { "" } else { "s" }));
+    }
+    result
+}
+
+// The relation of the (first) head atom of a rule symbol, e.g., `R` for `R(x), S(x)`.
+fn rule_relation(name: &str) -> &str {
+    let head = name.split(&['(', '[', ','][..]).next().unwrap_or(name);
+    head.trim().trim_start_matches('&')
+}
+
+// Compute the document symbols for ".dl" files (with every rule at the top level), or for a single
+// (annotated) item of them.
+fn symbols(content: &ropey::Rope, node: tree_sitter::Node) -> anyhow::Result<Vec<lsp::DocumentSymbol>> {
     // Vector to collect document symbols into as they are constructed.
-    let mut syms: Vec<lsp::SymbolInformation> = vec![];
+    let mut syms: Vec<lsp::DocumentSymbol> = vec![];
 
     // Prepare the stack machine:
     //   data: contains data for constructing upcoming DocumentSymbols
     //   work: contains remaining tree_sitter nodes to process
     let mut data: Vec<Data> = vec![];
-    let mut work: Vec<Work> = vec![Work::Node(tree.root_node())];
+    let mut work: Vec<Work> = vec![Work::Node(node)];
 
     // The stack machine work loop.
     while let Some(next) = work.pop() {
         match next {
-            // Construct a DocumentSymbol and pop data stack
+            // Construct a DocumentSymbol (from the symbols of its children) and pop data stack
             Work::Data => {
                 if let Some(Data {
-                    node, kind, name_hint, ..
+                    node,
+                    children_count,
+                    kind,
+                    name_hint,
                 }) = data.pop()
                 {
-                    let SymbolRange { name, range, .. } = symbol_range(content, node, name_hint, dl::field::IDENTIFIER);
+                    let SymbolRange {
+                        name,
+                        range,
+                        selection_range,
+                    } = symbol_range(content, node, name_hint, dl::field::IDENTIFIER);
+                    let (name, selection_range) = label(content, node).unwrap_or((name, selection_range));
+                    // children are processed (and thus constructed) in reverse order
+                    let mut children = syms.split_off(syms.len() - children_count);
+                    children.reverse();
                     #[allow(deprecated)]
-                    let this = lsp::SymbolInformation {
-                        name: name.to_string(),
+                    let this = lsp::DocumentSymbol {
+                        name,
+                        detail: detail(content, node),
                         kind,
                         tags: Default::default(),
                         deprecated: Default::default(),
-                        location: lsp::Location {
-                            uri: params.text_document.uri.clone(),
-                            range,
-                        },
-                        container_name: Default::default(),
+                        range,
+                        selection_range,
+                        children: Some(children).filter(|children| !children.is_empty()),
                     };
                     syms.push(this);
                 }
@@ -62,6 +257,34 @@ pub async fn document_symbol(
                 work.extend(items);
             },
 
+            Work::Node(node) if dl::kind::ARG == node.kind_id() => {
+                let kind = match node.parent().map(|parent| parent.kind_id()) {
+                    Some(dl::kind::REL_ARGS) => lsp::SymbolKind::FIELD,
+                    _ => lsp::SymbolKind::VARIABLE,
+                };
+                push_symbol(&mut work, &mut data, node, vec![], kind, "arg");
+            },
+
+            Work::Node(node) if dl::kind::ARG_TRANS == node.kind_id() => {
+                push_symbol(&mut work, &mut data, node, vec![], lsp::SymbolKind::VARIABLE, "arg");
+            },
+
+            Work::Node(node) if [dl::kind::CONS_POS, dl::kind::CONS_REC].contains(&node.kind_id()) => {
+                let children = children_of_kind(node, dl::kind::FIELD);
+                push_symbol(
+                    &mut work,
+                    &mut data,
+                    node,
+                    children,
+                    lsp::SymbolKind::CONSTRUCTOR,
+                    "constructor",
+                );
+            },
+
+            Work::Node(node) if dl::kind::FIELD == node.kind_id() => {
+                push_symbol(&mut work, &mut data, node, vec![], lsp::SymbolKind::FIELD, "field");
+            },
+
             Work::Node(node) if dl::kind::FUNCTION == node.kind_id() => {
                 let mut cursor = node.walk();
                 let items = node
@@ -74,33 +297,36 @@ pub async fn document_symbol(
             },
 
             Work::Node(node) if dl::kind::FUNCTION_EXTERN == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
+                let children = children_of_kind(node, dl::kind::ARG);
+                push_symbol(
+                    &mut work,
+                    &mut data,
                     node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::FUNCTION,
-                    name_hint: "function",
-                });
+                    children,
+                    lsp::SymbolKind::FUNCTION,
+                    "function",
+                );
             },
 
             Work::Node(node) if dl::kind::FUNCTION_NORMAL == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
+                let children = children_of_kind(node, dl::kind::ARG);
+                push_symbol(
+                    &mut work,
+                    &mut data,
                     node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::FUNCTION,
-                    name_hint: "function",
-                });
+                    children,
+                    lsp::SymbolKind::FUNCTION,
+                    "function",
+                );
+            },
+
+            Work::Node(node) if dl::kind::IMPORT == node.kind_id() => {
+                push_symbol(&mut work, &mut data, node, vec![], lsp::SymbolKind::MODULE, "import");
             },
 
             Work::Node(node) if dl::kind::INDEX == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
-                    node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::NULL,
-                    name_hint: "index",
-                });
+                let children = children_of_kind(node, dl::kind::ARG);
+                push_symbol(&mut work, &mut data, node, children, lsp::SymbolKind::NULL, "index");
             },
 
             Work::Node(node) if dl::kind::ITEM == node.kind_id() => {
@@ -111,8 +337,10 @@ pub async fn document_symbol(
                         [
                             dl::kind::APPLY,
                             dl::kind::FUNCTION,
+                            dl::kind::IMPORT,
                             dl::kind::INDEX,
                             dl::kind::REL,
+                            dl::kind::RULE,
                             dl::kind::TRANSFORMER,
                             dl::kind::TYPEDEF,
                         ]
@@ -132,33 +360,28 @@ pub async fn document_symbol(
             },
 
             Work::Node(node) if dl::kind::REL_ARGS == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
-                    node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::NULL,
-                    name_hint: "rel",
-                });
+                let children = children_of_kind(node, dl::kind::ARG);
+                push_symbol(&mut work, &mut data, node, children, lsp::SymbolKind::NULL, "rel");
             },
 
             Work::Node(node) if dl::kind::REL_ELEM == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
-                    node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::NULL,
-                    name_hint: "rel",
-                });
+                push_symbol(&mut work, &mut data, node, vec![], lsp::SymbolKind::NULL, "rel");
+            },
+
+            Work::Node(node) if dl::kind::RULE == node.kind_id() => {
+                push_symbol(&mut work, &mut data, node, vec![], lsp::SymbolKind::EVENT, "rule");
             },
 
             Work::Node(node) if dl::kind::TRANSFORMER == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
+                let children = children_of_kind(node, dl::kind::ARG_TRANS);
+                push_symbol(
+                    &mut work,
+                    &mut data,
                     node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::NULL,
-                    name_hint: "transformer",
-                });
+                    children,
+                    lsp::SymbolKind::NULL,
+                    "transformer",
+                );
             },
 
             Work::Node(node) if dl::kind::TYPEDEF == node.kind_id() => {
@@ -173,23 +396,12 @@ pub async fn document_symbol(
             },
 
             Work::Node(node) if dl::kind::TYPEDEF_EXTERN == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
-                    node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::NULL,
-                    name_hint: "typedef",
-                });
+                push_symbol(&mut work, &mut data, node, vec![], lsp::SymbolKind::NULL, "typedef");
             },
 
             Work::Node(node) if dl::kind::TYPEDEF_NORMAL == node.kind_id() => {
-                work.push(Work::Data);
-                data.push(Data {
-                    node,
-                    children_count: 0,
-                    kind: lsp::SymbolKind::NULL,
-                    name_hint: "typedef",
-                });
+                let children = constructors(node);
+                push_symbol(&mut work, &mut data, node, children, lsp::SymbolKind::NULL, "typedef");
             },
 
             _ => {},
@@ -201,3 +413,207 @@ pub async fn document_symbol(
 
     Ok(syms)
 }
+
+// Push the data for a symbol, followed by the nodes of its children (each of which must produce
+// exactly one symbol).
+fn push_symbol<'tree>(
+    work: &mut Vec<Work<'tree>>,
+    data: &mut Vec<Data<'tree>>,
+    node: tree_sitter::Node<'tree>,
+    children: Vec<tree_sitter::Node<'tree>>,
+    kind: lsp::SymbolKind,
+    name_hint: &'static str,
+) {
+    work.push(Work::Data);
+    data.push(Data {
+        node,
+        children_count: children.len(),
+        kind,
+        name_hint,
+    });
+    work.extend(children.into_iter().map(Work::Node));
+}
+
+// The named children of a node with the given kind.
+fn children_of_kind(node: tree_sitter::Node, kind: u16) -> Vec<tree_sitter::Node> {
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor);
+    children.filter(|child| kind == child.kind_id()).collect()
+}
+
+// The constructors of the type defined by a `typedef`, in order.
+fn constructors(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    let mut constructors = vec![];
+    let mut nodes = vec![node];
+    while let Some(node) = nodes.pop() {
+        if [dl::kind::CONS_POS, dl::kind::CONS_REC].contains(&node.kind_id()) {
+            constructors.push(node);
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+        nodes.extend(children.into_iter().rev());
+    }
+    constructors
+}
+
+// The name and selection range of symbols which aren't named by a single identifier, i.e., rules
+// (by their head atoms) and imports (by their module path and alias).
+fn label(content: &ropey::Rope, node: tree_sitter::Node) -> Option<(String, lsp::Range)> {
+    let text = |node: &tree_sitter::Node| {
+        let text = content.utf8_text_for_tree_sitter_node(node);
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    };
+    match node.kind_id() {
+        dl::kind::IMPORT => {
+            let path = node.child_by_field_id(dl::field::MODULE_PATH)?;
+            let name = match node.child_by_field_id(dl::field::MODULE_ALIAS) {
+                Some(alias) => format!("{} as {}", text(&path), text(&alias)),
+                None => text(&path),
+            };
+            Some((name, content.tree_sitter_range_to_lsp_range(path.range())))
+        },
+        dl::kind::RULE => {
+            let heads = rule_heads(node);
+            let first = heads.first()?;
+            let last = heads.last()?;
+            let name = heads.iter().map(text).collect::<Vec<_>>().join(", ");
+            let selection_range = lsp::Range::new(
+                content.tree_sitter_range_to_lsp_range(first.range()).start,
+                content.tree_sitter_range_to_lsp_range(last.range()).end,
+            );
+            Some((name, selection_range))
+        },
+        _ => None,
+    }
+}
+
+// The head atoms of a rule, i.e., those before the `:-` (or all of them for facts).
+fn rule_heads(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    let mut cursor = node.walk();
+    let children = node.children(&mut cursor);
+    children
+        .take_while(|child| symbol::COLON_HYPHEN_MINUS != child.kind_id())
+        .filter(|child| dl::kind::ATOM == child.kind_id())
+        .collect()
+}
+
+// The detail for a symbol, i.e., the declared type of arguments and fields, or the role and
+// semantics of relations (e.g., `input stream`).
+fn detail(content: &ropey::Rope, node: tree_sitter::Node) -> Option<String> {
+    if [dl::kind::REL_ARGS, dl::kind::REL_ELEM].contains(&node.kind_id()) {
+        let mut cursor = node.walk();
+        let words = node
+            .named_children(&mut cursor)
+            .filter(|child| [dl::kind::REL_ROLE, dl::kind::REL_SEMANTICS].contains(&child.kind_id()))
+            .map(|child| content.utf8_text_for_tree_sitter_node(&child).into_owned())
+            .collect::<Vec<_>>();
+        return Some(words.join(" ")).filter(|detail| !detail.is_empty());
+    }
+    if ![dl::kind::ARG, dl::kind::ARG_TRANS, dl::kind::FIELD].contains(&node.kind_id()) {
+        return None;
+    }
+    let r#type = node.named_child(node.named_child_count().checked_sub(1)?)?;
+    if r#type.kind().starts_with("name_") {
+        return None;
+    }
+    Some(content.utf8_text_for_tree_sitter_node(&r#type).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start_char_idx: usize, end_char_idx: usize, text: &str) -> TextEdit<'_> {
+        let point = tree_sitter::Point::new(0, 0);
+        TextEdit {
+            input_edit: tree_sitter::InputEdit::new(0, 0, 0, &point, &point, &point),
+            start_char_idx,
+            end_char_idx,
+            text,
+        }
+    }
+
+    #[test]
+    fn unedited_items_are_mapped_back() {
+        // "a. b. c." with "b" replaced by "bbb", i.e., "a. bbb. c."
+        let edits = [edit(3, 4, "bbb")];
+        assert_eq!(unedited(&edits, 0, 2), Some((0, 2)));
+        assert_eq!(unedited(&edits, 3, 7), None);
+        assert_eq!(unedited(&edits, 8, 10), Some((6, 8)));
+        // items adjoining an edit may have been affected by it
+        assert_eq!(unedited(&[edit(2, 2, "x")], 0, 2), None);
+    }
+
+    #[test]
+    fn positions_move_with_their_item() {
+        let old_start = lsp::Position::new(3, 2);
+        let new_start = lsp::Position::new(5, 0);
+        let moved = |line, character| moved(old_start, new_start, lsp::Position::new(line, character));
+        assert_eq!(moved(3, 4), lsp::Position::new(5, 2));
+        assert_eq!(moved(4, 4), lsp::Position::new(6, 4));
+    }
+
+    #[test]
+    fn rules_and_imports_are_not_flat_symbols() {
+        let uri = lsp::Url::parse("file:///main.dl").unwrap();
+        #[allow(deprecated)]
+        let symbol = |kind| lsp::DocumentSymbol {
+            name: String::from("R(x)"),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: lsp::Range::default(),
+            selection_range: lsp::Range::default(),
+            children: None,
+        };
+        assert!(information(&uri, symbol(lsp::SymbolKind::EVENT)).is_none());
+        assert!(information(&uri, symbol(lsp::SymbolKind::MODULE)).is_none());
+        let relation = information(&uri, symbol(lsp::SymbolKind::STRUCT)).unwrap();
+        assert_eq!(uri, relation.location.uri);
+    }
+
+    #[test]
+    fn consecutive_rules_are_grouped_by_their_head() {
+        #[allow(deprecated)]
+        let symbol = |name: &str, kind, line| lsp::DocumentSymbol {
+            name: String::from(name),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: lsp::Range::new(lsp::Position::new(line, 0), lsp::Position::new(line, 10)),
+            selection_range: lsp::Range::new(lsp::Position::new(line, 0), lsp::Position::new(line, 1)),
+            children: None,
+        };
+        let rule = |name, line| symbol(name, lsp::SymbolKind::EVENT, line);
+        let syms = vec![
+            rule("R(x)", 0),
+            rule("R(y)", 1),
+            symbol("S", lsp::SymbolKind::STRUCT, 2),
+            rule("R(z)", 3),
+            rule("S(x), T(x)", 4),
+        ];
+        let groups = group_rules(syms)
+            .into_iter()
+            .map(|symbol| {
+                let children = symbol.children.map_or(0, |children| children.len());
+                (
+                    symbol.name,
+                    symbol.detail,
+                    symbol.range.start.line,
+                    symbol.range.end.line,
+                    children,
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = vec![
+            (String::from("R"), Some(String::from("2 rules")), 0, 1, 2),
+            (String::from("S"), None, 2, 2, 0),
+            (String::from("R"), Some(String::from("1 rule")), 3, 3, 1),
+            (String::from("S"), Some(String::from("1 rule")), 4, 4, 1),
+        ];
+        assert_eq!(expected, groups);
+    }
+}
@@ -0,0 +1,277 @@
+pub mod dat;
+pub mod dl;
+
+use lsp_text::RopeExt;
+use std::convert::TryFrom;
+
+/// The formatting option (of type `bool`) which enables sorting the updates within the transactions
+/// of ".dat" documents (see `dat::format`).
+pub const SORT_COMMANDS: &str = "ddlog.sortCommands";
+
+/// Format a document, i.e., each of its top-level nodes (items, commands, and comments), keeping
+/// them on separate lines with at most one blank line in between.
+pub fn format(
+    language: crate::core::Language,
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+    options: &lsp::FormattingOptions,
+) -> String {
+    let indent = indentation(options);
+    let text = match language {
+        crate::core::Language::DDlogDat => self::dat::format(content, tree, &indent, sort_commands(options)),
+        crate::core::Language::DDlogDl => self::dl::format(content, tree, &indent),
+    };
+    let mut text = text.trim_end().to_string();
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+/// Format the top-level nodes of a document which intersect a range, as edits of the document.
+pub fn format_range(
+    language: crate::core::Language,
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+    options: &lsp::FormattingOptions,
+    range: lsp::Range,
+) -> anyhow::Result<Vec<lsp::TextEdit>> {
+    let indent = indentation(options);
+    let sort = sort_commands(options);
+    let range = content.lsp_range_to_tree_sitter_range(range)?;
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut edits = vec![];
+    // the end of the nodes formatted so far, since a node may be formatted along with its neighbors
+    let mut formatted_end = 0;
+    for node in root.children(&mut cursor) {
+        if node.start_byte() < formatted_end {
+            continue;
+        }
+        if node.end_byte() < range.start_byte() || range.end_byte() < node.start_byte() {
+            continue;
+        }
+        let (first, last, new_text) = format_node(language, content, node, &indent, sort);
+        formatted_end = last.end_byte();
+        // top-level nodes are placed at the start of their line (unless preceded by another node)
+        let start = content.byte_to_char(first.start_byte() as usize);
+        let line_start = content.line_to_char(content.char_to_line(start));
+        let prefix = content.slice(line_start .. start).to_string();
+        let (start_byte, new_text) = if prefix.trim().is_empty() {
+            (content.char_to_byte(line_start), new_text)
+        } else {
+            match language {
+                // commands are moved to lines of their own, but trailing comments are kept in place
+                crate::core::Language::DDlogDat => {
+                    let start_byte = first.prev_sibling().map_or(first.start_byte(), |prev| prev.end_byte());
+                    let new_text = if crate::core::language::dat::kind::COMMENT_LINE == first.kind_id() {
+                        format!(" {}", new_text.trim_start())
+                    } else {
+                        format!("\n{}", new_text)
+                    };
+                    (start_byte as usize, new_text)
+                },
+                crate::core::Language::DDlogDl => (first.start_byte() as usize, new_text),
+            }
+        };
+        let old_text = content
+            .slice(content.byte_to_char(start_byte) .. content.byte_to_char(last.end_byte() as usize))
+            .to_string();
+        if old_text != new_text {
+            let range = lsp::Range::new(
+                content.byte_to_lsp_position(start_byte),
+                content.byte_to_lsp_position(last.end_byte() as usize),
+            );
+            edits.push(lsp::TextEdit { range, new_text });
+        }
+    }
+    Ok(edits)
+}
+
+/// Format the text of a document (which is parsed first), e.g., for formatting files on disk.
+pub fn format_text(
+    language: crate::core::Language,
+    text: &str,
+    options: &lsp::FormattingOptions,
+) -> anyhow::Result<String> {
+    let mut parser = tree_sitter::Parser::try_from(language)?;
+    let tree = parser
+        .parse(text, None)
+        .ok()
+        .flatten()
+        .ok_or_else(|| anyhow::anyhow!("could not parse text"))?;
+    let content = ropey::Rope::from(text);
+    Ok(format(language, &content, &tree, options))
+}
+
+// Format a top-level node of a document, along with the first and last top-level nodes the text
+// replaces (see `dat::format_node`).
+fn format_node<'tree>(
+    language: crate::core::Language,
+    content: &ropey::Rope,
+    node: tree_sitter::Node<'tree>,
+    indent: &str,
+    sort: bool,
+) -> (tree_sitter::Node<'tree>, tree_sitter::Node<'tree>, String) {
+    match language {
+        crate::core::Language::DDlogDat => self::dat::format_node(content, node, indent, sort),
+        crate::core::Language::DDlogDl => (node, node, self::dl::format_node(content, node, indent)),
+    }
+}
+
+// Whether the updates within transactions are sorted (see `SORT_COMMANDS`).
+fn sort_commands(options: &lsp::FormattingOptions) -> bool {
+    matches!(
+        options.properties.get(SORT_COMMANDS),
+        Some(lsp::FormattingProperty::Bool(true))
+    )
+}
+
+// The text of a single level of indentation.
+fn indentation(options: &lsp::FormattingOptions) -> String {
+    if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        String::from("\t")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Language;
+    use indoc::indoc;
+
+    fn options() -> lsp::FormattingOptions {
+        lsp::FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        }
+    }
+
+    // Format a text, checking that formatting it again doesn't change it.
+    fn format(language: Language, text: &str) -> String {
+        let formatted = format_text(language, text, &options()).unwrap();
+        assert_eq!(formatted, format_text(language, &formatted, &options()).unwrap());
+        formatted
+    }
+
+    fn sorting() -> lsp::FormattingOptions {
+        let mut options = options();
+        let property = lsp::FormattingProperty::Bool(true);
+        options.properties.insert(SORT_COMMANDS.into(), property);
+        options
+    }
+
+    // Format the top-level nodes of a text which intersect a line.
+    fn format_line(language: Language, text: &str, line: u32, options: &lsp::FormattingOptions) -> Vec<lsp::TextEdit> {
+        let mut parser = tree_sitter::Parser::try_from(language).unwrap();
+        let tree = parser.parse(text, None).unwrap().unwrap();
+        let content = ropey::Rope::from(text);
+        let range = lsp::Range::new(lsp::Position::new(line, 0), lsp::Position::new(line, 0));
+        format_range(language, &content, &tree, options, range).unwrap()
+    }
+
+    fn edit(line: u32, start: u32, end: u32, new_text: &str) -> lsp::TextEdit {
+        let range = lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, end));
+        lsp::TextEdit::new(range, new_text.into())
+    }
+
+    #[test]
+    fn rules_are_spaced_and_their_bodies_indented() {
+        let text = "R(x)  :-   S(x,y),\nx==y.\n";
+        let expected = indoc! {"
+            R(x) :- S(x, y),
+                x == y.
+        "};
+        assert_eq!(expected, format(Language::DDlogDl, text));
+    }
+
+    #[test]
+    fn match_arms_are_indented_by_their_braces() {
+        let text = indoc! {r#"
+            function f(x:u32):string {
+            match(x) {
+            0->"zero",
+            _->"other"
+            }
+            }
+        "#};
+        let expected = indoc! {r#"
+            function f(x: u32): string {
+                match (x) {
+                    0 -> "zero",
+                    _ -> "other"
+                }
+            }
+        "#};
+        assert_eq!(expected, format(Language::DDlogDl, text));
+    }
+
+    #[test]
+    fn relation_fields_on_lines_of_their_own_are_aligned() {
+        let text = indoc! {"
+            input relation R(
+            a:u32,
+            long_name:   string
+            )
+        "};
+        let expected = indoc! {"
+            input relation R(
+                a:         u32,
+                long_name: string
+            )
+        "};
+        assert_eq!(expected, format(Language::DDlogDl, text));
+    }
+
+    #[test]
+    fn comments_are_kept_in_place() {
+        let text = "// leading comment   \ninput relation R(x:u32)    // trailing\n\n\n\noutput relation S(x: u32)\n";
+        let expected = indoc! {"
+            // leading comment
+            input relation R(x: u32) // trailing
+
+            output relation S(x: u32)
+        "};
+        assert_eq!(expected, format(Language::DDlogDl, text));
+    }
+
+    #[test]
+    fn items_within_a_range_are_formatted() {
+        let text = "input relation R(x:u32)\ninput relation S(x:u32)\n";
+        let expected = vec![edit(1, 0, 23, "input relation S(x: u32)")];
+        assert_eq!(expected, format_line(Language::DDlogDl, text, 1, &options()));
+    }
+
+    #[test]
+    fn updates_within_transactions_are_indented_one_per_line() {
+        let text = "start;\ninsert R(1,\"a\"),insert R(2,  \"b\");\ncommit;\n";
+        let expected = indoc! {r#"
+            start;
+                insert R(1, "a"),
+                insert R(2, "b");
+            commit;
+        "#};
+        assert_eq!(expected, format(Language::DDlogDat, text));
+    }
+
+    #[test]
+    fn commands_within_a_range_are_formatted() {
+        let text = "start;\ninsert R(1,\"a\");\ncommit;\n";
+        let expected = vec![edit(1, 0, 16, "    insert R(1, \"a\");")];
+        assert_eq!(expected, format_line(Language::DDlogDat, text, 1, &options()));
+    }
+
+    #[test]
+    fn commands_within_a_range_are_sorted_with_their_run() {
+        let text = "start;\ninsert R(2);\ninsert R(1);\ncommit;\n";
+        let range = lsp::Range::new(lsp::Position::new(1, 0), lsp::Position::new(2, 12));
+        let expected = vec![lsp::TextEdit::new(range, "    insert R(1);\n    insert R(2);".into())];
+        assert_eq!(expected, format_line(Language::DDlogDat, text, 2, &sorting()));
+        // without sorting, only the command on the line is formatted
+        let expected = vec![edit(2, 0, 12, "    insert R(1);")];
+        assert_eq!(expected, format_line(Language::DDlogDat, text, 2, &options()));
+    }
+}
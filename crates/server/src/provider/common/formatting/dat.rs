@@ -0,0 +1,481 @@
+use crate::{
+    analysis::tree::first_of_kind,
+    core::language::dat::{kind, symbol},
+};
+use lsp_text::RopeExt;
+use std::collections::{HashMap, HashSet};
+
+// The kinds of the nodes whose text is kept as is (and which are laid out as single tokens).
+const VERBATIM_KINDS: &[u16] = &[
+    kind::ATTRIBUTE,
+    kind::COMMENT_LINE,
+    kind::LIT_SERIALIZED,
+    kind::LIT_STRING,
+    kind::MISC_PAT0,
+];
+
+// The kinds of the binary expressions, whose operators are surrounded by spaces.
+const BINARY_KINDS: &[u16] = &[
+    kind::EXP_ADD,
+    kind::EXP_ASSIGN,
+    kind::EXP_BIT_AND,
+    kind::EXP_BIT_OR,
+    kind::EXP_BIT_XOR,
+    kind::EXP_CAT,
+    kind::EXP_DIV,
+    kind::EXP_EQ,
+    kind::EXP_GT,
+    kind::EXP_GTEQ,
+    kind::EXP_LOG_AND,
+    kind::EXP_LOG_IMP,
+    kind::EXP_LOG_OR,
+    kind::EXP_LT,
+    kind::EXP_LTEQ,
+    kind::EXP_MUL,
+    kind::EXP_NEQ,
+    kind::EXP_REM,
+    kind::EXP_SHL,
+    kind::EXP_SHR,
+    kind::EXP_SUB,
+];
+
+// The kinds of the unary expressions, whose (symbolic) operators are attached to their operand.
+const UNARY_KINDS: &[u16] = &[kind::EXP_BIT_NEG, kind::EXP_NEG, kind::EXP_REF];
+
+/// A token of a command, along with the syntactic context which determines its spacing.
+#[derive(Clone, Debug)]
+struct Token {
+    kind: u16,
+    parent: Option<u16>,
+    named: bool,
+    text: String,
+    /// Whether the token is separated from the previous one by whitespace.
+    spaced: bool,
+}
+
+/// An update of an "updates" command (e.g., `insert R(..)`), ordered by relation and then text.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Update {
+    relation: String,
+    text: String,
+    /// Whether the update applies to a record by its key (i.e., `delete_key`, `insert_or_update`,
+    /// or `modify`), which may coincide with the key of any other record of the relation.
+    keyed: bool,
+}
+
+impl Update {
+    // The record (or key) to which the update applies, i.e., its text without the command.
+    fn record(&self) -> &str {
+        self.text.split_once(' ').map_or("", |(_, record)| record)
+    }
+}
+
+/// The updates of an "updates" command, along with the text which ends them (i.e., `;`).
+type Updates = (Vec<Update>, String);
+
+/// Format a ".dat" document, i.e., put each command (and each update of a chain of updates) on a
+/// line of its own, and indent the commands within transactions, where `indent` is the text of a
+/// single level of indentation.
+///
+/// If `sort` is set, the updates of the consecutive "updates" commands within a transaction are
+/// sorted by relation and then text, which keeps the diffs of generated files small. Runs of
+/// updates whose order matters (see `is_sortable`) are left as written.
+pub fn format(content: &ropey::Rope, tree: &tree_sitter::Tree, indent: &str, sort: bool) -> String {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let nodes = root.children(&mut cursor).collect::<Vec<_>>();
+    let mut text = String::new();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < nodes.len() {
+        let node = nodes[i];
+        let separator = i.checked_sub(1).map(|i| separator(nodes[i], node));
+        text.push_str(separator.unwrap_or_default());
+        let command = command_kind(node);
+        if matches!(command, Some(kind::COMMIT | kind::ROLLBACK)) {
+            depth = 0;
+        }
+
+        if sort && 0 < depth {
+            let run = nodes[i ..]
+                .iter()
+                .map(|node| updates(content, *node))
+                .take_while(Option::is_some)
+                .flatten()
+                .collect::<Vec<_>>();
+            if !run.is_empty() {
+                i += run.len();
+                text.push_str(&layout_run(run, indent, depth));
+                continue;
+            }
+        }
+
+        let formatted = format_command(content, node, indent, depth);
+        // comments which trail a command are kept on its line
+        if Some(" ") == separator {
+            text.push_str(formatted.trim_start());
+        } else {
+            text.push_str(&formatted);
+        }
+        if Some(kind::START) == command {
+            depth = 1;
+        }
+        i += 1;
+    }
+    text
+}
+
+/// Format a top-level node of a ".dat" document (a command or a comment), indented by the
+/// transaction it occurs in, along with the first and last top-level nodes the text replaces.
+///
+/// If `sort` is set and the node is an "updates" command within a transaction, the whole run of
+/// consecutive "updates" commands it belongs to is formatted (and sorted, as by `format`).
+pub fn format_node<'tree>(
+    content: &ropey::Rope,
+    node: tree_sitter::Node<'tree>,
+    indent: &str,
+    sort: bool,
+) -> (tree_sitter::Node<'tree>, tree_sitter::Node<'tree>, String) {
+    let depth = depth(node);
+    let is_updates = |node: &tree_sitter::Node| updates(content, *node).is_some();
+    if sort && 0 < depth && is_updates(&node) {
+        let first = std::iter::successors(Some(node), |node| node.prev_sibling().filter(is_updates)).last();
+        let nodes = std::iter::successors(first, |node| node.next_sibling().filter(is_updates)).collect::<Vec<_>>();
+        let run = nodes.iter().filter_map(|node| updates(content, *node)).collect();
+        let first = nodes.first().copied().unwrap_or(node);
+        let last = nodes.last().copied().unwrap_or(node);
+        return (first, last, layout_run(run, indent, depth));
+    }
+    (node, node, format_command(content, node, indent, depth))
+}
+
+// The text between two consecutive top-level nodes of a document.
+fn separator(prev: tree_sitter::Node, next: tree_sitter::Node) -> &'static str {
+    let rows = next.start_position().row().saturating_sub(prev.end_position().row());
+    match rows {
+        0 if kind::COMMENT_LINE == next.kind_id() => " ",
+        0 | 1 => "\n",
+        _ => "\n\n",
+    }
+}
+
+// The kind of the command of a top-level node, if it is a command.
+fn command_kind(node: tree_sitter::Node) -> Option<u16> {
+    Some(node)
+        .filter(|node| kind::COMMAND == node.kind_id())
+        .and_then(|node| node.named_child(0))
+        .map(|command| command.kind_id())
+}
+
+// The indentation level of a top-level node, i.e., whether it occurs within a transaction.
+fn depth(node: tree_sitter::Node) -> usize {
+    if matches!(command_kind(node), Some(kind::COMMIT | kind::ROLLBACK)) {
+        return 0;
+    }
+    for prev in std::iter::successors(node.prev_named_sibling(), |node| node.prev_named_sibling()) {
+        match command_kind(prev) {
+            Some(kind::START) => return 1,
+            Some(kind::COMMIT | kind::ROLLBACK) => return 0,
+            _ => {},
+        }
+    }
+    0
+}
+
+// Format a top-level node at a given indentation level. Commands with syntax errors or comments
+// are left as is.
+fn format_command(content: &ropey::Rope, node: tree_sitter::Node, indent: &str, depth: usize) -> String {
+    let text = content.utf8_text_for_tree_sitter_node(&node);
+    if kind::COMMENT_LINE == node.kind_id() {
+        return format!("{}{}", indent.repeat(depth), text.trim_end());
+    }
+    if let Some((updates, end)) = updates(content, node) {
+        return layout_updates(&updates, &end, indent, depth);
+    }
+    if kind::COMMAND != node.kind_id() || !is_formattable(node) {
+        return text.into();
+    }
+    format!("{}{}", indent.repeat(depth), line(&tokens(content, node)))
+}
+
+// Collect the (formatted) updates of an "updates" command.
+fn updates(content: &ropey::Rope, node: tree_sitter::Node) -> Option<Updates> {
+    if kind::COMMAND != node.kind_id() || !is_formattable(node) {
+        return None;
+    }
+    let node = node.named_child(0).filter(|child| kind::UPDATES == child.kind_id())?;
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+    let end = children
+        .iter()
+        .find(|child| kind::UPDATES_END == child.kind_id())
+        .map(|end| content.utf8_text_for_tree_sitter_node(end).trim().to_string())
+        .unwrap_or_default();
+    let updates = children
+        .iter()
+        .filter(|child| kind::UPDATE == child.kind_id())
+        .map(|update| {
+            let relation = first_of_kind(*update, kind::NAME_REL);
+            let command = update.named_child(0).map(|command| command.kind_id());
+            Update {
+                relation: relation
+                    .map(|relation| content.utf8_text_for_tree_sitter_node(&relation).into())
+                    .unwrap_or_default(),
+                text: line(&tokens(content, *update)),
+                keyed: matches!(command, Some(kind::DELETE_KEY | kind::INSERT_OR_UPDATE | kind::MODIFY)),
+            }
+        })
+        .collect();
+    Some((updates, end))
+}
+
+// Whether sorting a run of updates preserves the meaning of the transaction, i.e., whether the
+// updates commute: no record may be updated more than once, and updates by key may only be
+// reordered if they are the only update of their relation.
+fn is_sortable(run: &[Updates]) -> bool {
+    let updates = run.iter().flat_map(|(updates, _)| updates).collect::<Vec<_>>();
+    let mut records = HashSet::new();
+    let mut relations = HashMap::<&str, usize>::new();
+    for update in &updates {
+        if !records.insert((update.relation.as_str(), update.record())) {
+            return false;
+        }
+        *relations.entry(update.relation.as_str()).or_default() += 1;
+    }
+    updates
+        .iter()
+        .all(|update| !update.keyed || 1 == relations[update.relation.as_str()])
+}
+
+// Sort the updates of each command, and then the commands by their first update.
+fn sorted(mut run: Vec<Updates>) -> Vec<Updates> {
+    for (updates, _) in run.iter_mut() {
+        updates.sort();
+    }
+    run.sort_by(|(lhs, _), (rhs, _)| lhs.first().cmp(&rhs.first()));
+    run
+}
+
+// Lay out a run of consecutive "updates" commands, sorted if that preserves the meaning of the
+// transaction (see `is_sortable`).
+fn layout_run(run: Vec<Updates>, indent: &str, depth: usize) -> String {
+    let run = if is_sortable(&run) { sorted(run) } else { run };
+    let commands = run
+        .iter()
+        .map(|(updates, end)| layout_updates(updates, end, indent, depth))
+        .collect::<Vec<_>>();
+    commands.join("\n")
+}
+
+// Lay out the updates of a command, one per line.
+fn layout_updates(updates: &[Update], end: &str, indent: &str, depth: usize) -> String {
+    let prefix = indent.repeat(depth);
+    let lines = updates
+        .iter()
+        .map(|update| format!("{}{}", prefix, update.text))
+        .collect::<Vec<_>>();
+    format!("{}{}", lines.join(",\n"), end)
+}
+
+// Whether a command can be laid out on a single line, i.e., it has no errors and no comments.
+fn is_formattable(node: tree_sitter::Node) -> bool {
+    !node.has_error() && first_of_kind(node, kind::COMMENT_LINE).is_none()
+}
+
+// Collect the tokens of a node.
+fn tokens(content: &ropey::Rope, node: tree_sitter::Node) -> Vec<Token> {
+    let mut leaves = vec![];
+    let mut work = vec![node];
+    while let Some(node) = work.pop() {
+        if 0 == node.child_count() || VERBATIM_KINDS.contains(&node.kind_id()) {
+            if node.start_byte() < node.end_byte() {
+                leaves.push(node);
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children = node.children(&mut cursor).collect::<Vec<_>>();
+        work.extend(children.into_iter().rev());
+    }
+
+    let mut tokens = vec![];
+    let mut prev_end_byte = None;
+    for node in leaves {
+        tokens.push(Token {
+            kind: node.kind_id(),
+            parent: node.parent().map(|parent| parent.kind_id()),
+            named: node.is_named(),
+            text: content.utf8_text_for_tree_sitter_node(&node).into(),
+            spaced: matches!(prev_end_byte, Some(end_byte) if end_byte < node.start_byte()),
+        });
+        prev_end_byte = Some(node.end_byte());
+    }
+    tokens
+}
+
+// Lay out tokens on a single line.
+fn line(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some(prev) = i.checked_sub(1).map(|i| &tokens[i]) {
+            text.push_str(&" ".repeat(spaces(prev, token)));
+        }
+        text.push_str(&token.text);
+    }
+    text
+}
+
+// The number of spaces between two tokens.
+fn spaces(prev: &Token, next: &Token) -> usize {
+    match (prev.kind, next.kind) {
+        (_, symbol::COMMA | symbol::SEMICOLON | kind::UPDATES_END) => 0,
+        (symbol::COMMA, symbol::RIGHT_CURLY_BRACKET | symbol::RIGHT_PARENTHESIS | symbol::RIGHT_SQUARE_BRACKET) => 0,
+        (symbol::COMMA | symbol::SEMICOLON, _) => 1,
+        (symbol::LEFT_CURLY_BRACKET | symbol::LEFT_PARENTHESIS | symbol::LEFT_SQUARE_BRACKET, _) => 0,
+        (_, symbol::RIGHT_CURLY_BRACKET | symbol::RIGHT_PARENTHESIS | symbol::RIGHT_SQUARE_BRACKET) => 0,
+        // keywords are separated from brackets, but names are not, e.g., `R(..)` or `C{..}`
+        (_, symbol::LEFT_CURLY_BRACKET | symbol::LEFT_PARENTHESIS | symbol::LEFT_SQUARE_BRACKET)
+            if is_keyword(prev) =>
+        {
+            1
+        },
+        (_, symbol::LEFT_CURLY_BRACKET | symbol::LEFT_PARENTHESIS | symbol::LEFT_SQUARE_BRACKET) if prev.named => 0,
+        (symbol::EQUALS_SIGN | symbol::EQUALS_SIGN_GREATER_THAN_SIGN | symbol::LEFTWARDS_ARROW, _) => 1,
+        (_, symbol::EQUALS_SIGN | symbol::EQUALS_SIGN_GREATER_THAN_SIGN | symbol::LEFTWARDS_ARROW) => 1,
+        // the fields of named records, i.e., `.field = ..`
+        (symbol::FULL_STOP, _) | (_, symbol::FULL_STOP) => 0,
+        (_, symbol::COLON) => 0,
+        (symbol::COLON, _) => 1,
+        _ if is_binary(prev) || is_binary(next) => 1,
+        _ if !prev.named && matches!(prev.parent, Some(parent) if UNARY_KINDS.contains(&parent)) => 0,
+        _ => usize::from(next.spaced),
+    }
+}
+
+// Whether a token is the operator of a binary expression.
+fn is_binary(token: &Token) -> bool {
+    !token.named && matches!(token.parent, Some(parent) if BINARY_KINDS.contains(&parent))
+}
+
+fn is_keyword(token: &Token) -> bool {
+    !token.named && token.text.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::language::dat::keyword;
+
+    fn token(kind: u16, parent: u16, text: &str, spaced: bool) -> Token {
+        let named = text.starts_with(|c: char| c.is_alphanumeric() || '"' == c) && keyword::INSERT != kind;
+        Token {
+            kind,
+            parent: Some(parent),
+            named,
+            text: text.into(),
+            spaced,
+        }
+    }
+
+    fn update(relation: &str, text: &str) -> Update {
+        Update {
+            relation: relation.into(),
+            text: text.into(),
+            keyed: text.starts_with("modify"),
+        }
+    }
+
+    fn format_sorted(text: &str) -> String {
+        let mut options = lsp::FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        };
+        let property = lsp::FormattingProperty::Bool(true);
+        options.properties.insert(super::super::SORT_COMMANDS.into(), property);
+        super::super::format_text(crate::core::Language::DDlogDat, text, &options).unwrap()
+    }
+
+    #[test]
+    fn named_records_are_normalized() {
+        let tokens = vec![
+            token(keyword::INSERT, kind::INSERT, "insert", false),
+            token(kind::NAME_REL, kind::ATOM_POS, "R", true),
+            token(symbol::LEFT_PARENTHESIS, kind::ATOM_POS, "(", true),
+            token(kind::NAME_REL, kind::VAL_STRUCT, "C", true),
+            token(symbol::LEFT_CURLY_BRACKET, kind::VAL_STRUCT, "{", true),
+            token(symbol::FULL_STOP, kind::RECORD_NAMED, ".", true),
+            token(kind::NAME_CONS, kind::RECORD_NAMED, "a", false),
+            token(symbol::EQUALS_SIGN, kind::RECORD_NAMED, "=", false),
+            token(kind::LIT_NUM_DEC, kind::RECORD, "1", false),
+            token(symbol::COMMA, kind::CONS_ARG, ",", true),
+            token(symbol::FULL_STOP, kind::RECORD_NAMED, ".", false),
+            token(kind::NAME_CONS, kind::RECORD_NAMED, "b", false),
+            token(symbol::EQUALS_SIGN, kind::RECORD_NAMED, "=", true),
+            token(kind::LIT_STRING, kind::RECORD, "\"x  y\"", true),
+            token(symbol::RIGHT_CURLY_BRACKET, kind::VAL_STRUCT, "}", true),
+            token(symbol::RIGHT_PARENTHESIS, kind::ATOM_POS, ")", false),
+        ];
+        assert_eq!(line(&tokens), "insert R(C{.a = 1, .b = \"x  y\"})");
+    }
+
+    #[test]
+    fn updates_are_sorted_and_laid_out() {
+        let run = vec![
+            (
+                vec![update("S", "insert S(2)"), update("R", "delete R(1)")],
+                String::from(";"),
+            ),
+            (vec![update("R", "insert R(0)")], String::from(";")),
+        ];
+        let run = sorted(run);
+        let commands = run
+            .iter()
+            .map(|(updates, end)| layout_updates(updates, end, "    ", 1))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commands.join("\n"),
+            "    delete R(1),\n    insert S(2);\n    insert R(0);"
+        );
+    }
+
+    #[test]
+    fn only_commuting_updates_are_sorted() {
+        let run = |updates: Vec<Update>| vec![(updates, String::from(";"))];
+        assert!(is_sortable(&run(vec![
+            update("S", "insert S(2)"),
+            update("R", "delete R(1)")
+        ])));
+        assert!(!is_sortable(&run(vec![
+            update("R", "insert R(1)"),
+            update("R", "delete R(1)")
+        ])));
+        assert!(!is_sortable(&run(vec![
+            update("R", "modify R 1 <- R{.a = 2}"),
+            update("R", "insert R(0)")
+        ])));
+        assert!(is_sortable(&run(vec![
+            update("R", "modify R 1 <- R{.a = 2}"),
+            update("S", "insert S(0)")
+        ])));
+    }
+
+    #[test]
+    fn updates_of_the_same_record_keep_their_order() {
+        let text = "start;\ninsert S(2),\ninsert R(1);\ndelete R(1);\ncommit;\n";
+        let formatted = format_sorted(text);
+        assert_eq!(
+            formatted,
+            "start;\n    insert S(2),\n    insert R(1);\n    delete R(1);\ncommit;\n"
+        );
+        assert_eq!(formatted, format_sorted(&formatted));
+
+        let text = "start;\ninsert S(2),\ninsert R(1);\ndelete R(0);\ncommit;\n";
+        let formatted = format_sorted(text);
+        assert_eq!(
+            formatted,
+            "start;\n    delete R(0);\n    insert R(1),\n    insert S(2);\ncommit;\n"
+        );
+        assert_eq!(formatted, format_sorted(&formatted));
+    }
+}
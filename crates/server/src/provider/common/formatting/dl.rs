@@ -0,0 +1,413 @@
+use crate::core::{
+    language::{
+        dl::{
+            kind,
+            symbol,
+            visitor::{default, Visitor},
+        },
+        HasWalker,
+        NodeMove,
+    },
+    node::NodeWalker,
+};
+use ddlog_lsp_syntax::error::SyntaxError;
+use lsp_text::RopeExt;
+use std::collections::HashMap;
+
+// The kinds of the nodes whose text is kept as is (and which are laid out as single tokens).
+const VERBATIM_KINDS: &[u16] = &[
+    kind::ATTRIBUTE,
+    kind::COMMENT_BLOCK,
+    kind::COMMENT_LINE,
+    kind::LIT_NUM,
+    kind::LIT_STRING,
+];
+
+// The kinds of the binary expressions, whose operators are surrounded by spaces.
+const BINARY_KINDS: &[u16] = &[
+    kind::EXP_ADD,
+    kind::EXP_ASSIGN,
+    kind::EXP_BIT_AND,
+    kind::EXP_BIT_OR,
+    kind::EXP_BIT_XOR,
+    kind::EXP_CAT,
+    kind::EXP_DIV,
+    kind::EXP_EQ,
+    kind::EXP_GT,
+    kind::EXP_GTEQ,
+    kind::EXP_LOG_AND,
+    kind::EXP_LOG_IMP,
+    kind::EXP_LOG_OR,
+    kind::EXP_LT,
+    kind::EXP_LTEQ,
+    kind::EXP_MUL,
+    kind::EXP_NEQ,
+    kind::EXP_REM,
+    kind::EXP_SHL,
+    kind::EXP_SHR,
+    kind::EXP_SUB,
+];
+
+// The kinds of the unary expressions, whose (symbolic) operators are attached to their operand.
+const UNARY_KINDS: &[u16] = &[kind::EXP_BIT_NEG, kind::EXP_NEG, kind::EXP_REF];
+
+/// A visitor which records the relation declarations (whose arguments may be aligned) of an item.
+///
+/// Items which can't be visited completely (e.g., due to syntax errors) are left as is.
+struct FormatVisitor<'tree> {
+    walker: NodeWalker<'tree>,
+    relations: Vec<tree_sitter::Node<'tree>>,
+}
+
+impl<'tree> FormatVisitor<'tree> {
+    fn new(node: tree_sitter::Node<'tree>) -> Self {
+        let walker = NodeWalker::new(crate::core::Language::DDlogDl, node);
+        let relations = vec![];
+        Self { walker, relations }
+    }
+}
+
+impl<'tree> HasWalker<'tree> for FormatVisitor<'tree> {
+    fn walker(&mut self) -> &mut NodeWalker<'tree> {
+        &mut self.walker
+    }
+}
+
+impl<'tree> Visitor<'tree> for FormatVisitor<'tree> {
+    fn visit_rel_args(&mut self, node_move: NodeMove) -> Result<(), SyntaxError<()>> {
+        default::rel_args(self, node_move)?;
+        let node = std::iter::successors(Some(self.walker.node()), |node| node.parent())
+            .find(|node| kind::REL_ARGS == node.kind_id());
+        self.relations.extend(node);
+        Ok(())
+    }
+}
+
+/// A token of an item, along with the syntactic context which determines its layout.
+#[derive(Clone, Debug)]
+struct Token {
+    kind: u16,
+    parent: Option<u16>,
+    named: bool,
+    text: String,
+    start_row: u32,
+    end_row: u32,
+    /// Whether the token is separated from the previous one by whitespace.
+    spaced: bool,
+    /// Whether the token begins a part of the item, i.e., its attributes or the item proper.
+    starts_part: bool,
+    /// The number of spaces following the token, if it is aligned with the tokens of other lines.
+    pad: Option<usize>,
+}
+
+/// Format a ".dl" document, i.e., each of its top-level nodes (items and comments), where `indent`
+/// is the text of a single level of indentation.
+pub fn format(content: &ropey::Rope, tree: &tree_sitter::Tree, indent: &str) -> String {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut text = String::new();
+    let mut prev: Option<tree_sitter::Node> = None;
+    for node in root.children(&mut cursor) {
+        if let Some(prev) = prev {
+            text.push_str(match node.start_position().row() - prev.end_position().row() {
+                0 => " ",
+                1 => "\n",
+                _ => "\n\n",
+            });
+        }
+        text.push_str(&format_node(content, node, indent));
+        prev = Some(node);
+    }
+    text
+}
+
+/// Format a top-level node of a ".dl" document (an item or a comment), where `indent` is the text
+/// of a single level of indentation.
+pub fn format_node(content: &ropey::Rope, node: tree_sitter::Node, indent: &str) -> String {
+    let text = content.utf8_text_for_tree_sitter_node(&node);
+    if kind::COMMENT_LINE == node.kind_id() {
+        return text.trim_end().into();
+    }
+    if kind::ANNOTATED_ITEM != node.kind_id() || node.has_error() {
+        return text.into();
+    }
+    let mut visitor = FormatVisitor::new(node);
+    if visitor.visit().is_err() {
+        return text.into();
+    }
+    layout(&tokens(content, node, &visitor.relations), indent)
+}
+
+// Collect the tokens of an item.
+fn tokens(content: &ropey::Rope, item: tree_sitter::Node, relations: &[tree_sitter::Node]) -> Vec<Token> {
+    let pads = alignment(content, relations);
+    let parts = {
+        let mut cursor = item.walk();
+        let parts = item.named_children(&mut cursor).map(|child| child.start_byte());
+        parts.collect::<Vec<_>>()
+    };
+
+    let mut leaves = vec![];
+    let mut work = vec![item];
+    while let Some(node) = work.pop() {
+        if 0 == node.child_count() || VERBATIM_KINDS.contains(&node.kind_id()) {
+            if node.start_byte() < node.end_byte() {
+                leaves.push(node);
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children = node.children(&mut cursor).collect::<Vec<_>>();
+        work.extend(children.into_iter().rev());
+    }
+
+    let mut tokens = vec![];
+    let mut prev_end_byte = None;
+    for node in leaves {
+        let text = content.utf8_text_for_tree_sitter_node(&node);
+        let text = if kind::COMMENT_LINE == node.kind_id() {
+            text.trim_end()
+        } else {
+            &text
+        };
+        tokens.push(Token {
+            kind: node.kind_id(),
+            parent: node.parent().map(|parent| parent.kind_id()),
+            named: node.is_named(),
+            text: text.into(),
+            start_row: node.start_position().row(),
+            end_row: node.end_position().row(),
+            spaced: matches!(prev_end_byte, Some(end_byte) if end_byte < node.start_byte()),
+            starts_part: parts.contains(&node.start_byte()),
+            pad: pads.get(&node.start_byte()).copied(),
+        });
+        prev_end_byte = Some(node.end_byte());
+    }
+    tokens
+}
+
+// Compute the padding following the `:` of each argument of the relation declarations with one
+// argument per line, so that the types of the arguments are aligned. The padding is keyed by the
+// start byte of the `:` token.
+fn alignment(content: &ropey::Rope, relations: &[tree_sitter::Node]) -> HashMap<u32, usize> {
+    let mut pads = HashMap::new();
+    for relation in relations {
+        let mut cursor = relation.walk();
+        let children = relation.children(&mut cursor).collect::<Vec<_>>();
+        let paren = children
+            .iter()
+            .find(|child| symbol::LEFT_PARENTHESIS == child.kind_id());
+        let args = children
+            .iter()
+            .filter(|child| kind::ARG == child.kind_id())
+            .collect::<Vec<_>>();
+        let one_per_line = match (paren, args.first()) {
+            (Some(paren), Some(first)) => {
+                paren.end_position().row() < first.start_position().row()
+                    && args
+                        .iter()
+                        .all(|arg| arg.start_position().row() == arg.end_position().row())
+                    && args
+                        .windows(2)
+                        .all(|pair| pair[0].end_position().row() < pair[1].start_position().row())
+            },
+            _ => false,
+        };
+        if args.len() < 2 || !one_per_line {
+            continue;
+        }
+
+        let mut columns = vec![];
+        for arg in args {
+            let mut cursor = arg.walk();
+            let children = arg.children(&mut cursor).collect::<Vec<_>>();
+            let name = children.iter().find(|child| kind::NAME_ARG == child.kind_id());
+            let colon = children.iter().find(|child| symbol::COLON == child.kind_id());
+            if let (Some(name), Some(colon)) = (name, colon) {
+                let width = content.utf8_text_for_tree_sitter_node(name).chars().count();
+                columns.push((colon.start_byte(), width));
+            }
+        }
+        let width = columns.iter().map(|(_, width)| *width).max().unwrap_or_default();
+        for (colon, name_width) in columns {
+            pads.insert(colon, width - name_width + 1);
+        }
+    }
+    pads
+}
+
+// Lay out the tokens of an item, keeping its line breaks (but at most one blank line) and
+// indenting each line by the nesting of its brackets. Lines which continue the item outside of
+// any brackets (e.g., the bodies of rules) are indented by one level.
+fn layout(tokens: &[Token], indent: &str) -> String {
+    let mut text = String::new();
+    // the indentation levels of the lines which opened the enclosing brackets
+    let mut brackets: Vec<usize> = vec![];
+    let mut level = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some(prev) = i.checked_sub(1).map(|i| &tokens[i]) {
+            let rows = token.start_row.saturating_sub(prev.end_row);
+            if 0 < rows {
+                text.truncate(text.trim_end().len());
+                text.push_str(if 1 < rows { "\n\n" } else { "\n" });
+                // comments are indented like the code which follows them
+                let code = tokens[i ..].iter().find(|token| !is_comment(token)).unwrap_or(token);
+                level = match brackets.last() {
+                    Some(opener) if is_closer(token) => *opener,
+                    Some(opener) => opener + 1,
+                    None if code.starts_part => 0,
+                    None => 1,
+                };
+                text.push_str(&indent.repeat(level));
+            } else {
+                text.push_str(&" ".repeat(spaces(prev, token)));
+            }
+        }
+        text.push_str(&token.text);
+        if is_opener(token) {
+            brackets.push(level);
+        } else if is_closer(token) {
+            brackets.pop();
+        }
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+// The number of spaces between two tokens on the same line.
+fn spaces(prev: &Token, next: &Token) -> usize {
+    if is_comment(next) || kind::COMMENT_BLOCK == prev.kind {
+        return 1;
+    }
+    if let Some(pad) = prev.pad {
+        return pad;
+    }
+    match (prev.kind, next.kind) {
+        (_, symbol::COMMA | symbol::SEMICOLON) => 0,
+        (symbol::COMMA | symbol::SEMICOLON, symbol::RIGHT_PARENTHESIS | symbol::RIGHT_SQUARE_BRACKET) => 0,
+        (symbol::COMMA | symbol::SEMICOLON, _) => 1,
+        (symbol::LEFT_PARENTHESIS | symbol::LEFT_SQUARE_BRACKET | symbol::NUMBER_SIGN_LEFT_SQUARE_BRACKET, _) => 0,
+        (_, symbol::RIGHT_PARENTHESIS | symbol::RIGHT_SQUARE_BRACKET) => 0,
+        (symbol::COLON_HYPHEN_MINUS, _) | (_, symbol::COLON_HYPHEN_MINUS) => 1,
+        (symbol::COLON_COLON, _) | (_, symbol::COLON_COLON) => 0,
+        (symbol::FULL_STOP, _) | (_, symbol::FULL_STOP | kind::RULE_END) => 0,
+        (_, symbol::COLON) => 0,
+        (symbol::COLON, _) => 1,
+        (symbol::EQUALS_SIGN | symbol::EQUALS_SIGN_GREATER_THAN_SIGN | symbol::RIGHTWARDS_ARROW, _) => 1,
+        (_, symbol::EQUALS_SIGN | symbol::EQUALS_SIGN_GREATER_THAN_SIGN | symbol::RIGHTWARDS_ARROW) => 1,
+        _ if is_binary(prev) || is_binary(next) => 1,
+        (symbol::VERTICAL_LINE, _) if Some(kind::TYPE_UNION) == prev.parent => 1,
+        (_, symbol::VERTICAL_LINE) if Some(kind::TYPE_UNION) == next.parent => 1,
+        _ if !prev.named && matches!(prev.parent, Some(parent) if UNARY_KINDS.contains(&parent)) => 0,
+        // the (non-binary) angle brackets enclose type arguments
+        (symbol::LESS_THAN_SIGN, _) | (_, symbol::LESS_THAN_SIGN | symbol::GREATER_THAN_SIGN) => 0,
+        // keywords are separated from brackets, e.g., `match (..)`, but names are not, e.g., `R(..)`
+        (_, symbol::LEFT_PARENTHESIS | symbol::LEFT_SQUARE_BRACKET | symbol::LEFT_CURLY_BRACKET)
+            if is_keyword(prev) =>
+        {
+            1
+        },
+        (_, symbol::LEFT_PARENTHESIS | symbol::LEFT_SQUARE_BRACKET) if prev.named => 0,
+        (symbol::GREATER_THAN_SIGN, symbol::LEFT_PARENTHESIS) => 0,
+        (_, symbol::LEFT_CURLY_BRACKET) if !prev.named => 1,
+        _ => usize::from(next.spaced),
+    }
+}
+
+fn is_comment(token: &Token) -> bool {
+    [kind::COMMENT_BLOCK, kind::COMMENT_LINE].contains(&token.kind)
+}
+
+fn is_opener(token: &Token) -> bool {
+    [
+        symbol::LEFT_CURLY_BRACKET,
+        symbol::LEFT_PARENTHESIS,
+        symbol::LEFT_SQUARE_BRACKET,
+        symbol::NUMBER_SIGN_LEFT_SQUARE_BRACKET,
+    ]
+    .contains(&token.kind)
+}
+
+fn is_closer(token: &Token) -> bool {
+    [
+        symbol::RIGHT_CURLY_BRACKET,
+        symbol::RIGHT_PARENTHESIS,
+        symbol::RIGHT_SQUARE_BRACKET,
+    ]
+    .contains(&token.kind)
+}
+
+// Whether a token is the operator of a binary expression.
+fn is_binary(token: &Token) -> bool {
+    !token.named && matches!(token.parent, Some(parent) if BINARY_KINDS.contains(&parent))
+}
+
+fn is_keyword(token: &Token) -> bool {
+    !token.named && token.text.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Construct a token on a single row.
+    fn token(kind: u16, parent: u16, text: &str, row: u32, spaced: bool) -> Token {
+        let named = text.starts_with(|c: char| c.is_alphanumeric()) && kind != symbol::COLON;
+        Token {
+            kind,
+            parent: Some(parent),
+            named,
+            text: text.into(),
+            start_row: row,
+            end_row: row,
+            spaced,
+            starts_part: false,
+            pad: None,
+        }
+    }
+
+    #[test]
+    fn rule_bodies_are_indented_and_spaced() {
+        let mut tokens = vec![
+            token(kind::NAME_REL, kind::ATOM_POS, "R", 0, false),
+            token(symbol::LEFT_PARENTHESIS, kind::ATOM_POS, "(", 0, false),
+            token(kind::NAME_VAR_TERM, kind::EXP_DECL_VAR, "x", 0, true),
+            token(symbol::RIGHT_PARENTHESIS, kind::ATOM_POS, ")", 0, true),
+            token(symbol::COLON_HYPHEN_MINUS, kind::RULE, ":-", 0, false),
+            token(kind::NAME_REL, kind::ATOM_POS, "S", 0, false),
+            token(symbol::LEFT_PARENTHESIS, kind::ATOM_POS, "(", 0, true),
+            token(kind::NAME_VAR_TERM, kind::EXP_DECL_VAR, "x", 0, false),
+            token(symbol::COMMA, kind::ATOM_POS, ",", 0, true),
+            token(kind::NAME_VAR_TERM, kind::EXP_DECL_VAR, "y", 0, false),
+            token(symbol::RIGHT_PARENTHESIS, kind::ATOM_POS, ")", 0, false),
+            token(symbol::COMMA, kind::RULE, ",", 0, false),
+            token(kind::NAME_VAR_TERM, kind::EXP_DECL_VAR, "x", 1, false),
+            token(symbol::EQUALS_SIGN_EQUALS_SIGN, kind::EXP_EQ, "==", 1, false),
+            token(kind::NAME_VAR_TERM, kind::EXP_DECL_VAR, "y", 1, false),
+            token(kind::RULE_END, kind::RULE, ".", 1, true),
+        ];
+        tokens[0].starts_part = true;
+        assert_eq!(layout(&tokens, "    "), "R(x) :- S(x, y),\n    x == y.");
+    }
+
+    #[test]
+    fn relation_arguments_are_aligned() {
+        let mut tokens = vec![
+            token(kind::NAME_REL, kind::REL_ARGS, "R", 0, true),
+            token(symbol::LEFT_PARENTHESIS, kind::REL_ARGS, "(", 0, false),
+            token(kind::NAME_ARG, kind::ARG, "a", 1, false),
+            token(symbol::COLON, kind::ARG, ":", 1, false),
+            token(kind::NAME_TYPE, kind::TYPE_USER, "string", 1, true),
+            token(symbol::COMMA, kind::REL_ARGS, ",", 1, false),
+            token(kind::NAME_ARG, kind::ARG, "bcd", 2, false),
+            token(symbol::COLON, kind::ARG, ":", 2, true),
+            token(kind::NAME_TYPE, kind::TYPE_USER, "u32", 2, false),
+            token(symbol::RIGHT_PARENTHESIS, kind::REL_ARGS, ")", 3, false),
+        ];
+        tokens[0].starts_part = true;
+        tokens[3].pad = Some(3);
+        tokens[7].pad = Some(1);
+        let expected = "R(\n    a:   string,\n    bcd: u32\n)";
+        assert_eq!(layout(&tokens, "    "), expected);
+    }
+}
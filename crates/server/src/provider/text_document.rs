@@ -1,7 +1,25 @@
+mod call_hierarchy;
+mod completion;
 mod definition;
 mod diagnostics;
 pub mod document_symbol;
+mod formatting;
+mod hover;
+mod references;
+mod rename;
+mod semantic_tokens;
+mod type_definition;
+mod type_hierarchy;
 
+pub use call_hierarchy::{incoming_calls, outgoing_calls, prepare_call_hierarchy};
+pub use completion::completion;
 pub use definition::definition;
 pub use diagnostics::*;
 pub use document_symbol::document_symbol;
+pub use formatting::{formatting, range_formatting};
+pub use hover::hover;
+pub use references::references;
+pub use rename::{prepare_rename, rename};
+pub use semantic_tokens::{legend as semantic_tokens_legend, semantic_tokens_full, semantic_tokens_full_delta};
+pub use type_definition::type_definition;
+pub use type_hierarchy::{prepare_type_hierarchy, subtypes, supertypes};
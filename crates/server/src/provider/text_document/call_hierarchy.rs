@@ -0,0 +1,426 @@
+use crate::{
+    analysis::symbol::{
+        context::{self, Namespace, Role},
+        occurrence::Occurrence,
+        resolve::{Declaration, Resolver},
+        ScopedName,
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::sync::Arc;
+
+/// A call of a function, or a use of a relation within the body of a rule (which "calls" the
+/// relations of its heads).
+struct CallSite {
+    /// The enclosing function (by its declared name) or the head relations of the enclosing rule.
+    callers: Vec<Occurrence>,
+    callee: Occurrence,
+}
+
+/// A call site whose callers and callee have been resolved to their declarations.
+struct Call {
+    uri: lsp::Url,
+    range: lsp::Range,
+    callers: Vec<Declaration>,
+    callees: Vec<Declaration>,
+}
+
+/// Compute "textDocument/prepareCallHierarchy" for a given document and position.
+pub async fn prepare_call_hierarchy(
+    session: Arc<crate::core::Session>,
+    params: lsp::CallHierarchyPrepareParams,
+) -> anyhow::Result<Option<Vec<lsp::CallHierarchyItem>>> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let mut resolver = Resolver::new(&session);
+    let index = resolver
+        .index(uri)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not index document for uri: {:#?}", uri))?;
+    let occurrence = match index.occurrence_at(position) {
+        Some(occurrence) if [Namespace::Function, Namespace::Relation].contains(&occurrence.namespace) => {
+            occurrence.clone()
+        },
+        _ => return Ok(None),
+    };
+
+    let mut items = vec![];
+    for declaration in resolver.resolve(uri, &occurrence).await {
+        items.push(item(&session, &declaration).await?);
+    }
+    Ok(Some(items).filter(|items| !items.is_empty()))
+}
+
+/// Compute "callHierarchy/incomingCalls" for a given item, i.e., the functions calling it (for
+/// functions) and the relations derived from it (for both functions and relations).
+pub async fn incoming_calls(
+    session: Arc<crate::core::Session>,
+    params: lsp::CallHierarchyIncomingCallsParams,
+) -> anyhow::Result<Option<Vec<lsp::CallHierarchyIncomingCall>>> {
+    let target = declaration(&params.item);
+    let calls = calls(&session, |site| {
+        target.namespace == site.callee.namespace && target.name == site.callee.name.name
+    })
+    .await?;
+
+    let mut incoming: Vec<(Declaration, Vec<lsp::Range>)> = vec![];
+    for call in calls.into_iter().filter(|call| call.callees.contains(&target)) {
+        for caller in call.callers {
+            // the ranges are relative to the caller, so calls from elsewhere can't be included
+            let range = Some(call.range).filter(|_| call.uri == caller.uri);
+            insert(&mut incoming, caller, range);
+        }
+    }
+
+    let mut result = vec![];
+    for (caller, from_ranges) in incoming {
+        let from = item(&session, &caller).await?;
+        result.push(lsp::CallHierarchyIncomingCall { from, from_ranges });
+    }
+    Ok(Some(result))
+}
+
+/// Compute "callHierarchy/outgoingCalls" for a given item, i.e., the functions it calls (for
+/// functions) and the functions and relations its rules use (for relations).
+pub async fn outgoing_calls(
+    session: Arc<crate::core::Session>,
+    params: lsp::CallHierarchyOutgoingCallsParams,
+) -> anyhow::Result<Option<Vec<lsp::CallHierarchyOutgoingCall>>> {
+    let source = declaration(&params.item);
+    let calls = calls(&session, |site| {
+        let mut callers = site.callers.iter();
+        callers.any(|caller| source.namespace == caller.namespace && source.name == caller.name.name)
+    })
+    .await?;
+
+    let mut outgoing: Vec<(Declaration, Vec<lsp::Range>)> = vec![];
+    for call in calls.into_iter().filter(|call| call.callers.contains(&source)) {
+        for callee in call.callees {
+            // the ranges are relative to the item, so calls from elsewhere can't be included
+            let range = Some(call.range).filter(|_| call.uri == source.uri);
+            insert(&mut outgoing, callee, range);
+        }
+    }
+
+    let mut result = vec![];
+    for (callee, from_ranges) in outgoing {
+        let to = item(&session, &callee).await?;
+        result.push(lsp::CallHierarchyOutgoingCall { to, from_ranges });
+    }
+    Ok(Some(result))
+}
+
+// Collect (and resolve) the call sites of every ".dl" document which satisfy a predicate.
+async fn calls(session: &crate::core::Session, predicate: impl Fn(&CallSite) -> bool) -> anyhow::Result<Vec<Call>> {
+    let mut resolver = Resolver::new(session);
+    let mut calls = vec![];
+    for uri in session.document_uris() {
+        if !uri.path().ends_with(".dl") {
+            continue;
+        }
+        let sites = {
+            let text = session.get_text(&uri).await?.clone();
+            let content = text.get_content().await?;
+            let tree = session
+                .get_tree(&uri)
+                .await?
+                .clone()
+                .await
+                .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+            let tree = tree.lock().await;
+            call_sites(&content, &tree)
+        };
+        for site in sites.into_iter().filter(|site| predicate(site)) {
+            let mut callers = vec![];
+            for caller in &site.callers {
+                callers.extend(resolver.resolve(&uri, caller).await);
+            }
+            let callees = resolver.resolve(&uri, &site.callee).await;
+            calls.push(Call {
+                uri: uri.clone(),
+                range: site.callee.range,
+                callers,
+                callees,
+            });
+        }
+    }
+    Ok(calls)
+}
+
+// Collect the call sites of a ".dl" document.
+fn call_sites(content: &ropey::Rope, tree: &tree_sitter::Tree) -> Vec<CallSite> {
+    let mut sites = vec![];
+    let mut work = vec![tree.root_node()];
+    while let Some(node) = work.pop() {
+        let mut cursor = node.walk();
+        work.extend(node.named_children(&mut cursor));
+
+        let is_dot_call = matches!(node.parent(), Some(parent) if dl::kind::EXP_FUN_CALL_DOT == parent.kind_id());
+        let namespace = match (node.kind_id(), context::classify_dl(&node)) {
+            // method-style calls, i.e., `x.f()`
+            (dl::kind::NAME_FUNC, Some((Namespace::Function, Role::Reference))) if is_dot_call => Namespace::Function,
+            // calls, i.e., `f(x)`
+            (dl::kind::NAME_VAR_TERM, Some((Namespace::Function, _))) => Namespace::Function,
+            (dl::kind::NAME_REL, Some((Namespace::Relation, Role::Reference))) => Namespace::Relation,
+            _ => continue,
+        };
+        if let Some(callers) = callers(content, node, namespace) {
+            let callee = occurrence(content, &node, namespace, Role::Reference);
+            sites.push(CallSite { callers, callee });
+        }
+    }
+    sites
+}
+
+// The callers of a call site: its enclosing function, or the head relations of its enclosing rule
+// (for functions called anywhere in the rule, and relations used in its body).
+fn callers(content: &ropey::Rope, node: tree_sitter::Node, namespace: Namespace) -> Option<Vec<Occurrence>> {
+    for ancestor in std::iter::successors(node.parent(), |node| node.parent()) {
+        match ancestor.kind_id() {
+            dl::kind::FUNCTION_NORMAL => {
+                let mut cursor = ancestor.walk();
+                let mut children = ancestor.named_children(&mut cursor);
+                let name = children.find(|child| dl::kind::NAME_FUNC == child.kind_id())?;
+                return Some(vec![occurrence(content, &name, Namespace::Function, Role::Declaration)]);
+            },
+            dl::kind::RULE => {
+                let mut cursor = ancestor.walk();
+                let children = ancestor.children(&mut cursor).collect::<Vec<_>>();
+                let arrow = children
+                    .iter()
+                    .find(|child| dl::symbol::COLON_HYPHEN_MINUS == child.kind_id())?;
+                if Namespace::Relation == namespace && node.start_byte() < arrow.end_byte() {
+                    return None;
+                }
+                let heads = children
+                    .iter()
+                    .take_while(|child| dl::symbol::COLON_HYPHEN_MINUS != child.kind_id())
+                    .filter(|child| dl::kind::ATOM == child.kind_id())
+                    .filter_map(|atom| atom.named_child(0))
+                    .filter_map(|atom| {
+                        let mut cursor = atom.walk();
+                        let mut children = atom.named_children(&mut cursor);
+                        children.find(|child| dl::kind::NAME_REL == child.kind_id())
+                    })
+                    .map(|name| occurrence(content, &name, Namespace::Relation, Role::Reference))
+                    .collect();
+                return Some(heads);
+            },
+            dl::kind::ANNOTATED_ITEM => return None,
+            _ => {},
+        }
+    }
+    None
+}
+
+fn occurrence(content: &ropey::Rope, node: &tree_sitter::Node, namespace: Namespace, role: Role) -> Occurrence {
+    Occurrence {
+        name: ScopedName::parse(&content.utf8_text_for_tree_sitter_node(node)),
+        namespace,
+        role,
+        range: content.tree_sitter_range_to_lsp_range(node.range()),
+    }
+}
+
+// Add a range to the entry for a declaration, keeping the declarations in order of appearance.
+fn insert(entries: &mut Vec<(Declaration, Vec<lsp::Range>)>, declaration: Declaration, range: Option<lsp::Range>) {
+    let index = match entries.iter().position(|(other, _)| *other == declaration) {
+        Some(index) => index,
+        None => {
+            entries.push((declaration, vec![]));
+            entries.len() - 1
+        },
+    };
+    entries[index].1.extend(range);
+}
+
+// Construct the call hierarchy item for a declaration.
+async fn item(session: &crate::core::Session, declaration: &Declaration) -> anyhow::Result<lsp::CallHierarchyItem> {
+    let (kind, kinds): (lsp::SymbolKind, &[u16]) = match declaration.namespace {
+        Namespace::Function => (lsp::SymbolKind::FUNCTION, &[
+            dl::kind::FUNCTION_EXTERN,
+            dl::kind::FUNCTION_NORMAL,
+        ]),
+        _ => (lsp::SymbolKind::STRUCT, &[
+            dl::kind::APPLY,
+            dl::kind::REL_ARGS,
+            dl::kind::REL_ELEM,
+        ]),
+    };
+    let range = declaration
+        .with_node(session, kinds, |content, node| {
+            content.tree_sitter_range_to_lsp_range(node.range())
+        })
+        .await?;
+    Ok(lsp::CallHierarchyItem {
+        name: declaration.name.clone(),
+        kind,
+        tags: Default::default(),
+        detail: Default::default(),
+        uri: declaration.uri.clone(),
+        range: range.unwrap_or(declaration.range),
+        selection_range: declaration.range,
+        data: Default::default(),
+    })
+}
+
+// Recover the declaration from a call hierarchy item (see `item`).
+fn declaration(item: &lsp::CallHierarchyItem) -> Declaration {
+    let namespace = match item.kind {
+        lsp::SymbolKind::FUNCTION => Namespace::Function,
+        _ => Namespace::Relation,
+    };
+    Declaration {
+        uri: item.uri.clone(),
+        namespace,
+        name: item.name.clone(),
+        range: item.selection_range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Session;
+
+    fn range(line: u32, start: u32, end: u32) -> lsp::Range {
+        lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, end))
+    }
+
+    async fn prepare(session: &Arc<Session>, uri: &lsp::Url, line: u32, character: u32) -> lsp::CallHierarchyItem {
+        let params = lsp::CallHierarchyPrepareParams {
+            text_document_position_params: lsp::TextDocumentPositionParams::new(
+                lsp::TextDocumentIdentifier::new(uri.clone()),
+                lsp::Position::new(line, character),
+            ),
+            work_done_progress_params: Default::default(),
+        };
+        let items = prepare_call_hierarchy(session.clone(), params).await.unwrap();
+        items.unwrap().remove(0)
+    }
+
+    // The callers of an item, by name and document, with the ranges of their calls (sorted by name).
+    async fn incoming(
+        session: &Arc<Session>,
+        item: lsp::CallHierarchyItem,
+    ) -> Vec<(String, lsp::Url, Vec<lsp::Range>)> {
+        let params = lsp::CallHierarchyIncomingCallsParams {
+            item,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let calls = incoming_calls(session.clone(), params)
+            .await
+            .unwrap()
+            .unwrap_or_default();
+        let mut calls = calls
+            .into_iter()
+            .map(|call| (call.from.name, call.from.uri, call.from_ranges))
+            .collect::<Vec<_>>();
+        calls.sort_by(|a, b| a.0.cmp(&b.0));
+        calls
+    }
+
+    // The callees of an item, by name and document, with the ranges of their calls (sorted by name).
+    async fn outgoing(
+        session: &Arc<Session>,
+        item: lsp::CallHierarchyItem,
+    ) -> Vec<(String, lsp::Url, Vec<lsp::Range>)> {
+        let params = lsp::CallHierarchyOutgoingCallsParams {
+            item,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let calls = outgoing_calls(session.clone(), params)
+            .await
+            .unwrap()
+            .unwrap_or_default();
+        let mut calls = calls
+            .into_iter()
+            .map(|call| (call.to.name, call.to.uri, call.from_ranges))
+            .collect::<Vec<_>>();
+        calls.sort_by(|a, b| a.0.cmp(&b.0));
+        calls
+    }
+
+    #[tokio::test]
+    async fn rules_call_the_functions_and_relations_of_their_bodies() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let text = indoc::indoc! {"
+            input relation A(x: u32)
+            output relation B(x: u32)
+            function inc(x: u32): u32 { x + 1 }
+            B(y) :- A(x), var y = inc(x).
+        "};
+        let uri = session.insert_text("/program/main.dl", text).await;
+
+        let b = prepare(&session, &uri, 1, 16).await;
+        let expected = vec![
+            (String::from("A"), uri.clone(), vec![range(3, 8, 9)]),
+            (String::from("inc"), uri.clone(), vec![range(3, 22, 25)]),
+        ];
+        assert_eq!(expected, outgoing(&session, b).await);
+
+        let inc = prepare(&session, &uri, 2, 9).await;
+        let expected = vec![(String::from("B"), uri.clone(), vec![range(3, 22, 25)])];
+        assert_eq!(expected, incoming(&session, inc).await);
+        let a = prepare(&session, &uri, 0, 15).await;
+        let expected = vec![(String::from("B"), uri.clone(), vec![range(3, 8, 9)])];
+        assert_eq!(expected, incoming(&session, a).await);
+    }
+
+    #[tokio::test]
+    async fn functions_call_functions() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let text = indoc::indoc! {"
+            function f(x: u32): u32 { g(x) + x.h() }
+            function g(x: u32): u32 { x }
+            function h(x: u32): u32 { x }
+        "};
+        let uri = session.insert_text("/program/main.dl", text).await;
+
+        let f = prepare(&session, &uri, 0, 9).await;
+        let expected = vec![
+            (String::from("g"), uri.clone(), vec![range(0, 26, 27)]),
+            (String::from("h"), uri.clone(), vec![range(0, 35, 36)]),
+        ];
+        assert_eq!(expected, outgoing(&session, f).await);
+
+        let h = prepare(&session, &uri, 2, 9).await;
+        let expected = vec![(String::from("f"), uri.clone(), vec![range(0, 35, 36)])];
+        assert_eq!(expected, incoming(&session, h).await);
+        let g = prepare(&session, &uri, 1, 9).await;
+        assert_eq!(
+            Vec::<(String, lsp::Url, Vec<lsp::Range>)>::new(),
+            outgoing(&session, g).await
+        );
+    }
+
+    #[tokio::test]
+    async fn calls_across_modules() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let lib = session
+            .insert_text("/program/lib.dl", "function g(x: u32): u32 { x }\n")
+            .await;
+        let text = indoc::indoc! {"
+            import lib
+            input relation A(x: u32)
+            output relation B(x: u32)
+            B(y) :- A(x), var y = g(x).
+        "};
+        let main = session.insert_text("/program/main.dl", text).await;
+
+        // the ranges of the calls are within the rules of `main`, i.e., the callers
+        let g = prepare(&session, &lib, 0, 9).await;
+        let expected = vec![(String::from("B"), main.clone(), vec![range(3, 22, 23)])];
+        assert_eq!(expected, incoming(&session, g).await);
+        let b = prepare(&session, &main, 2, 16).await;
+        let expected = vec![
+            (String::from("A"), main.clone(), vec![range(3, 8, 9)]),
+            (String::from("g"), lib.clone(), vec![range(3, 22, 23)]),
+        ];
+        assert_eq!(expected, outgoing(&session, b).await);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::analysis::symbol::context::Namespace;
+use std::sync::Arc;
+
+mod dat;
+mod dl;
+
+/// Compute "textDocument/completion" for a given document.
+pub async fn completion(
+    session: Arc<crate::core::Session>,
+    params: lsp::CompletionParams,
+) -> anyhow::Result<Option<lsp::CompletionResponse>> {
+    let uri = &params.text_document_position.text_document.uri;
+    let language = session.get_text(uri).await?.language;
+    let items = match language {
+        crate::core::Language::DDlogDat => self::dat::completion(&session, &params).await?,
+        crate::core::Language::DDlogDl => self::dl::completion(&session, &params).await?,
+    };
+    Ok(Some(lsp::CompletionResponse::Array(items)))
+}
+
+/// The kind of completion item used for entities of a given namespace.
+pub fn item_kind(namespace: Namespace) -> lsp::CompletionItemKind {
+    match namespace {
+        Namespace::Constructor => lsp::CompletionItemKind::CONSTRUCTOR,
+        Namespace::Function => lsp::CompletionItemKind::FUNCTION,
+        Namespace::Index => lsp::CompletionItemKind::REFERENCE,
+        Namespace::Relation => lsp::CompletionItemKind::STRUCT,
+        Namespace::Transformer => lsp::CompletionItemKind::OPERATOR,
+        Namespace::Type => lsp::CompletionItemKind::CLASS,
+    }
+}
+
+/// Create completion items for the given keywords (by node kind id).
+pub fn keyword_items(language: tree_sitter::Language, keywords: &[u16]) -> Vec<lsp::CompletionItem> {
+    keywords
+        .iter()
+        .filter_map(|id| language.node_kind_for_id(*id))
+        .map(|keyword| lsp::CompletionItem {
+            label: keyword.into_owned(),
+            kind: Some(lsp::CompletionItemKind::KEYWORD),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Split the text preceding the cursor into the part before the identifier under the cursor (if
+/// any) and that identifier (the prefix being completed).
+pub fn split_prefix(text_before: &str) -> (&str, &str) {
+    let prefix_len = text_before
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || '_' == *c)
+        .map(char::len_utf8)
+        .sum::<usize>();
+    text_before.split_at(text_before.len() - prefix_len)
+}
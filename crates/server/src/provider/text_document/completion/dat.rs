@@ -0,0 +1,338 @@
+use crate::{
+    analysis::{
+        schema::Schema,
+        symbol::{context::Namespace, resolve::Resolver},
+        types::child_of_kind,
+    },
+    core::language::{dat, dl},
+};
+use lsp_text::RopeExt;
+use std::collections::HashSet;
+
+// Keywords which may begin a command.
+const COMMAND_KEYWORDS: &[u16] = &[
+    dat::keyword::CLEAR,
+    dat::keyword::COMMIT,
+    dat::keyword::DELETE,
+    dat::keyword::DELETE_KEY,
+    dat::keyword::DUMP,
+    dat::keyword::DUMP_INDEX,
+    dat::keyword::ECHO,
+    dat::keyword::EXIT,
+    dat::keyword::INSERT,
+    dat::keyword::INSERT_OR_UPDATE,
+    dat::keyword::LOG_LEVEL,
+    dat::keyword::MODIFY,
+    dat::keyword::PROFILE,
+    dat::keyword::QUERY_INDEX,
+    dat::keyword::ROLLBACK,
+    dat::keyword::SLEEP,
+    dat::keyword::START,
+    dat::keyword::TIMESTAMP,
+];
+
+// Keywords which may begin an update following another one (separated by `,`).
+const UPDATE_KEYWORDS: &[u16] = &[
+    dat::keyword::DELETE,
+    dat::keyword::DELETE_KEY,
+    dat::keyword::INSERT,
+    dat::keyword::INSERT_OR_UPDATE,
+    dat::keyword::MODIFY,
+];
+
+/// The syntactic context of the cursor, which determines the kind of completions offered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Context {
+    /// The beginning of a command.
+    Command,
+    /// The beginning of an update within a list of updates.
+    Update,
+    /// A relation name, e.g., after `insert`.
+    Relation { inputs_only: bool, skeleton: bool },
+    /// An index name, e.g., after `query_index`.
+    Index { skeleton: bool },
+    /// Anywhere else (e.g., within a value).
+    Other,
+}
+
+/// A relation or index declared in one of the program's ".dl" files.
+#[derive(Clone, Debug)]
+struct Entry {
+    namespace: Namespace,
+    name: String,
+    schema: Schema,
+}
+
+impl Entry {
+    // The signature of the relation or index, e.g., `R(a: u32, b: string)`.
+    fn signature(&self) -> String {
+        if let Some(element) = &self.schema.element {
+            return format!("{}[{}]", self.name, element);
+        }
+        let args = self.schema.fields.iter().map(|(name, r#type)| match r#type {
+            Some(r#type) => format!("{}: {}", name, r#type),
+            None => name.clone(),
+        });
+        format!("{}({})", self.name, args.collect::<Vec<_>>().join(", "))
+    }
+
+    // A snippet of the record of the relation (with named fields, unless `positional`), or of the
+    // key of the index.
+    fn skeleton(&self, positional: bool) -> String {
+        if let Some(element) = &self.schema.element {
+            return format!("{}[${{1:{}}}]", self.name, escape(element));
+        }
+        let args = self.schema.fields.iter().enumerate().map(|(i, (name, r#type))| {
+            if positional || Namespace::Index == self.namespace {
+                return format!("${{{}:{}}}", i + 1, escape(name));
+            }
+            let r#type = r#type.as_deref().unwrap_or(name);
+            format!(".{} = ${{{}:{}}}", name, i + 1, escape(r#type))
+        });
+        format!("{}({})", self.name, args.collect::<Vec<_>>().join(", "))
+    }
+}
+
+// Escape the characters with special meaning within snippet placeholders.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}")
+}
+
+pub async fn completion(
+    session: &crate::core::Session,
+    params: &lsp::CompletionParams,
+) -> anyhow::Result<Vec<lsp::CompletionItem>> {
+    let uri = &params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let char_offset = content.lsp_position_to_core(position)?.char as usize;
+    let context = context(&content.slice(.. char_offset).to_string());
+
+    let language = ddlog_lsp_languages::language::dat();
+    let items = match context {
+        Context::Command => super::keyword_items(language, COMMAND_KEYWORDS),
+        Context::Update => super::keyword_items(language, UPDATE_KEYWORDS),
+        Context::Relation { inputs_only, skeleton } => {
+            let entries = entries(session, uri).await.into_iter();
+            let entries =
+                entries.filter(|entry| Namespace::Relation == entry.namespace && (entry.schema.input || !inputs_only));
+            entry_items(session, entries, skeleton).await
+        },
+        Context::Index { skeleton } => {
+            let entries = entries(session, uri).await.into_iter();
+            let entries = entries.filter(|entry| Namespace::Index == entry.namespace);
+            entry_items(session, entries, skeleton).await
+        },
+        Context::Other => vec![],
+    };
+
+    Ok(items)
+}
+
+// Determine the completion context from the text preceding the cursor.
+fn context(text_before: &str) -> Context {
+    let (before_prefix, _) = super::split_prefix(text_before);
+    // commands are terminated by `;`
+    let command = before_prefix.rsplit(';').next().unwrap_or_default();
+    let trimmed = command.trim_end();
+    if trimmed.trim_start().is_empty() {
+        return Context::Command;
+    }
+
+    let depth = command.chars().fold(0, |depth, c| match c {
+        '(' | '[' | '{' => depth + 1,
+        ')' | ']' | '}' => depth - 1,
+        _ => depth,
+    });
+    if 0 == depth && trimmed.ends_with(',') {
+        return Context::Update;
+    }
+
+    let (_, keyword) = super::split_prefix(trimmed);
+    match keyword {
+        "insert" | "insert_or_update" | "delete" => Context::Relation {
+            inputs_only: true,
+            skeleton: true,
+        },
+        "delete_key" | "modify" => Context::Relation {
+            inputs_only: true,
+            skeleton: false,
+        },
+        "dump" => Context::Relation {
+            inputs_only: false,
+            skeleton: false,
+        },
+        "query_index" => Context::Index { skeleton: true },
+        "dump_index" => Context::Index { skeleton: false },
+        _ => Context::Other,
+    }
+}
+
+// Create the completion items for relations or indexes, optionally as a snippet of their record.
+async fn entry_items(
+    session: &crate::core::Session,
+    entries: impl Iterator<Item = Entry>,
+    skeleton: bool,
+) -> Vec<lsp::CompletionItem> {
+    let snippet_support = {
+        let capabilities = session.client_capabilities.read().await;
+        let completion_item = capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.text_document.as_ref())
+            .and_then(|text_document| text_document.completion.as_ref())
+            .and_then(|completion| completion.completion_item.as_ref());
+        completion_item.and_then(|item| item.snippet_support).unwrap_or(false)
+    };
+
+    let mut items = vec![];
+    let mut seen = HashSet::new();
+    for entry in entries {
+        if !seen.insert(entry.name.clone()) {
+            continue;
+        }
+        let mut item = lsp::CompletionItem {
+            label: entry.name.clone(),
+            kind: Some(super::item_kind(entry.namespace)),
+            detail: Some(entry.signature()),
+            ..Default::default()
+        };
+        if skeleton && snippet_support {
+            item.insert_text = Some(entry.skeleton(false));
+            item.insert_text_format = Some(lsp::InsertTextFormat::SNIPPET);
+            // records may also be given by position, e.g., `R(1, "a")`
+            let named = Namespace::Relation == entry.namespace && entry.schema.element.is_none();
+            if named && !entry.schema.fields.is_empty() {
+                let positional = lsp::CompletionItem {
+                    label: format!("{}(..)", entry.name),
+                    filter_text: Some(entry.name.clone()),
+                    insert_text: Some(entry.skeleton(true)),
+                    ..item.clone()
+                };
+                items.push(item);
+                items.push(positional);
+                continue;
+            }
+        }
+        items.push(item);
+    }
+    items
+}
+
+// Collect the relations and indexes declared in the modules of the program associated with the
+// ".dat" file at `uri` or, without an associated program, in the ".dl" files known to the session.
+// Modules which can't be read are skipped.
+async fn entries(session: &crate::core::Session, uri: &lsp::Url) -> Vec<Entry> {
+    let modules = match session.program(uri).await {
+        Some(main) => Resolver::new(session).program(&main).await,
+        None => session.document_uris(),
+    };
+    let mut entries = vec![];
+    for uri in modules.iter().filter(|uri| uri.path().ends_with(".dl")) {
+        let text = match session.get_text(uri).await {
+            Ok(text) => text.clone(),
+            Err(_) => continue,
+        };
+        let content = match text.get_content().await {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let tree = match session.get_tree(uri).await {
+            Ok(tree) => tree.clone().await,
+            Err(_) => continue,
+        };
+        if let Some(tree) = tree {
+            let tree = tree.lock().await;
+            collect_entries(&content, &tree, &mut entries);
+        }
+    }
+    entries
+}
+
+fn collect_entries(content: &ropey::Rope, tree: &tree_sitter::Tree, entries: &mut Vec<Entry>) {
+    let mut work = vec![tree.root_node()];
+    while let Some(node) = work.pop() {
+        let namespace = match node.kind_id() {
+            dl::kind::REL_ARGS | dl::kind::REL_ELEM => Namespace::Relation,
+            dl::kind::INDEX => Namespace::Index,
+            _ => {
+                let mut cursor = node.walk();
+                work.extend(node.named_children(&mut cursor));
+                continue;
+            },
+        };
+        let name = match child_of_kind(node, &[dl::kind::NAME_REL, dl::kind::NAME_INDEX]) {
+            Some(name) => content.utf8_text_for_tree_sitter_node(&name).into_owned(),
+            None => continue,
+        };
+        entries.push(Entry {
+            namespace,
+            name,
+            schema: Schema::read(content, node),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_begin_after_a_semicolon() {
+        assert_eq!(Context::Command, context(""));
+        assert_eq!(Context::Command, context("ins"));
+        assert_eq!(Context::Command, context("start;\n"));
+        assert_eq!(Context::Command, context("insert R(1);\n  com"));
+    }
+
+    #[test]
+    fn updates_follow_a_comma_outside_of_a_record() {
+        assert_eq!(Context::Update, context("start;\ninsert R(1),\n"));
+        assert_eq!(Context::Update, context("insert R(1, \"a\"), del"));
+        assert_eq!(Context::Other, context("insert R(1, "));
+    }
+
+    #[test]
+    fn relations_and_indexes_follow_their_keywords() {
+        let relation = |inputs_only, skeleton| Context::Relation { inputs_only, skeleton };
+        assert_eq!(relation(true, true), context("insert "));
+        assert_eq!(relation(true, true), context("insert R(1), delete R"));
+        assert_eq!(relation(true, true), context("insert_or_update "));
+        assert_eq!(relation(true, false), context("delete_key R"));
+        assert_eq!(relation(true, false), context("modify "));
+        assert_eq!(relation(false, false), context("dump "));
+        assert_eq!(Context::Index { skeleton: true }, context("query_index I"));
+        assert_eq!(Context::Index { skeleton: false }, context("dump_index "));
+    }
+
+    #[test]
+    fn values_are_not_completed() {
+        assert_eq!(Context::Other, context("insert R("));
+        assert_eq!(Context::Other, context("insert R(.a = tr"));
+        assert_eq!(Context::Other, context("echo hel"));
+    }
+
+    #[test]
+    fn records_are_given_by_name_or_by_position() {
+        let field = |name: &str, r#type: &str| (String::from(name), Some(String::from(r#type)));
+        let entry = |namespace, fields, element: Option<&str>| Entry {
+            namespace,
+            name: String::from("R"),
+            schema: Schema {
+                input: true,
+                fields,
+                element: element.map(String::from),
+            },
+        };
+        let relation = entry(Namespace::Relation, vec![field("a", "u32"), field("b", "string")], None);
+        assert_eq!("R(a: u32, b: string)", relation.signature());
+        assert_eq!("R(.a = ${1:u32}, .b = ${2:string})", relation.skeleton(false));
+        assert_eq!("R(${1:a}, ${2:b})", relation.skeleton(true));
+        let index = entry(Namespace::Index, vec![field("k", "u32")], None);
+        assert_eq!("R(${1:k})", index.skeleton(false));
+        let element = entry(Namespace::Relation, vec![], Some("Map<u32, $T>"));
+        assert_eq!("R[Map<u32, $T>]", element.signature());
+        assert_eq!("R[${1:Map<u32, \\$T>}]", element.skeleton(false));
+    }
+}
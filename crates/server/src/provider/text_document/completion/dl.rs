@@ -0,0 +1,609 @@
+use crate::{
+    analysis::{
+        symbol::{
+            context::{Namespace, Role},
+            resolve::Resolver,
+            ScopedName,
+        },
+        tree::{first_of_kind, named_children},
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::{collections::HashSet, path::PathBuf};
+
+// Keywords which may begin a top-level item.
+const ITEM_KEYWORDS: &[u16] = &[
+    dl::keyword::APPLY,
+    dl::keyword::EXTERN,
+    dl::keyword::FUNCTION,
+    dl::keyword::IMPORT,
+    dl::keyword::INDEX,
+    dl::keyword::INPUT,
+    dl::keyword::INTERNAL,
+    dl::keyword::MULTISET,
+    dl::keyword::OUTPUT,
+    dl::keyword::RELATION,
+    dl::keyword::STREAM,
+    dl::keyword::TRANSFORMER,
+    dl::keyword::TYPEDEF,
+];
+
+// Keywords which may occur within an expression.
+const EXPRESSION_KEYWORDS: &[u16] = &[
+    dl::keyword::AND,
+    dl::keyword::ELSE,
+    dl::keyword::FALSE,
+    dl::keyword::FOR,
+    dl::keyword::IF,
+    dl::keyword::IN,
+    dl::keyword::MATCH,
+    dl::keyword::NOT,
+    dl::keyword::OR,
+    dl::keyword::RETURN,
+    dl::keyword::TRUE,
+    dl::keyword::VAR,
+];
+
+// Keywords which may begin a clause of a rule body (in addition to those of expressions).
+const RULE_BODY_KEYWORDS: &[u16] = &[dl::keyword::FLAT_MAP, dl::keyword::INSPECT];
+
+// Keywords which may begin a type.
+const TYPE_KEYWORDS: &[u16] = &[dl::keyword::BIT, dl::keyword::SIGNED];
+
+/// Where the value whose fields are being completed comes from.
+#[derive(Clone, Debug, PartialEq)]
+enum FieldSource {
+    /// A variable annotated with the given type.
+    Type(ScopedName),
+    /// A variable bound by position within an atom of the given relation.
+    RelationPosition(ScopedName, usize),
+    /// A variable bound by name within an atom of the given relation.
+    RelationField(ScopedName, String),
+}
+
+/// The syntactic context of the cursor, which determines the kind of completions offered.
+#[derive(Clone, Debug, PartialEq)]
+enum Context {
+    /// A module path after `import`.
+    Import,
+    /// A field name after `.` (if the type of the value could be determined).
+    Field(Option<FieldSource>),
+    /// A type, e.g., after `:` or within type arguments.
+    Type,
+    /// A pattern, e.g., within a `match` arm.
+    Pattern,
+    /// The head or body of a rule.
+    Rule,
+    /// An expression outside of a rule, e.g., in the body of a function.
+    Expression,
+    /// The beginning of a top-level item.
+    Item,
+}
+
+impl Context {
+    fn namespaces(&self) -> &'static [Namespace] {
+        match self {
+            Context::Import | Context::Field(_) => &[],
+            Context::Type => &[Namespace::Type],
+            Context::Pattern => &[Namespace::Constructor],
+            Context::Rule => &[Namespace::Constructor, Namespace::Function, Namespace::Relation],
+            Context::Expression => &[Namespace::Constructor, Namespace::Function],
+            Context::Item => &[Namespace::Relation],
+        }
+    }
+
+    fn keywords(&self) -> Vec<u16> {
+        match self {
+            Context::Import | Context::Field(_) | Context::Pattern => vec![],
+            Context::Type => TYPE_KEYWORDS.to_vec(),
+            Context::Rule => [EXPRESSION_KEYWORDS, RULE_BODY_KEYWORDS].concat(),
+            Context::Expression => EXPRESSION_KEYWORDS.to_vec(),
+            Context::Item => ITEM_KEYWORDS.to_vec(),
+        }
+    }
+}
+
+pub async fn completion(
+    session: &crate::core::Session,
+    params: &lsp::CompletionParams,
+) -> anyhow::Result<Vec<lsp::CompletionItem>> {
+    let uri = &params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let (context, qualifier) = {
+        let tree = session
+            .get_tree(uri)
+            .await?
+            .clone()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+        let tree = tree.lock().await;
+        let position = content.lsp_position_to_core(position)?;
+        let text_before = content.slice(.. position.char as usize).to_string();
+        let (before_prefix, _) = super::split_prefix(&text_before);
+        (
+            context(&content, &tree, position.char as usize),
+            qualifier(before_prefix),
+        )
+    };
+
+    let mut resolver = Resolver::new(session);
+    // only names declared in the module(s) the qualifier refers to can follow it
+    if let Some(qualifier) = qualifier {
+        let namespaces = context.namespaces();
+        return Ok(qualified_items(&mut resolver, uri, qualifier, namespaces).await);
+    }
+
+    let mut items = super::keyword_items(ddlog_lsp_languages::language::dl(), &context.keywords());
+    match &context {
+        Context::Import => items.extend(module_items(session, uri).await),
+        Context::Field(source) => {
+            if let Some(source) = source {
+                items.extend(field_items(session, &mut resolver, uri, source).await?);
+            }
+        },
+        _ => items.extend(declaration_items(session, &mut resolver, uri, context.namespaces()).await),
+    }
+
+    Ok(items)
+}
+
+// The qualifier (e.g., `m::n` in `m::n::prefix`) of the name being completed, given the text
+// preceding the prefix, if the name is qualified.
+fn qualifier(before_prefix: &str) -> Option<Vec<String>> {
+    let path = before_prefix.strip_suffix("::")?;
+    let path_len = path
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || '_' == *c || ':' == *c)
+        .map(char::len_utf8)
+        .sum::<usize>();
+    let path = &path[path.len() - path_len ..];
+    if path.is_empty() {
+        return None;
+    }
+    Some(path.split("::").map(String::from).collect())
+}
+
+// Determine the completion context at the given (char) offset of the cursor.
+fn context(content: &ropey::Rope, tree: &tree_sitter::Tree, char_offset: usize) -> Context {
+    let text_before = content.slice(.. char_offset).to_string();
+    let line_before = text_before.rsplit('\n').next().unwrap_or_default();
+    let (before_prefix, _) = super::split_prefix(&text_before);
+
+    let after_import = line_before.trim_start().strip_prefix("import");
+    if matches!(after_import, Some(rest) if rest.starts_with(char::is_whitespace)) {
+        return Context::Import;
+    }
+
+    let trimmed = before_prefix.trim_end();
+    let root = tree.root_node();
+
+    // the top-level node (item or error) the cursor is within or directly after
+    let anchor = trimmed.len().saturating_sub(1) as u32;
+    let mut cursor = root.walk();
+    let top_level = root
+        .children(&mut cursor)
+        .filter(|child| child.start_byte() <= anchor)
+        .last();
+
+    if let Some(receiver) = before_prefix.strip_suffix('.') {
+        let (_, variable) = super::split_prefix(receiver);
+        if variable.starts_with(|c: char| c.is_lowercase() || '_' == c) {
+            let source = top_level.and_then(|node| field_source(content, node, variable));
+            return Context::Field(source);
+        }
+    }
+
+    if trimmed.ends_with(':') && !trimmed.ends_with("::") {
+        return Context::Type;
+    }
+
+    // classify by the innermost node enclosing the prefix (or the token preceding the cursor)
+    // (byte offsets into the text preceding the cursor are also byte offsets into the document)
+    let node = if before_prefix.len() < text_before.len() {
+        let start = before_prefix.len() as u32;
+        root.named_descendant_for_byte_range(start, start)
+    } else {
+        root.descendant_for_byte_range(anchor, anchor)
+    };
+    let language = ddlog_lsp_languages::language::dl();
+    let mut ancestor = node;
+    while let Some(node) = ancestor {
+        let kind = language.node_kind_for_id(node.kind_id()).unwrap_or_default();
+        if node.is_error() {
+            break;
+        } else if kind.starts_with("pat") {
+            return Context::Pattern;
+        } else if kind == "type" || kind.starts_with("type_") {
+            return Context::Type;
+        } else if dl::kind::RULE == node.kind_id() {
+            return Context::Rule;
+        } else if kind.starts_with("exp") || kind.starts_with("statement") {
+            let in_rule =
+                std::iter::successors(node.parent(), |node| node.parent()).any(|node| dl::kind::RULE == node.kind_id());
+            return if in_rule { Context::Rule } else { Context::Expression };
+        } else if [dl::kind::ROOT, dl::kind::ANNOTATED_ITEM, dl::kind::ITEM].contains(&node.kind_id()) {
+            break;
+        }
+        ancestor = node.parent();
+    }
+
+    // fall back to the text of the (possibly malformed) item for incomplete input
+    let item_text = top_level.map_or(String::new(), |node| {
+        let start = content.byte_to_char(node.start_byte() as usize).min(char_offset);
+        content.slice(start .. char_offset).to_string()
+    });
+    if item_text.contains(":-") {
+        Context::Rule
+    } else if item_text.contains("function") && (item_text.contains('=') || item_text.contains('{')) {
+        Context::Expression
+    } else {
+        Context::Item
+    }
+}
+
+// Determine the type (or the relation argument providing the type) of a variable within an item.
+fn field_source(content: &ropey::Rope, item: tree_sitter::Node, variable: &str) -> Option<FieldSource> {
+    let text = |node: &tree_sitter::Node| content.utf8_text_for_tree_sitter_node(node).into_owned();
+    // whether an expression consists only of the variable (optionally declared with `var`)
+    let is_variable = |exp: &tree_sitter::Node| {
+        let decl_var = named_children(exp).into_iter().next();
+        let decl_var = decl_var.filter(|node| dl::kind::EXP_DECL_VAR == node.kind_id());
+        let name = decl_var.and_then(|node| named_children(&node).into_iter().next());
+        matches!(name, Some(name) if text(&name) == variable)
+    };
+
+    let mut work = vec![item];
+    while let Some(node) = work.pop() {
+        let children = named_children(&node);
+        match node.kind_id() {
+            dl::kind::ARG | dl::kind::ARG_OPT_TYPE => {
+                let name = children.iter().find(|child| dl::kind::NAME_ARG == child.kind_id());
+                if matches!(name, Some(name) if text(name) == variable) {
+                    let name_type = children
+                        .iter()
+                        .find_map(|child| first_of_kind(*child, dl::kind::NAME_TYPE));
+                    return name_type.map(|name_type| FieldSource::Type(ScopedName::parse(&text(&name_type))));
+                }
+            },
+            dl::kind::ATOM_POS => {
+                let relation = children.iter().find(|child| dl::kind::NAME_REL == child.kind_id());
+                let exps = children.iter().filter(|child| dl::kind::EXP == child.kind_id());
+                if let (Some(relation), Some(position)) = (relation, exps.clone().position(is_variable)) {
+                    return Some(FieldSource::RelationPosition(
+                        ScopedName::parse(&text(relation)),
+                        position,
+                    ));
+                }
+            },
+            dl::kind::ATOM_REC => {
+                let relation = children.iter().find(|child| dl::kind::NAME_REL == child.kind_id());
+                let mut fields = children.iter().zip(children.iter().skip(1));
+                let field = fields.find(|(name, exp)| {
+                    dl::kind::NAME_ARG == name.kind_id() && dl::kind::EXP == exp.kind_id() && is_variable(exp)
+                });
+                if let (Some(relation), Some((name, _))) = (relation, field) {
+                    return Some(FieldSource::RelationField(
+                        ScopedName::parse(&text(relation)),
+                        text(name),
+                    ));
+                }
+            },
+            _ => {},
+        }
+        work.extend(children.into_iter().rev());
+    }
+    None
+}
+
+// Complete the fields of the (record) type of a value.
+async fn field_items(
+    session: &crate::core::Session,
+    resolver: &mut Resolver<'_>,
+    uri: &lsp::Url,
+    source: &FieldSource,
+) -> anyhow::Result<Vec<lsp::CompletionItem>> {
+    // the name of the type along with the module it is referred to from
+    let mut types = vec![];
+    match source {
+        FieldSource::Type(name) => types.push((uri.clone(), name.clone())),
+        FieldSource::RelationPosition(relation, _) | FieldSource::RelationField(relation, _) => {
+            for declaration in resolver.resolve_name(uri, Namespace::Relation, relation.clone()).await {
+                let argument_type = declaration
+                    .with_node(session, &[dl::kind::REL_ARGS], |content, node| {
+                        let mut cursor = node.walk();
+                        let mut args = node
+                            .named_children(&mut cursor)
+                            .filter(|child| dl::kind::ARG == child.kind_id());
+                        let arg = match source {
+                            FieldSource::RelationPosition(_, position) => args.nth(*position),
+                            FieldSource::RelationField(_, field) => args.find(|arg| {
+                                let name = first_of_kind(*arg, dl::kind::NAME_ARG);
+                                let name = name.map(|name| content.utf8_text_for_tree_sitter_node(&name));
+                                matches!(name, Some(name) if name == field.as_str())
+                            }),
+                            FieldSource::Type(_) => None,
+                        };
+                        let name_type = arg.and_then(|arg| first_of_kind(arg, dl::kind::NAME_TYPE));
+                        name_type
+                            .map(|name_type| ScopedName::parse(&content.utf8_text_for_tree_sitter_node(&name_type)))
+                    })
+                    .await?;
+                if let Some(name) = argument_type.flatten() {
+                    types.push((declaration.uri.clone(), name));
+                }
+            }
+        },
+    }
+
+    let mut items = vec![];
+    let mut seen = HashSet::new();
+    for (origin, name) in types {
+        for declaration in resolver.resolve_name(&origin, Namespace::Type, name).await {
+            let fields = declaration
+                .with_node(session, &[dl::kind::TYPEDEF_NORMAL], |content, node| {
+                    let mut fields = vec![];
+                    let mut work = vec![node];
+                    while let Some(node) = work.pop() {
+                        let children = named_children(&node);
+                        if dl::kind::FIELD == node.kind_id() {
+                            let name = children.iter().find(|child| dl::kind::NAME_FIELD == child.kind_id());
+                            let r#type = children.last().filter(|child| dl::kind::NAME_FIELD != child.kind_id());
+                            if let Some(name) = name {
+                                let name = content.utf8_text_for_tree_sitter_node(name).into_owned();
+                                let r#type =
+                                    r#type.map(|node| content.utf8_text_for_tree_sitter_node(node).into_owned());
+                                fields.push((name, r#type));
+                            }
+                            continue;
+                        }
+                        work.extend(children.into_iter().rev());
+                    }
+                    fields
+                })
+                .await?;
+            for (label, detail) in fields.unwrap_or_default() {
+                if seen.insert(label.clone()) {
+                    items.push(lsp::CompletionItem {
+                        label,
+                        kind: Some(lsp::CompletionItemKind::FIELD),
+                        detail,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+    Ok(items)
+}
+
+// The path by which the module at `target` would be imported, given the directories imports are
+// resolved against (see `Session::import_roots`), if it can be imported at all.
+fn module_path(roots: &[PathBuf], target: &lsp::Url) -> Option<String> {
+    let path = target.to_file_path().ok()?;
+    let relative = roots.iter().find_map(|root| path.strip_prefix(root).ok())?;
+    let relative = relative.to_str()?.strip_suffix(".dl")?;
+    Some(relative.split(std::path::MAIN_SEPARATOR).collect::<Vec<_>>().join("::"))
+}
+
+// Complete the paths of the modules importable from the document at `uri`.
+async fn module_items(session: &crate::core::Session, uri: &lsp::Url) -> Vec<lsp::CompletionItem> {
+    let roots = session.import_roots(uri).await;
+    session
+        .document_uris()
+        .iter()
+        .filter(|target| *target != uri)
+        .filter_map(|target| module_path(&roots, target))
+        .map(|label| lsp::CompletionItem {
+            label,
+            kind: Some(lsp::CompletionItemKind::MODULE),
+            ..Default::default()
+        })
+        .collect()
+}
+
+// Complete the names of entities declared (in the given namespaces) in the modules a qualifier
+// refers to from the document at `uri`.
+async fn qualified_items(
+    resolver: &mut Resolver<'_>,
+    uri: &lsp::Url,
+    qualifier: Vec<String>,
+    namespaces: &[Namespace],
+) -> Vec<lsp::CompletionItem> {
+    let scope = match resolver.index(uri).await.and_then(|index| index.scope.clone()) {
+        Some(scope) => scope,
+        None => return vec![],
+    };
+    let name = ScopedName {
+        qualifier,
+        name: String::new(),
+    };
+    let mut items = vec![];
+    let mut seen = HashSet::new();
+    for target in scope.candidate_modules(&name).concat() {
+        let index = match resolver.index(&target).await {
+            Some(index) => index,
+            None => continue,
+        };
+        let declarations = index
+            .occurrences
+            .iter()
+            .filter(|occurrence| Role::Declaration == occurrence.role && namespaces.contains(&occurrence.namespace));
+        for declaration in declarations {
+            let label = declaration.name.name.clone();
+            if seen.insert((declaration.namespace, label.clone())) {
+                items.push(lsp::CompletionItem {
+                    label,
+                    kind: Some(super::item_kind(declaration.namespace)),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    items
+}
+
+// Complete the names of entities declared in the given namespaces, in this module or any other.
+// Names from modules which are not yet visible are completed along with an edit importing their
+// module.
+async fn declaration_items(
+    session: &crate::core::Session,
+    resolver: &mut Resolver<'_>,
+    uri: &lsp::Url,
+    namespaces: &[Namespace],
+) -> Vec<lsp::CompletionItem> {
+    let origin = match resolver.index(uri).await {
+        Some(origin) => origin,
+        None => return vec![],
+    };
+    let imports = origin.scope.as_ref().map_or(vec![], |scope| scope.imports.clone());
+    let mut std_modules = resolver.std_modules();
+    std_modules.extend(origin.scope.as_ref().and_then(|scope| scope.std.clone()));
+    let roots = session.import_roots(uri).await;
+    let import_position = imports
+        .iter()
+        .map(|resolved| lsp::Position::new(resolved.import.range.end.line + 1, 0))
+        .max()
+        .unwrap_or_default();
+
+    let mut items = vec![];
+    let mut seen = HashSet::new();
+    // visible modules come first, so that their names take precedence over importable ones
+    let mut modules = session
+        .document_uris()
+        .into_iter()
+        .filter(|target| target.path().ends_with(".dl"))
+        .map(|target| {
+            let visible = &target == uri
+                || std_modules.contains(&target)
+                || imports.iter().any(|resolved| resolved.uri == target);
+            (!visible, target)
+        })
+        .collect::<Vec<_>>();
+    modules.sort();
+
+    for (importable, target) in modules {
+        let import = if importable {
+            match module_path(&roots, &target) {
+                Some(path) => Some(path),
+                None => continue,
+            }
+        } else {
+            None
+        };
+        let index = match resolver.index(&target).await {
+            Some(index) => index,
+            None => continue,
+        };
+        let declarations = index
+            .occurrences
+            .iter()
+            .filter(|occurrence| Role::Declaration == occurrence.role && namespaces.contains(&occurrence.namespace));
+        for declaration in declarations {
+            let label = declaration.name.name.clone();
+            if !seen.insert((declaration.namespace, label.clone())) {
+                continue;
+            }
+            let detail = if &target == uri {
+                None
+            } else {
+                let path = target.path();
+                let module = path.rsplit('/').next().unwrap_or(path).trim_end_matches(".dl");
+                Some(import.clone().unwrap_or_else(|| String::from(module)))
+            };
+            let additional_text_edits = import.as_ref().map(|path| {
+                let range = lsp::Range::new(import_position, import_position);
+                vec![lsp::TextEdit::new(range, format!("import {}\n", path))]
+            });
+            items.push(lsp::CompletionItem {
+                label: label.clone(),
+                kind: Some(super::item_kind(declaration.namespace)),
+                detail,
+                sort_text: Some(format!("{}{}", if importable { 1 } else { 0 }, label)),
+                additional_text_edits,
+                ..Default::default()
+            });
+        }
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_paths_are_relative_to_the_import_roots() {
+        let roots = [PathBuf::from("/program"), PathBuf::from("/lib")];
+        let path = |path: &str| module_path(&roots, &lsp::Url::from_file_path(path).unwrap());
+        assert_eq!(Some(String::from("a::b")), path("/program/a/b.dl"));
+        assert_eq!(Some(String::from("c")), path("/lib/c.dl"));
+        assert_eq!(None, path("/other/d.dl"));
+        assert_eq!(None, path("/program/e.dat"));
+    }
+
+    // Determine the context of the cursor between `before` and `after`.
+    fn context_at(before: &str, after: &str) -> Context {
+        let text = format!("{}{}", before, after);
+        let mut parser = tree_sitter::Parser::try_from(crate::core::Language::DDlogDl).unwrap();
+        let tree = parser.parse(&text, None).unwrap().unwrap();
+        context(&ropey::Rope::from(text.as_str()), &tree, before.chars().count())
+    }
+
+    #[test]
+    fn contexts_of_items_and_imports() {
+        assert_eq!(Context::Item, context_at("", ""));
+        assert_eq!(Context::Item, context_at("input relation R(x: u32)\n", ""));
+        assert_eq!(Context::Item, context_at("input relation R(x: u32)\nout", ""));
+        assert_eq!(Context::Import, context_at("import ", ""));
+        assert_eq!(Context::Import, context_at("import li", "\n"));
+    }
+
+    #[test]
+    fn contexts_of_types() {
+        assert_eq!(Context::Type, context_at("input relation R(x: ", ")"));
+        assert_eq!(Context::Type, context_at("input relation R(x: u", ")"));
+        assert_eq!(Context::Type, context_at("function f(): ", " { 1 }"));
+    }
+
+    #[test]
+    fn contexts_of_rules_and_expressions() {
+        let relation = "input relation R(x: u32)\n";
+        assert_eq!(Context::Rule, context_at(&format!("{}R(x) :- ", relation), "R(x)."));
+        assert_eq!(
+            Context::Rule,
+            context_at(&format!("{}R(x) :- R(x), ", relation), "x > 1.")
+        );
+        assert_eq!(Context::Expression, context_at("function f(): u32 { ", "1 }"));
+        assert_eq!(Context::Expression, context_at("function f(x: u32): u32 { x + ", "1 }"));
+        let text = "function f(x: Option<u32>): u32 { match (x) { Non";
+        assert_eq!(Context::Pattern, context_at(text, "e -> 0, _ -> 1 } }"));
+    }
+
+    #[test]
+    fn contexts_of_fields() {
+        let source = |name| Some(FieldSource::Type(ScopedName::parse(name)));
+        assert_eq!(
+            Context::Field(source("P")),
+            context_at("function f(p: P): u32 { p.", " }")
+        );
+        let text = "input relation R(p: P)\noutput relation O(p: P)\nO(x) :- R(q), var x = q.";
+        let source = Some(FieldSource::RelationPosition(ScopedName::parse("R"), 0));
+        assert_eq!(Context::Field(source), context_at(text, ""));
+    }
+
+    #[test]
+    fn qualifiers_precede_the_prefix() {
+        assert_eq!(
+            Some(vec![String::from("a"), String::from("b")]),
+            qualifier("R(x) :- a::b::")
+        );
+        assert_eq!(None, qualifier("R(x) :- "));
+        assert_eq!(None, qualifier("::"));
+    }
+}
@@ -1,93 +1,83 @@
-use crate::core::language::dl;
-use lsp_text::RopeExt;
-use std::{borrow::Borrow, sync::Arc};
-
-// FIXME: split functionality for {.dl, .dat}
-
-pub fn is_definition_candidate<'tree>(node: &tree_sitter::Node<'tree>) -> bool {
-    [dl::kind::IDENT_LOWER_SCOPED, dl::kind::IDENT_UPPER_SCOPED].contains(&node.kind_id())
-}
+use crate::analysis::symbol::resolve::Resolver;
+use std::sync::Arc;
 
+/// Compute "textDocument/definition" for a given document and position, i.e., the declarations
+/// the name at the position resolves to (within its namespace).
+///
+/// Candidate modules are grouped into tiers (e.g., local definitions before imported ones). The
+/// first tier with any matching declarations wins, and if it contains more than one match then the
+/// name is genuinely ambiguous and every match is returned.
 pub async fn definition(
     session: Arc<crate::core::Session>,
     params: lsp::GotoDefinitionParams,
 ) -> anyhow::Result<Option<lsp::GotoDefinitionResponse>> {
-    use crate::analysis::imports;
+    let origin_uri = &params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
 
-    let origin_module_uri = &params.text_document_position_params.text_document.uri;
+    let mut resolver = Resolver::new(&session);
 
-    let text = session.get_text(origin_module_uri).await?;
-    let content = text.get_content().await?;
-
-    let tree = session
-        .get_tree(origin_module_uri)
-        .await?
-        .clone()
+    let origin_index = resolver
+        .index(origin_uri)
         .await
-        .ok_or_else(|| {
-            anyhow::anyhow!(
-                "could not open tree for uri: {:#?}",
-                params.text_document_position_params.text_document.uri
-            )
-        })?;
-    let tree = tree.lock().await;
-
-    let import_uris = {
-        let base = origin_module_uri.clone();
-        let mut uris = imports::collect_imports(&content, &tree)
-            .map(imports::resolve_import(base))
-            .map(|import| import.uri)
-            .collect::<Vec<_>>();
-        uris.push(origin_module_uri.clone());
-        uris
+        .ok_or_else(|| anyhow::anyhow!("could not index document for uri: {:#?}", origin_uri))?;
+    let origin = if let Some(occurrence) = origin_index.occurrence_at(position) {
+        occurrence.clone()
+    } else {
+        return Ok(None);
     };
 
-    let mut aggregate_symbols = vec![];
-    for uri in import_uris {
-        if let Some(symbols) = session.document_symbols.get(&uri) {
-            if let Some(symbols) = symbols.value().clone().await {
-                let symbols: &Vec<lsp::SymbolInformation> = symbols.borrow();
-                for info in symbols {
-                    aggregate_symbols.push(info.clone());
-                }
-            }
-        }
+    let links = resolver
+        .resolve(origin_uri, &origin)
+        .await
+        .into_iter()
+        .map(|declaration| lsp::LocationLink {
+            origin_selection_range: Some(origin.range),
+            target_uri: declaration.uri,
+            target_range: declaration.range,
+            target_selection_range: declaration.range,
+        })
+        .collect::<Vec<_>>();
+    if links.is_empty() {
+        return Ok(None);
     }
 
-    let node = {
-        let position = content.lsp_position_to_core(params.text_document_position_params.position)?;
-        let start = position.byte;
-        let end = position.byte;
-        tree.root_node().named_descendant_for_byte_range(start, end)
-    };
+    Ok(Some(lsp::GotoDefinitionResponse::Link(links)))
+}
 
-    if let Some(node) = node {
-        if is_definition_candidate(&node) {
-            let node_text = content
-                .utf8_text_for_tree_sitter_node(&node)
-                .split("::")
-                .last()
-                .expect("valid scoped paths should always have a final component")
-                .to_string();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Session;
 
-            if let Some(candidate) = aggregate_symbols.iter().find(|info| info.name == node_text) {
-                let link = {
-                    let origin_selection_range = Some(content.tree_sitter_range_to_lsp_range(node.range()));
-                    let target_uri = candidate.location.uri.clone();
-                    let target_range = candidate.location.range;
-                    let target_selection_range = candidate.location.range;
-                    lsp::LocationLink {
-                        origin_selection_range,
-                        target_uri,
-                        target_range,
-                        target_selection_range,
-                    }
-                };
+    #[tokio::test]
+    async fn names_resolve_within_their_namespace() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let text = "typedef T = T{x: u32}\nfunction f(): T { T{.x = 0} }\n";
+        let uri = session.insert_text("/program/main.dl", text).await;
 
-                return Ok(Some(lsp::GotoDefinitionResponse::Link(vec![link])));
-            }
-        }
-    }
+        let range = |line, start| lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, start + 1));
+        let definition = |position| {
+            let params = lsp::GotoDefinitionParams {
+                text_document_position_params: lsp::TextDocumentPositionParams::new(
+                    lsp::TextDocumentIdentifier::new(uri.clone()),
+                    position,
+                ),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            definition(session.clone(), params)
+        };
+        let targets = |response: Option<lsp::GotoDefinitionResponse>| match response {
+            Some(lsp::GotoDefinitionResponse::Link(links)) => {
+                links.into_iter().map(|link| link.target_range).collect::<Vec<_>>()
+            },
+            _ => vec![],
+        };
 
-    Ok(None)
+        // the type `T` resolves to the typedef, and the constructor `T` to the constructor
+        let response = definition(lsp::Position::new(1, 14)).await.unwrap();
+        assert_eq!(vec![range(0, 8)], targets(response));
+        let response = definition(lsp::Position::new(1, 18)).await.unwrap();
+        assert_eq!(vec![range(0, 12)], targets(response));
+    }
 }
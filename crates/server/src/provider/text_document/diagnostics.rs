@@ -2,6 +2,9 @@ use crate::core::Language;
 
 mod dat;
 mod dl;
+mod schema;
+mod semantic;
+mod stratification;
 
 pub fn diagnostics(
     tree: &tree_sitter::Tree,
@@ -14,3 +17,221 @@ pub fn diagnostics(
         crate::core::Language::DDlogDl => dl::diagnostics(tree, uri, content),
     }
 }
+
+pub async fn semantic_diagnostics(
+    session: &crate::core::Session,
+    uri: &lsp::Url,
+) -> anyhow::Result<Vec<lsp::Diagnostic>> {
+    let mut diagnostics = semantic::diagnostics(session, uri).await?;
+    diagnostics.extend(schema::diagnostics(session, uri).await?);
+    diagnostics.extend(stratification::diagnostics(session, uri).await?);
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{semantic::code, semantic_diagnostics};
+    use crate::core::{ProgramAssociation, Session};
+    use indoc::indoc;
+    use std::path::PathBuf;
+
+    // A session which finds the standard library (if given) in "/lib" rather than on disk.
+    async fn session(std: Option<&str>) -> Session {
+        let session = Session::new(None).unwrap();
+        {
+            let mut config = session.config.write().await;
+            config.library_paths = vec![PathBuf::from("/lib")];
+            config.ddlog_home = Some(PathBuf::from("/ddlog"));
+        }
+        if let Some(std) = std {
+            session.insert_text("/lib/ddlog_std.dl", std).await;
+        }
+        session
+    }
+
+    // The codes of the diagnostics of a document, along with the text they cover.
+    async fn codes(session: &Session, uri: &lsp::Url) -> Vec<(String, String)> {
+        let content = session
+            .get_text(uri)
+            .await
+            .unwrap()
+            .get_content()
+            .await
+            .unwrap()
+            .clone();
+        let mut codes = semantic_diagnostics(session, uri)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| {
+                let code = match diagnostic.code {
+                    Some(lsp::NumberOrString::String(code)) => code,
+                    code => panic!("unexpected code: {:?}", code),
+                };
+                // the texts are ASCII, so characters and UTF-16 code units coincide
+                let offset = |position: lsp::Position| {
+                    content.line_to_char(position.line as usize) + position.character as usize
+                };
+                let range = diagnostic.range;
+                (
+                    code,
+                    content.slice(offset(range.start) .. offset(range.end)).to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        codes.sort();
+        codes
+    }
+
+    fn expected(codes: &[(&str, &str)]) -> Vec<(String, String)> {
+        let mut codes = codes
+            .iter()
+            .map(|(code, text)| (String::from(*code), String::from(*text)))
+            .collect::<Vec<_>>();
+        codes.sort();
+        codes
+    }
+
+    const STD: &str = indoc! {r#"
+        typedef Option<'A> = None | Some{x: 'A}
+        function id(x: u32): u32 { x }
+    "#};
+
+    #[tokio::test]
+    async fn undefined_names() {
+        let session = session(Some(STD)).await;
+        let uri = session
+            .insert_text("/program/main.dl", indoc! {r#"
+                input relation R(x: u32)
+                output relation S(x: u32)
+                S(y) :- R(x), var y = id(x), T(x).
+                S(y) :- R(x), var y = f(x).
+                function g(x: Missing): Option<u32> { Some{x} }
+                function h(): Option<u32> { Nothing{} }
+            "#})
+            .await;
+        let codes = codes(&session, &uri).await;
+        assert_eq!(
+            expected(&[
+                (code::UNDEFINED_RELATION, "T"),
+                (code::UNDEFINED_FUNCTION, "f"),
+                (code::UNDEFINED_TYPE, "Missing"),
+                (code::UNDEFINED_CONSTRUCTOR, "Nothing"),
+            ]),
+            codes
+        );
+    }
+
+    #[tokio::test]
+    async fn only_relations_are_checked_without_std() {
+        let session = session(None).await;
+        let uri = session
+            .insert_text("/program/main.dl", indoc! {r#"
+                input relation R(x: Missing)
+                output relation S(x: u32)
+                S(y) :- R(x), var y = f(x), T(x).
+            "#})
+            .await;
+        let codes = codes(&session, &uri).await;
+        assert_eq!(expected(&[(code::UNDEFINED_RELATION, "T")]), codes);
+        assert!(session.missing_std_reported.load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn variables_are_scoped_to_their_item() {
+        let session = session(Some(STD)).await;
+        let uri = session
+            .insert_text("/program/main.dl", indoc! {r#"
+                function apply(f: function(u32): u32, x: u32): u32 { f(x) }
+                function call(x: u32): u32 { f(x) }
+            "#})
+            .await;
+        let codes = codes(&session, &uri).await;
+        assert_eq!(expected(&[(code::UNDEFINED_FUNCTION, "f")]), codes);
+    }
+
+    #[tokio::test]
+    async fn duplicate_definition() {
+        let session = session(None).await;
+        let uri = session
+            .insert_text("/program/main.dl", indoc! {r#"
+                input relation R(x: u32)
+                input relation R(y: u32)
+            "#})
+            .await;
+        let codes = codes(&session, &uri).await;
+        assert_eq!(expected(&[(code::DUPLICATE_DEFINITION, "R")]), codes);
+    }
+
+    #[tokio::test]
+    async fn unresolved_import() {
+        let session = session(None).await;
+        let uri = session.insert_text("/program/main.dl", "import missing\n").await;
+        let codes = codes(&session, &uri).await;
+        assert_eq!(expected(&[(code::UNRESOLVED_IMPORT, "import missing")]), codes);
+    }
+
+    #[tokio::test]
+    async fn circular_import() {
+        let session = session(None).await;
+        let main = session.insert_text("/program/main.dl", "import other\n").await;
+        session.insert_text("/program/other.dl", "import main\n").await;
+        let codes = codes(&session, &main).await;
+        assert_eq!(expected(&[(code::CIRCULAR_IMPORT, "import other")]), codes);
+    }
+
+    #[tokio::test]
+    async fn unstratified_negation_and_aggregation() {
+        let session = session(None).await;
+        let uri = session
+            .insert_text("/program/main.dl", indoc! {r#"
+                input relation R(x: u32)
+                relation P(x: u32)
+                P(x) :- R(x), not P(x).
+                relation C(x: u32)
+                C(x) :- R(x).
+                C(n) :- C(x), var n = x.group_by(()).count().
+            "#})
+            .await;
+        let codes = codes(&session, &uri).await;
+        assert!(codes.iter().any(|(code, _)| code == code::UNSTRATIFIED_NEGATION));
+        assert!(codes.iter().any(|(code, _)| code == code::UNSTRATIFIED_AGGREGATION));
+    }
+
+    #[tokio::test]
+    async fn updates_are_checked_against_the_schema() {
+        let session = session(None).await;
+        session.config.write().await.programs = vec![ProgramAssociation {
+            commands: PathBuf::from("data.dat"),
+            program: PathBuf::from("main.dl"),
+        }];
+        session
+            .insert_text("/program/main.dl", indoc! {r#"
+                input relation R(x: u32, y: string)
+                output relation S(x: u32)
+            "#})
+            .await;
+        let uri = session
+            .insert_text("/program/data.dat", indoc! {r#"
+                start;
+                insert R(1);
+                insert S(1);
+                insert R(.x = 1, .z = "a");
+                insert R(1, 2);
+                insert T(1);
+                commit;
+            "#})
+            .await;
+        let codes = codes(&session, &uri).await;
+        assert_eq!(
+            expected(&[
+                (code::ARITY_MISMATCH, "R"),
+                (code::NON_INPUT_RELATION, "S"),
+                (code::UNKNOWN_FIELD, "z"),
+                (code::TYPE_MISMATCH, "2"),
+                (code::UNDEFINED_RELATION, "T"),
+            ]),
+            codes
+        );
+    }
+}
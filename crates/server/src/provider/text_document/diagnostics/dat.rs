@@ -1,18 +1,14 @@
-use crate::core::language::dl::visitor::{validating::ValidatingVisitor, Visitor};
+use crate::core::language::dat::visitor::recovering::RecoveringVisitor;
 
 pub fn diagnostics(tree: &tree_sitter::Tree, uri: &lsp::Url, content: &ropey::Rope) -> Vec<lsp::Diagnostic> {
-    let mut diagnostics = vec![];
     let mut visitor = {
-        let language = crate::core::Language::DDlogDl;
+        let language = crate::core::Language::DDlogDat;
         let node = tree.root_node();
-        ValidatingVisitor::new(language, node)
+        RecoveringVisitor::new(language, node)
     };
-    let result = visitor.visit();
-
-    if let Err(error) = result {
-        let diagnostic = error.to_lsp_diagnostic(uri, content);
-        diagnostics.push(diagnostic);
-    }
-
-    diagnostics
+    visitor
+        .visit()
+        .iter()
+        .map(|error| error.to_lsp_diagnostic(uri, content))
+        .collect()
 }
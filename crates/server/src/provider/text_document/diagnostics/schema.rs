@@ -0,0 +1,387 @@
+use super::semantic::{code, diagnostic};
+use crate::{
+    analysis::{
+        schema::{read_fields, Schema},
+        symbol::{context::Namespace, resolve::Resolver, ScopedName},
+        tree::named_children,
+    },
+    core::language::{dat, dl},
+};
+use lsp_text::RopeExt;
+
+/// The kind of value denoted by a literal, or accepted by a (primitive) type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Literal {
+    Bool,
+    Float,
+    Integer,
+    String,
+}
+
+impl Literal {
+    // Classify the (primitive) type a field is declared with. Other types are not checked.
+    fn of_type(r#type: &str) -> Option<Self> {
+        let r#type = r#type.trim();
+        match r#type {
+            "bool" => Some(Literal::Bool),
+            "string" => Some(Literal::String),
+            "float" | "double" => Some(Literal::Float),
+            "bigint" => Some(Literal::Integer),
+            _ if r#type.starts_with("bit<") || r#type.starts_with("signed<") => Some(Literal::Integer),
+            _ => None,
+        }
+    }
+
+    // Whether a value of this kind may be used for a field of the given kind.
+    fn fits(self, expected: Self) -> bool {
+        self == expected || (Literal::Integer == self && Literal::Float == expected)
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Literal::Bool => "a boolean",
+            Literal::Float => "a floating-point number",
+            Literal::Integer => "an integer",
+            Literal::String => "a string",
+        }
+    }
+}
+
+/// A value given for a field, along with the kind of literal it is (if it is one).
+#[derive(Clone, Debug)]
+struct Value {
+    range: lsp::Range,
+    literal: Option<Literal>,
+}
+
+/// The values given for the fields of the relation targeted by an update.
+#[derive(Clone, Debug)]
+enum Values {
+    Positional(Vec<Value>),
+    Named(Vec<(String, lsp::Range, Value)>),
+    Element,
+}
+
+/// A struct value (e.g., `C{.a = 1}`) occurring within an update.
+#[derive(Clone, Debug)]
+struct Struct {
+    name: ScopedName,
+    fields: Vec<(String, lsp::Range, Value)>,
+}
+
+/// An `insert`, `delete`, or `insert_or_update` command.
+#[derive(Clone, Debug)]
+struct Update {
+    keyword: String,
+    relation: ScopedName,
+    range: lsp::Range,
+    values: Values,
+    structs: Vec<Struct>,
+}
+
+/// Check the updates of a ".dat" document against the schema of the relations of its program.
+pub async fn diagnostics(session: &crate::core::Session, uri: &lsp::Url) -> anyhow::Result<Vec<lsp::Diagnostic>> {
+    let mut diagnostics = vec![];
+    if crate::core::Language::DDlogDat != session.get_text(uri).await?.language {
+        return Ok(diagnostics);
+    }
+
+    // Without an associated program, the relation may be declared in a module we don't know of.
+    let associated = session.program(uri).await.is_some();
+    let updates = {
+        let text = session.get_text(uri).await?.clone();
+        let content = text.get_content().await?;
+        let tree = session
+            .get_tree(uri)
+            .await?
+            .clone()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+        let tree = tree.lock().await;
+        updates(&content, &tree)
+    };
+
+    let mut resolver = Resolver::new(session);
+    for update in updates {
+        let mut schema = None;
+        for declaration in resolver
+            .resolve_name(uri, Namespace::Relation, update.relation.clone())
+            .await
+        {
+            let kinds = &[dl::kind::REL_ARGS, dl::kind::REL_ELEM];
+            if let Some(found) = declaration.with_node(session, kinds, Schema::read).await? {
+                schema = Some(found);
+                break;
+            }
+        }
+        let schema = match schema {
+            Some(schema) => schema,
+            None => {
+                if associated {
+                    let message = format!("undefined relation: `{}`", update.relation);
+                    diagnostics.push(diagnostic(update.range, code::UNDEFINED_RELATION, message, None));
+                }
+                continue;
+            },
+        };
+        check_update(&update, &schema, &mut diagnostics);
+        for r#struct in &update.structs {
+            check_struct(
+                session,
+                &mut resolver,
+                uri,
+                &update,
+                &schema,
+                r#struct,
+                &mut diagnostics,
+            )
+            .await?;
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+// Check the target and the values of an update against the schema of its relation.
+fn check_update(update: &Update, schema: &Schema, diagnostics: &mut Vec<lsp::Diagnostic>) {
+    if !schema.input {
+        let message = format!(
+            "cannot `{}` into `{}`: only input relations can be updated",
+            update.keyword, update.relation
+        );
+        diagnostics.push(diagnostic(update.range, code::NON_INPUT_RELATION, message, None));
+    }
+    if schema.element.is_some() {
+        return;
+    }
+    match &update.values {
+        Values::Positional(values) => {
+            if values.len() != schema.fields.len() {
+                let message = format!(
+                    "relation `{}` has {} field(s) but {} value(s) were given",
+                    update.relation,
+                    schema.fields.len(),
+                    values.len()
+                );
+                diagnostics.push(diagnostic(update.range, code::ARITY_MISMATCH, message, None));
+            }
+            for (value, (name, r#type)) in values.iter().zip(&schema.fields) {
+                check_value(value, name, r#type.as_deref(), diagnostics);
+            }
+        },
+        Values::Named(values) => check_fields(&update.relation, &schema.fields, values, diagnostics),
+        Values::Element => {},
+    }
+}
+
+// Check the fields of a struct value, which is either a record of the updated relation or a value
+// built by a constructor with named fields.
+async fn check_struct(
+    session: &crate::core::Session,
+    resolver: &mut Resolver<'_>,
+    uri: &lsp::Url,
+    update: &Update,
+    schema: &Schema,
+    r#struct: &Struct,
+    diagnostics: &mut Vec<lsp::Diagnostic>,
+) -> anyhow::Result<()> {
+    if r#struct.name.name == update.relation.name {
+        check_fields(&r#struct.name, &schema.fields, &r#struct.fields, diagnostics);
+        return Ok(());
+    }
+    let declarations = resolver
+        .resolve_name(uri, Namespace::Constructor, r#struct.name.clone())
+        .await;
+    for declaration in declarations {
+        if let Some(fields) = declaration
+            .with_node(session, &[dl::kind::CONS_REC], read_fields)
+            .await?
+        {
+            check_fields(&r#struct.name, &fields, &r#struct.fields, diagnostics);
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Check values given by field name against the declared fields.
+fn check_fields(
+    owner: &ScopedName,
+    fields: &[(String, Option<String>)],
+    values: &[(String, lsp::Range, Value)],
+    diagnostics: &mut Vec<lsp::Diagnostic>,
+) {
+    for (name, range, value) in values {
+        match fields.iter().find(|(field, _)| field == name) {
+            Some((_, r#type)) => check_value(value, name, r#type.as_deref(), diagnostics),
+            None => {
+                let message = format!("`{}` has no field named `{}`", owner, name);
+                diagnostics.push(diagnostic(*range, code::UNKNOWN_FIELD, message, None));
+            },
+        }
+    }
+}
+
+// Check a literal value against the declared type of its field.
+fn check_value(value: &Value, field: &str, r#type: Option<&str>, diagnostics: &mut Vec<lsp::Diagnostic>) {
+    let expected = r#type.and_then(Literal::of_type);
+    if let (Some(literal), Some(expected), Some(r#type)) = (value.literal, expected, r#type) {
+        if !literal.fits(expected) {
+            let message = format!(
+                "mismatched types: field `{}` has type `{}` but {} was given",
+                field,
+                r#type,
+                literal.describe()
+            );
+            diagnostics.push(diagnostic(value.range, code::TYPE_MISMATCH, message, None));
+        }
+    }
+}
+
+// Collect the updates (along with the values they give) of a ".dat" document.
+fn updates(content: &ropey::Rope, tree: &tree_sitter::Tree) -> Vec<Update> {
+    let text = |node: &tree_sitter::Node| content.utf8_text_for_tree_sitter_node(node).into_owned();
+    let range = |node: &tree_sitter::Node| content.tree_sitter_range_to_lsp_range(node.range());
+
+    let mut updates = vec![];
+    let mut work = vec![tree.root_node()];
+    while let Some(node) = work.pop() {
+        let children = named_children(&node);
+        if ![dat::kind::INSERT, dat::kind::DELETE, dat::kind::INSERT_OR_UPDATE].contains(&node.kind_id()) {
+            work.extend(children);
+            continue;
+        }
+        let keyword = node.child(0).map(|keyword| text(&keyword)).unwrap_or_default();
+        let atom = children
+            .iter()
+            .find(|child| dat::kind::ATOM == child.kind_id())
+            .and_then(|atom| named_children(atom).into_iter().next());
+        let atom = match atom {
+            Some(atom) => atom,
+            None => continue,
+        };
+        let atom_children = named_children(&atom);
+        let name = match atom_children
+            .iter()
+            .find(|child| dat::kind::NAME_REL == child.kind_id())
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        let value = |node: &tree_sitter::Node| Value {
+            range: range(node),
+            literal: literal(node),
+        };
+        let values = match atom.kind_id() {
+            dat::kind::ATOM_POS => Values::Positional(
+                atom_children
+                    .iter()
+                    .filter(|child| dat::kind::EXP == child.kind_id())
+                    .map(value)
+                    .collect(),
+            ),
+            dat::kind::ATOM_REC => {
+                let mut values = vec![];
+                let mut field = None;
+                for child in &atom_children {
+                    match child.kind_id() {
+                        dat::kind::NAME_ARG => field = Some(child),
+                        dat::kind::EXP => {
+                            if let Some(field) = field.take() {
+                                values.push((text(field), range(field), value(child)));
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+                Values::Named(values)
+            },
+            _ => Values::Element,
+        };
+        updates.push(Update {
+            keyword,
+            relation: ScopedName::parse(&text(name)),
+            range: range(name),
+            values,
+            structs: structs(content, &atom),
+        });
+    }
+    updates.reverse();
+    updates
+}
+
+// Collect the struct values with named fields (e.g., `C{.a = 1}`) occurring within a node.
+fn structs(content: &ropey::Rope, node: &tree_sitter::Node) -> Vec<Struct> {
+    let text = |node: &tree_sitter::Node| content.utf8_text_for_tree_sitter_node(node).into_owned();
+    let range = |node: &tree_sitter::Node| content.tree_sitter_range_to_lsp_range(node.range());
+
+    let mut structs = vec![];
+    let mut work = vec![*node];
+    while let Some(node) = work.pop() {
+        let children = named_children(&node);
+        if dat::kind::VAL_STRUCT != node.kind_id() {
+            work.extend(children);
+            continue;
+        }
+        let name = match children.iter().find(|child| dat::kind::NAME_REL == child.kind_id()) {
+            Some(name) => ScopedName::parse(&text(name)),
+            None => continue,
+        };
+        // the fields of this struct, not descending into nested structs (which are visited later)
+        let mut fields = vec![];
+        let mut nested = children;
+        while let Some(child) = nested.pop() {
+            match child.kind_id() {
+                dat::kind::VAL_STRUCT => work.push(child),
+                dat::kind::RECORD_NAMED => {
+                    let record_children = named_children(&child);
+                    let field = record_children.iter().find(|c| dat::kind::NAME_CONS == c.kind_id());
+                    let record = record_children.iter().find(|c| dat::kind::RECORD == c.kind_id());
+                    if let (Some(field), Some(record)) = (field, record) {
+                        let value = Value {
+                            range: range(record),
+                            literal: literal(record),
+                        };
+                        fields.push((text(field), range(field), value));
+                        work.push(*record);
+                    }
+                },
+                _ => nested.extend(named_children(&child)),
+            }
+        }
+        fields.reverse();
+        structs.push(Struct { name, fields });
+    }
+    structs
+}
+
+// Determine the kind of literal an expression or record is, looking through the wrapping nodes.
+fn literal(node: &tree_sitter::Node) -> Option<Literal> {
+    let mut node = *node;
+    loop {
+        match node.kind_id() {
+            dat::kind::EXP | dat::kind::EXP_LIT | dat::kind::RECORD => {
+                let children = named_children(&node);
+                if 1 != children.len() {
+                    return None;
+                }
+                node = children[0];
+            },
+            dat::kind::LIT_BOOL => return Some(Literal::Bool),
+            dat::kind::LIT_STRING => return Some(Literal::String),
+            dat::kind::LIT_NUM_FLOAT => return Some(Literal::Float),
+            dat::kind::LIT_NUM_DEC | dat::kind::LIT_NUM_HEX => return Some(Literal::Integer),
+            dat::kind::LIT_NUM => {
+                let mut work = vec![node];
+                while let Some(node) = work.pop() {
+                    if dat::kind::LIT_NUM_FLOAT == node.kind_id() {
+                        return Some(Literal::Float);
+                    }
+                    work.extend(named_children(&node));
+                }
+                return Some(Literal::Integer);
+            },
+            _ => return None,
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    analysis::{
+        imports::{ImportGraph, ResolvedImport},
+        symbol::{
+            context::{self, Namespace, Role},
+            resolve::{DocumentIndex, Resolver},
+        },
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+};
+
+/// Stable codes for the semantic diagnostics.
+pub mod code {
+    pub const ARITY_MISMATCH: &str = "arity-mismatch";
+    pub const CIRCULAR_IMPORT: &str = "circular-import";
+    pub const DUPLICATE_DEFINITION: &str = "duplicate-definition";
+    pub const NON_INPUT_RELATION: &str = "non-input-relation";
+    pub const TYPE_MISMATCH: &str = "type-mismatch";
+    pub const UNDEFINED_CONSTRUCTOR: &str = "undefined-constructor";
+    pub const UNDEFINED_FUNCTION: &str = "undefined-function";
+    pub const UNDEFINED_RELATION: &str = "undefined-relation";
+    pub const UNDEFINED_TYPE: &str = "undefined-type";
+    pub const UNKNOWN_FIELD: &str = "unknown-field";
+    pub const UNRESOLVED_IMPORT: &str = "unresolved-import";
+    pub const UNSTRATIFIED_AGGREGATION: &str = "unstratified-aggregation";
+    pub const UNSTRATIFIED_NEGATION: &str = "unstratified-negation";
+}
+
+pub(super) fn diagnostic(
+    range: lsp::Range,
+    code: &str,
+    message: String,
+    related_information: Option<Vec<lsp::DiagnosticRelatedInformation>>,
+) -> lsp::Diagnostic {
+    lsp::Diagnostic {
+        range,
+        severity: Some(lsp::DiagnosticSeverity::ERROR),
+        code: Some(lsp::NumberOrString::String(code.into())),
+        message,
+        related_information,
+        ..Default::default()
+    }
+}
+
+/// Compute the semantic (i.e., name resolution) diagnostics for a document.
+pub async fn diagnostics(session: &crate::core::Session, uri: &lsp::Url) -> anyhow::Result<Vec<lsp::Diagnostic>> {
+    let mut diagnostics = vec![];
+
+    let mut resolver = Resolver::new(session);
+    let index = match resolver.index(uri).await {
+        Some(index) if crate::core::Language::DDlogDl == index.language => index,
+        _ => return Ok(diagnostics),
+    };
+
+    unresolved_imports(&index, &mut diagnostics);
+    circular_imports(&mut resolver, uri, &mut diagnostics).await;
+    duplicate_definitions(&index, &mut diagnostics);
+
+    // Without the standard library we can't tell whether names of types, constructors, or
+    // functions are defined there, so only relations are checked in that case.
+    let has_std = match index.scope.as_ref().and_then(|scope| scope.std.clone()) {
+        Some(std) => resolver.index(&std).await.is_some(),
+        None => !resolver.std_modules().is_empty(),
+    };
+    if !has_std {
+        report_missing_std(session).await;
+    }
+    let bound_variables = bound_variables(session, uri).await?;
+
+    for occurrence in index.occurrences.iter() {
+        if Role::Reference != occurrence.role {
+            continue;
+        }
+        let (code, description) = match occurrence.namespace {
+            Namespace::Constructor if has_std => (code::UNDEFINED_CONSTRUCTOR, "constructor"),
+            Namespace::Function if has_std => (code::UNDEFINED_FUNCTION, "function"),
+            Namespace::Relation => (code::UNDEFINED_RELATION, "relation"),
+            Namespace::Type if has_std => (code::UNDEFINED_TYPE, "type"),
+            _ => continue,
+        };
+        // calls of (function-typed) variables look just like calls of functions
+        let is_bound = || {
+            bound_variables
+                .iter()
+                .filter(|(range, _)| range.start <= occurrence.range.start && occurrence.range.end <= range.end)
+                .any(|(_, names)| names.contains(&occurrence.name.name))
+        };
+        if Namespace::Function == occurrence.namespace && !occurrence.name.is_qualified() && is_bound() {
+            continue;
+        }
+        if resolver.resolve(uri, occurrence).await.is_empty() {
+            let name = occurrence
+                .name
+                .qualifier
+                .iter()
+                .chain(Some(&occurrence.name.name))
+                .cloned();
+            let message = format!("undefined {}: `{}`", description, name.collect::<Vec<_>>().join("::"));
+            diagnostics.push(diagnostic(occurrence.range, code, message, None));
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+// Tell the user (once per session) that names other than those of relations aren't checked, since
+// the standard library couldn't be found.
+async fn report_missing_std(session: &crate::core::Session) {
+    if session.missing_std_reported.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(client) = session.client() {
+        let message = "The DDlog standard library (\"ddlog_std.dl\") could not be found, so only the names of \
+                       relations are checked. Set \"ddlog.home\" (or DDLOG_HOME) to a differential-datalog \
+                       checkout to check the names of types, constructors, and functions too.";
+        client.show_message(lsp::MessageType::WARNING, message).await;
+    }
+}
+
+// Report imports whose module can't be found in the program root or any library directory.
+fn unresolved_imports(index: &DocumentIndex, diagnostics: &mut Vec<lsp::Diagnostic>) {
+    if let Some(scope) = &index.scope {
+        for import in &scope.unresolved {
+            let components = import.module_path.components();
+            let message = format!(
+                "unresolved import: `{}` (no module \"{}.dl\" in the program or library directories)",
+                components.join("::"),
+                components.join("/")
+            );
+            diagnostics.push(diagnostic(import.range, code::UNRESOLVED_IMPORT, message, None));
+        }
+    }
+}
+
+// Report imports which close a cycle, i.e., whose module (transitively) imports this one back,
+// listing the chain of imports which leads back as related information.
+async fn circular_imports(resolver: &mut Resolver<'_>, uri: &lsp::Url, diagnostics: &mut Vec<lsp::Diagnostic>) {
+    let modules = resolver.program(uri).await;
+    let graph = ImportGraph::of_modules(resolver, &modules).await;
+    for chain in graph.cycles(uri) {
+        let module_path = |resolved: &ResolvedImport| resolved.import.module_path.components().join("::");
+        let (_, first) = &chain[0];
+        let message = format!("circular import: `{}` leads back to this module", module_path(first));
+        let related_information = chain
+            .iter()
+            .map(|(importer, resolved)| lsp::DiagnosticRelatedInformation {
+                location: lsp::Location::new(importer.clone(), resolved.import.range),
+                message: format!("imports `{}`", module_path(resolved)),
+            })
+            .collect();
+        let code = code::CIRCULAR_IMPORT;
+        diagnostics.push(diagnostic(first.import.range, code, message, Some(related_information)));
+    }
+}
+
+// Report declarations of a name which was already declared (in the same namespace) in the module.
+fn duplicate_definitions(index: &DocumentIndex, diagnostics: &mut Vec<lsp::Diagnostic>) {
+    let mut first_declarations = HashMap::<(Namespace, &str), lsp::Range>::new();
+    for occurrence in index.occurrences.iter() {
+        // functions may be overloaded, so multiple declarations are not necessarily an error
+        if Role::Declaration != occurrence.role || Namespace::Function == occurrence.namespace {
+            continue;
+        }
+        let key = (occurrence.namespace, occurrence.name.name.as_str());
+        if let Some(first) = first_declarations.get(&key) {
+            let message = format!("duplicate definition: `{}`", occurrence.name.name);
+            let related_information = Some(vec![lsp::DiagnosticRelatedInformation {
+                location: lsp::Location::new(index.uri.clone(), *first),
+                message: String::from("first defined here"),
+            }]);
+            let code = code::DUPLICATE_DEFINITION;
+            diagnostics.push(diagnostic(occurrence.range, code, message, related_information));
+        } else {
+            first_declarations.insert(key, occurrence.range);
+        }
+    }
+}
+
+// Collect the names of the variables and arguments used within each item (e.g., rule or function)
+// of the document, along with the range of the item.
+async fn bound_variables(
+    session: &crate::core::Session,
+    uri: &lsp::Url,
+) -> anyhow::Result<Vec<(lsp::Range, HashSet<String>)>> {
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let tree = session
+        .get_tree(uri)
+        .await?
+        .clone()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+    let tree = tree.lock().await;
+
+    let mut items = vec![];
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for item in root.named_children(&mut cursor) {
+        let mut names = HashSet::new();
+        let mut work = vec![item];
+        while let Some(node) = work.pop() {
+            if [dl::kind::NAME_ARG, dl::kind::NAME_VAR_TERM].contains(&node.kind_id()) {
+                // skip the callees of function calls, which are themselves `name_var_term` nodes
+                if context::classify_dl(&node).is_none() {
+                    names.insert(content.utf8_text_for_tree_sitter_node(&node).into_owned());
+                }
+                continue;
+            }
+            let mut cursor = node.walk();
+            work.extend(node.named_children(&mut cursor));
+        }
+        items.push((content.tree_sitter_range_to_lsp_range(item.range()), names));
+    }
+    Ok(items)
+}
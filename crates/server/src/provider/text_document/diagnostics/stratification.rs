@@ -0,0 +1,68 @@
+use super::semantic::{code, diagnostic};
+use crate::analysis::dependency::{Dependency, DependencyGraph};
+use std::collections::HashMap;
+
+/// Compute the stratification diagnostics for a ".dl" document, i.e., report every negated or
+/// aggregated atom whose relation is (mutually) recursive with the head of its rule.
+pub async fn diagnostics(session: &crate::core::Session, uri: &lsp::Url) -> anyhow::Result<Vec<lsp::Diagnostic>> {
+    let mut diagnostics = vec![];
+    if crate::core::Language::DDlogDl != session.get_text(uri).await?.language {
+        return Ok(diagnostics);
+    }
+
+    // the rules of modules importing this one can't be involved in cycles through its atoms
+    // (unless the imports are circular), so the program rooted at this module suffices
+    let graph = match DependencyGraph::of_program(session, uri).await {
+        Ok(graph) => graph,
+        // stratification is only checked for programs whose graph can be computed
+        Err(_) => return Ok(diagnostics),
+    };
+    let components = graph.components();
+    let component_of = components
+        .iter()
+        .enumerate()
+        .flat_map(|(index, component)| component.iter().map(move |name| (name.clone(), index)))
+        .collect::<HashMap<_, _>>();
+
+    for edge in graph.edges() {
+        let (code, description) = match edge.dependency {
+            Dependency::Negative => (code::UNSTRATIFIED_NEGATION, "negation"),
+            Dependency::Aggregate => (code::UNSTRATIFIED_AGGREGATION, "aggregation"),
+            _ => continue,
+        };
+        let component = component_of[&edge.from];
+        if component != component_of[&edge.to] {
+            continue;
+        }
+        // the cycle continues from the head of the rule back to the negated (or aggregated) relation
+        let path = graph
+            .path(&edge.to, &edge.from, &components[component])
+            .unwrap_or_default();
+        let mut cycle = vec![edge.from.name.as_str(), edge.to.name.as_str()];
+        cycle.extend(path.iter().map(|step| step.to.name.as_str()));
+        let related_information = std::iter::once(&edge)
+            .chain(path.iter())
+            .map(|step| lsp::DiagnosticRelatedInformation {
+                location: step.location.clone(),
+                message: format!("`{}` depends on `{}`", step.to, step.from),
+            })
+            .collect::<Vec<_>>();
+
+        let message = format!(
+            "{} of relation `{}` within a recursive cycle: {}",
+            description,
+            edge.from,
+            cycle.join(" -> ")
+        );
+        for atom in edge.atoms.iter().filter(|atom| atom.uri == *uri) {
+            diagnostics.push(diagnostic(
+                atom.range,
+                code,
+                message.clone(),
+                Some(related_information.clone()),
+            ));
+        }
+    }
+
+    Ok(diagnostics)
+}
@@ -49,7 +49,13 @@ pub fn symbol_range(
     let name;
     let range = content.tree_sitter_range_to_lsp_range(node.range());
     let selection_range;
-    if let Some(inner_node) = node.child_by_field_id(field_id) {
+    // fall back to the first `name_*` child for nodes without an identifier field
+    let inner_node = node.child_by_field_id(field_id).or_else(|| {
+        let mut cursor = node.walk();
+        let mut children = node.named_children(&mut cursor);
+        children.find(|child| child.kind().starts_with("name_"))
+    });
+    if let Some(inner_node) = inner_node {
         name = content.utf8_text_for_tree_sitter_node(&inner_node).into();
         selection_range = content.tree_sitter_range_to_lsp_range(inner_node.range());
     } else {
@@ -78,9 +84,19 @@ pub async fn document_symbol(
         .await
         .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", params.text_document.uri))?;
     let tree = tree.lock().await;
+    let hierarchical = {
+        let capabilities = session.client_capabilities.read().await;
+        let document_symbol = capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.text_document.as_ref())
+            .and_then(|text_document| text_document.document_symbol.as_ref());
+        document_symbol
+            .and_then(|document_symbol| document_symbol.hierarchical_document_symbol_support)
+            .unwrap_or(false)
+    };
     let response = match text.language {
-        crate::core::Language::DDlogDat => self::dat::document_symbol(&content, &tree, params).await?,
-        crate::core::Language::DDlogDl => self::dl::document_symbol(&content, &tree, params).await?,
+        crate::core::Language::DDlogDat => self::dat::document_symbol(&content, &tree, params, hierarchical).await?,
+        crate::core::Language::DDlogDl => self::dl::document_symbol(&content, &tree, params, hierarchical).await?,
     };
     Ok(response)
 }
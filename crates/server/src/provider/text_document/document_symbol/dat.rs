@@ -1,7 +1,17 @@
+// Document symbol provider definitions for ".dat" files.
 pub async fn document_symbol(
-    _content: &ropey::Rope,
-    _tree: &tree_sitter::Tree,
-    _params: lsp::DocumentSymbolParams,
+    content: &ropey::Rope,
+    tree: &tree_sitter::Tree,
+    params: lsp::DocumentSymbolParams,
+    hierarchical: bool,
 ) -> anyhow::Result<Option<lsp::DocumentSymbolResponse>> {
-    Ok(None)
+    let result = if hierarchical {
+        let result = crate::provider::common::document_symbol::dat::document_symbol_nested(content, tree).await?;
+        lsp::DocumentSymbolResponse::Nested(result)
+    } else {
+        let result = crate::provider::common::document_symbol::dat::document_symbol(content, tree, params).await?;
+        lsp::DocumentSymbolResponse::Flat(result)
+    };
+    let result = Some(result);
+    Ok(result)
 }
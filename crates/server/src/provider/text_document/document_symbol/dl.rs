@@ -9,9 +9,15 @@ pub async fn document_symbol(
     content: &ropey::Rope,
     tree: &tree_sitter::Tree,
     params: lsp::DocumentSymbolParams,
+    hierarchical: bool,
 ) -> anyhow::Result<Option<lsp::DocumentSymbolResponse>> {
-    let result = crate::provider::common::document_symbol::dl::document_symbol(content, tree, params).await?;
-    let result = lsp::DocumentSymbolResponse::Flat(result);
+    let result = if hierarchical {
+        let result = crate::provider::common::document_symbol::dl::document_symbol_nested(content, tree).await?;
+        lsp::DocumentSymbolResponse::Nested(result)
+    } else {
+        let result = crate::provider::common::document_symbol::dl::document_symbol(content, tree, params).await?;
+        lsp::DocumentSymbolResponse::Flat(result)
+    };
     let result = Some(result);
     Ok(result)
 }
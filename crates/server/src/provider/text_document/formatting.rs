@@ -0,0 +1,66 @@
+use crate::provider::common::formatting;
+use lsp_text::RopeExt;
+use std::sync::Arc;
+
+/// Compute "textDocument/formatting" for a given document.
+pub async fn formatting(
+    session: Arc<crate::core::Session>,
+    params: lsp::DocumentFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp::TextEdit>>> {
+    let uri = &params.text_document.uri;
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let tree = session
+        .get_tree(uri)
+        .await?
+        .clone()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+    let tree = tree.lock().await;
+
+    let options = options(&session, params.options).await;
+    let new_text = formatting::format(text.language, &content, &tree, &options);
+    if new_text == content {
+        return Ok(Some(vec![]));
+    }
+    let range = lsp::Range::new(
+        lsp::Position::new(0, 0),
+        content.byte_to_lsp_position(content.len_bytes()),
+    );
+    Ok(Some(vec![lsp::TextEdit { range, new_text }]))
+}
+
+/// Compute "textDocument/rangeFormatting" for a given document and range, i.e., format the
+/// top-level items intersecting the range.
+pub async fn range_formatting(
+    session: Arc<crate::core::Session>,
+    params: lsp::DocumentRangeFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp::TextEdit>>> {
+    let uri = &params.text_document.uri;
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let tree = session
+        .get_tree(uri)
+        .await?
+        .clone()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+    let tree = tree.lock().await;
+
+    let options = options(&session, params.options).await;
+    let edits = formatting::format_range(text.language, &content, &tree, &options, params.range)?;
+    Ok(Some(edits))
+}
+
+// Complete the formatting options of a request with the configuration of the server, i.e., sort
+// the updates within transactions if configured (unless the client decided otherwise).
+async fn options(session: &crate::core::Session, mut options: lsp::FormattingOptions) -> lsp::FormattingOptions {
+    if session.config.read().await.sort_commands {
+        let property = lsp::FormattingProperty::Bool(true);
+        options
+            .properties
+            .entry(formatting::SORT_COMMANDS.into())
+            .or_insert(property);
+    }
+    options
+}
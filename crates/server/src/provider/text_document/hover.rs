@@ -0,0 +1,277 @@
+use crate::{
+    analysis::{
+        symbol::resolve::{Declaration, Resolver},
+        tree::ancestors,
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::sync::Arc;
+
+// The kinds of the nodes which declare the entity named by one of their `NAME_*` children.
+const DECLARATION_KINDS: &[u16] = &[
+    dl::kind::APPLY,
+    dl::kind::CONS_POS,
+    dl::kind::CONS_REC,
+    dl::kind::FUNCTION_EXTERN,
+    dl::kind::FUNCTION_NORMAL,
+    dl::kind::INDEX,
+    dl::kind::REL_ARGS,
+    dl::kind::REL_ELEM,
+    dl::kind::TRANSFORMER,
+    dl::kind::TYPEDEF_EXTERN,
+    dl::kind::TYPEDEF_NORMAL,
+];
+
+pub async fn hover(session: Arc<crate::core::Session>, params: lsp::HoverParams) -> anyhow::Result<Option<lsp::Hover>> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let mut resolver = Resolver::new(&session);
+
+    let index = resolver
+        .index(uri)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not index document for uri: {:#?}", uri))?;
+    let occurrence = if let Some(occurrence) = index.occurrence_at(position) {
+        occurrence.clone()
+    } else {
+        return Ok(None);
+    };
+
+    // overloaded functions resolve to several declarations, each of which is described
+    let mut sections = vec![];
+    for declaration in resolver.resolve(uri, &occurrence).await {
+        if let Some(section) = describe(&session, &declaration).await? {
+            sections.push(section);
+        }
+    }
+    if sections.is_empty() {
+        return Ok(None);
+    }
+
+    let contents = lsp::HoverContents::Markup(lsp::MarkupContent {
+        kind: lsp::MarkupKind::Markdown,
+        value: sections.join("\n\n---\n\n"),
+    });
+    let range = Some(occurrence.range);
+    Ok(Some(lsp::Hover { contents, range }))
+}
+
+// Render the signature and documentation of a declaration as markdown.
+async fn describe(session: &crate::core::Session, declaration: &Declaration) -> anyhow::Result<Option<String>> {
+    let uri = &declaration.uri;
+    let text = session.get_text(uri).await?.clone();
+    let content = text.get_content().await?;
+    let tree = session
+        .get_tree(uri)
+        .await?
+        .clone()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+    let tree = tree.lock().await;
+    let range = content.lsp_range_to_tree_sitter_range(declaration.range)?;
+    Ok(render(&content, &tree, range))
+}
+
+fn render(content: &ropey::Rope, tree: &tree_sitter::Tree, range: tree_sitter::Range) -> Option<String> {
+    let name = tree
+        .root_node()
+        .named_descendant_for_byte_range(range.start_byte(), range.end_byte())?;
+    let declaration = ancestors(name).find(|node| DECLARATION_KINDS.contains(&node.kind_id()))?;
+
+    let mut value = String::from("```ddlog\n");
+    let documented = match declaration.kind_id() {
+        // constructors are documented within the type definition (and carry their own attributes)
+        dl::kind::CONS_POS | dl::kind::CONS_REC => declaration
+            .parent()
+            .filter(|parent| dl::kind::CONS == parent.kind_id())
+            .unwrap_or(declaration),
+        _ => {
+            let item = ancestors(declaration).find(|node| dl::kind::ANNOTATED_ITEM == node.kind_id())?;
+            let mut cursor = item.walk();
+            let attributes = item
+                .named_children(&mut cursor)
+                .find(|child| dl::kind::ATTRIBUTES == child.kind_id());
+            if let Some(attributes) = attributes {
+                value.push_str(&content.utf8_text_for_tree_sitter_node(&attributes));
+                value.push('\n');
+            }
+            item
+        },
+    };
+    value.push_str(&signature(content, &declaration));
+    value.push_str("\n```");
+
+    if let dl::kind::CONS_POS | dl::kind::CONS_REC = declaration.kind_id() {
+        let typedef = ancestors(declaration).find(|node| dl::kind::TYPEDEF_NORMAL == node.kind_id());
+        if let Some(typedef) = typedef {
+            let mut cursor = typedef.walk();
+            let name_type = typedef
+                .named_children(&mut cursor)
+                .find(|child| dl::kind::NAME_TYPE == child.kind_id());
+            if let Some(name_type) = name_type {
+                let name_type = content.utf8_text_for_tree_sitter_node(&name_type);
+                value.push_str(&format!("\n\nconstructor of type `{}`", name_type));
+            }
+        }
+    }
+
+    let docs = doc_comments(content, &documented);
+    if !docs.is_empty() {
+        value.push_str("\n\n");
+        value.push_str(&docs);
+    }
+
+    Some(value)
+}
+
+// The source text of a declaration, omitting the body of (non-extern) functions.
+fn signature(content: &ropey::Rope, declaration: &tree_sitter::Node) -> String {
+    let end_byte = if dl::kind::FUNCTION_NORMAL == declaration.kind_id() {
+        let mut cursor = declaration.walk();
+        let body = declaration.named_children(&mut cursor).find(|child| {
+            [dl::kind::FUNCTION_NORMAL_BRANCH_0, dl::kind::FUNCTION_NORMAL_BRANCH_1].contains(&child.kind_id())
+        });
+        body.map_or(declaration.end_byte(), |body| body.start_byte())
+    } else {
+        declaration.end_byte()
+    };
+    let start = content.byte_to_char(declaration.start_byte() as usize);
+    let end = content.byte_to_char(end_byte as usize);
+    content.slice(start .. end).to_string().trim_end().to_string()
+}
+
+// Collect the comments immediately preceding a node (without intervening blank lines), with the
+// comment delimiters removed.
+fn doc_comments(content: &ropey::Rope, node: &tree_sitter::Node) -> String {
+    let mut comments = vec![];
+    let mut row = node.start_position().row();
+    let mut prev = node.prev_sibling();
+    while let Some(sibling) = prev {
+        let kind = sibling.kind_id();
+        // constructors are separated by `|`, which may follow their documentation
+        if dl::symbol::VERTICAL_LINE == kind {
+            prev = sibling.prev_sibling();
+            continue;
+        }
+        if ![dl::kind::COMMENT_LINE, dl::kind::COMMENT_BLOCK].contains(&kind) || sibling.end_position().row() + 1 < row
+        {
+            break;
+        }
+        comments.push(strip_comment(&content.utf8_text_for_tree_sitter_node(&sibling)));
+        row = sibling.start_position().row();
+        prev = sibling.prev_sibling();
+    }
+    comments.reverse();
+    comments.join("\n")
+}
+
+// Remove the delimiters (and any leading `*` decoration) from the text of a comment.
+fn strip_comment(comment: &str) -> String {
+    let strip_space = |line: &str| line.strip_prefix(' ').unwrap_or(line).trim_end().to_string();
+    if let Some(line) = comment.strip_prefix("//") {
+        let line = line.trim_start_matches('/').trim_start_matches('!');
+        return strip_space(line);
+    }
+    let block = comment.trim_start_matches("/*").trim_start_matches('*');
+    let block = block.strip_suffix("*/").unwrap_or(block);
+    let lines = block.lines().map(|line| {
+        let line = line.trim_start();
+        strip_space(line.strip_prefix('*').unwrap_or(line))
+    });
+    let lines = lines.collect::<Vec<_>>();
+    lines.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Render the declaration named at the start of the first occurrence of `at` in the text.
+    fn render_at(text: &str, at: &str) -> Option<String> {
+        let mut parser = tree_sitter::Parser::try_from(crate::core::Language::DDlogDl).unwrap();
+        let tree = parser.parse(text, None).unwrap().unwrap();
+        let content = ropey::Rope::from(text);
+        let byte = text.find(at).unwrap() as u32;
+        let name = tree.root_node().named_descendant_for_byte_range(byte, byte)?;
+        render(&content, &tree, name.range())
+    }
+
+    #[test]
+    fn signatures_omit_function_bodies() {
+        let text = indoc::indoc! {"
+            input relation R(a: u32)
+            typedef T = C{x: u32}
+            function f(x: u32): u32 { x + 1 }
+            extern function g(): string
+        "};
+        let expected = "```ddlog\ninput relation R(a: u32)\n```";
+        assert_eq!(Some(expected), render_at(text, "R(").as_deref());
+        let expected = "```ddlog\ntypedef T = C{x: u32}\n```";
+        assert_eq!(Some(expected), render_at(text, "T =").as_deref());
+        let expected = "```ddlog\nfunction f(x: u32): u32\n```";
+        assert_eq!(Some(expected), render_at(text, "f(").as_deref());
+        let expected = "```ddlog\nextern function g(): string\n```";
+        assert_eq!(Some(expected), render_at(text, "g(").as_deref());
+    }
+
+    #[test]
+    fn constructors_name_their_type() {
+        let text = "typedef T = C{x: u32}\n";
+        let expected = "```ddlog\nC{x: u32}\n```\n\nconstructor of type `T`";
+        assert_eq!(Some(expected), render_at(text, "C{").as_deref());
+    }
+
+    #[test]
+    fn doc_comments_precede_the_attributes_of_an_item() {
+        let text = indoc::indoc! {"
+            // The relation.
+            //   Indented.
+            #[size=4]
+            input relation R(a: u32)
+        "};
+        let expected = "```ddlog\n#[size=4]\ninput relation R(a: u32)\n```\n\nThe relation.\n  Indented.";
+        assert_eq!(Some(expected), render_at(text, "R(").as_deref());
+    }
+
+    #[test]
+    fn doc_comments_may_be_blocks() {
+        let text = indoc::indoc! {"
+            /* The first line.
+             * The second line.
+             */
+            function f(): u32 { 1 }
+        "};
+        let expected = "```ddlog\nfunction f(): u32\n```\n\nThe first line.\nThe second line.";
+        assert_eq!(Some(expected), render_at(text, "f(").as_deref());
+    }
+
+    #[test]
+    fn doc_comments_end_at_a_blank_line() {
+        let text = indoc::indoc! {"
+            // Not about the relation.
+
+            // The relation.
+            relation R(a: u32)
+        "};
+        let expected = "```ddlog\nrelation R(a: u32)\n```\n\nThe relation.";
+        assert_eq!(Some(expected), render_at(text, "R(").as_deref());
+        let text = "// Not about the relation.\n\nrelation R(a: u32)\n";
+        let expected = "```ddlog\nrelation R(a: u32)\n```";
+        assert_eq!(Some(expected), render_at(text, "R(").as_deref());
+    }
+
+    #[test]
+    fn comment_delimiters_are_stripped() {
+        assert_eq!("doc", strip_comment("// doc"));
+        assert_eq!("doc", strip_comment("/// doc"));
+        assert_eq!("doc", strip_comment("//! doc"));
+        assert_eq!("  indented", strip_comment("//   indented"));
+        assert_eq!("", strip_comment("//"));
+        assert_eq!("doc", strip_comment("/* doc */"));
+        assert_eq!("doc", strip_comment("/** doc */"));
+        assert_eq!("first\nsecond", strip_comment("/*\n * first\n * second\n */"));
+        assert_eq!("first\n\nsecond", strip_comment("/* first\n *\n * second */"));
+    }
+}
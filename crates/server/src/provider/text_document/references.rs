@@ -0,0 +1,101 @@
+use crate::analysis::symbol::resolve::Resolver;
+use std::sync::Arc;
+
+pub async fn references(
+    session: Arc<crate::core::Session>,
+    params: lsp::ReferenceParams,
+) -> anyhow::Result<Option<Vec<lsp::Location>>> {
+    let origin_uri = &params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+    let include_declaration = params.context.include_declaration;
+
+    let mut resolver = Resolver::new(&session);
+
+    let origin_index = resolver
+        .index(origin_uri)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not index document for uri: {:#?}", origin_uri))?;
+    let origin = if let Some(occurrence) = origin_index.occurrence_at(position) {
+        occurrence.clone()
+    } else {
+        return Ok(None);
+    };
+
+    let targets = resolver.resolve(origin_uri, &origin).await;
+    if targets.is_empty() {
+        return Ok(None);
+    }
+
+    let locations = resolver
+        .references(&targets, include_declaration)
+        .await
+        .into_iter()
+        .map(|(uri, occurrence)| lsp::Location {
+            uri,
+            range: occurrence.range,
+        })
+        .collect();
+
+    Ok(Some(locations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Session;
+
+    async fn references_at(
+        session: &Arc<Session>,
+        uri: &lsp::Url,
+        position: lsp::Position,
+        include_declaration: bool,
+    ) -> Vec<lsp::Location> {
+        let params = lsp::ReferenceParams {
+            text_document_position: lsp::TextDocumentPositionParams::new(
+                lsp::TextDocumentIdentifier::new(uri.clone()),
+                position,
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: lsp::ReferenceContext { include_declaration },
+        };
+        let mut locations = references(session.clone(), params).await.unwrap().unwrap_or_default();
+        locations.sort_by_key(|location| (location.uri.to_string(), location.range.start));
+        locations
+    }
+
+    #[tokio::test]
+    async fn references_in_importing_modules_are_found() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let lib = session
+            .insert_text("/program/lib.dl", "input relation A(x: u32)\n")
+            .await;
+        let main = session
+            .insert_text(
+                "/program/main.dl",
+                "import lib\noutput relation B(x: u32)\nB(x) :- lib::A(x).\nB(x) :- B(x), A(x).\n",
+            )
+            .await;
+        // the `A` of a module which isn't imported is a different relation
+        session
+            .insert_text(
+                "/program/other.dl",
+                "input relation A(x: u32)\nrelation C(x: u32)\nC(x) :- A(x).\n",
+            )
+            .await;
+
+        let range = |line, start, end| lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, end));
+        let declaration = lsp::Location::new(lib.clone(), range(0, 15, 16));
+        let uses = vec![
+            lsp::Location::new(main.clone(), range(2, 13, 14)),
+            lsp::Location::new(main.clone(), range(3, 14, 15)),
+        ];
+        let with_declaration = std::iter::once(declaration).chain(uses.clone()).collect::<Vec<_>>();
+
+        // from the declaration and from a use, with and without the declaration
+        for (uri, position) in [(&lib, lsp::Position::new(0, 15)), (&main, lsp::Position::new(2, 13))] {
+            assert_eq!(with_declaration, references_at(&session, uri, position, true).await);
+            assert_eq!(uses, references_at(&session, uri, position, false).await);
+        }
+    }
+}
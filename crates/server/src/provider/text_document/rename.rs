@@ -0,0 +1,248 @@
+use crate::analysis::symbol::{
+    context::{Namespace, Role},
+    occurrence::Occurrence,
+    resolve::{Declaration, Resolver},
+    ScopedName,
+};
+use std::{collections::HashMap, sync::Arc};
+
+// Find the occurrence at the given position along with the declarations it resolves to. Only
+// entities declared in a known module can be renamed.
+async fn rename_target(
+    resolver: &mut Resolver<'_>,
+    uri: &lsp::Url,
+    position: lsp::Position,
+) -> anyhow::Result<Option<(Occurrence, Vec<Declaration>)>> {
+    let index = resolver
+        .index(uri)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not index document for uri: {:#?}", uri))?;
+    if let Some(occurrence) = index.occurrence_at(position) {
+        let declarations = resolver.resolve(uri, occurrence).await;
+        if !declarations.is_empty() {
+            return Ok(Some((occurrence.clone(), declarations)));
+        }
+    }
+    Ok(None)
+}
+
+// Check that a new name is a well-formed (unqualified) identifier for the given namespace, which is
+// not a keyword of any of the given languages (i.e., those of the documents it would be written
+// in).
+fn validate_name(namespace: Namespace, name: &str, languages: &[crate::core::Language]) -> anyhow::Result<()> {
+    let mut chars = name.chars();
+    let first = chars
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("new name must not be empty"))?;
+    if !(first.is_alphabetic() || '_' == first) || !chars.all(|c| c.is_alphanumeric() || '_' == c) {
+        anyhow::bail!("`{}` is not a valid identifier", name);
+    }
+    match namespace {
+        Namespace::Constructor | Namespace::Relation if !first.is_uppercase() => {
+            anyhow::bail!("{:?} names must start with an uppercase letter", namespace);
+        },
+        Namespace::Function if first.is_uppercase() => {
+            anyhow::bail!("{:?} names must not start with an uppercase letter", namespace);
+        },
+        _ => {},
+    }
+    for language in languages {
+        if tree_sitter::Language::from(*language).id_for_node_kind(name, false) != 0 {
+            anyhow::bail!("`{}` is a reserved keyword", name);
+        }
+    }
+    Ok(())
+}
+
+pub async fn prepare_rename(
+    session: Arc<crate::core::Session>,
+    params: lsp::TextDocumentPositionParams,
+) -> anyhow::Result<Option<lsp::PrepareRenameResponse>> {
+    let mut resolver = Resolver::new(&session);
+    let target = rename_target(&mut resolver, &params.text_document.uri, params.position).await?;
+    Ok(target.map(|(occurrence, _)| {
+        let range = occurrence.name_range();
+        let placeholder = occurrence.name.name;
+        lsp::PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }
+    }))
+}
+
+pub async fn rename(
+    session: Arc<crate::core::Session>,
+    params: lsp::RenameParams,
+) -> anyhow::Result<Option<lsp::WorkspaceEdit>> {
+    let uri = &params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+    let new_name = params.new_name;
+
+    let mut resolver = Resolver::new(&session);
+    let (origin, declarations) = if let Some(target) = rename_target(&mut resolver, uri, position).await? {
+        target
+    } else {
+        return Ok(None);
+    };
+
+    if new_name == origin.name.name {
+        return Ok(None);
+    }
+
+    let references = resolver.references(&declarations, true).await;
+
+    let mut languages = vec![];
+    for (reference_uri, _) in &references {
+        if let Some(index) = resolver.index(reference_uri).await {
+            if !languages.contains(&index.language) {
+                languages.push(index.language);
+            }
+        }
+    }
+    validate_name(origin.namespace, &new_name, &languages)?;
+
+    let collision = |existing: &lsp::Url| {
+        anyhow::anyhow!(
+            "renaming `{}` to `{}` would collide with an existing `{}` in {}",
+            origin.name.name,
+            new_name,
+            new_name,
+            existing
+        )
+    };
+
+    // Refuse the rename if the new name, written the same way as each existing reference, would
+    // already resolve to some other declaration (e.g., in the defining module or an import).
+    for (reference_uri, reference) in &references {
+        let renamed = Occurrence {
+            name: ScopedName {
+                qualifier: reference.name.qualifier.clone(),
+                name: new_name.clone(),
+            },
+            role: Role::Reference,
+            ..reference.clone()
+        };
+        if let Some(existing) = resolver.resolve(reference_uri, &renamed).await.first() {
+            return Err(collision(&existing.uri));
+        }
+    }
+
+    // Likewise refuse it if existing occurrences of the new name could see the renamed declaration,
+    // i.e., if they would become ambiguous (e.g., in a module importing both the defining module and
+    // another one declaring the new name) or refer to the renamed declaration instead.
+    for document_uri in session.document_uris() {
+        let index = match resolver.index(&document_uri).await {
+            Some(index) => index,
+            None => continue,
+        };
+        let existing = index
+            .occurrences
+            .iter()
+            .filter(|occurrence| origin.namespace == occurrence.namespace && new_name == occurrence.name.name);
+        for occurrence in existing {
+            let original = Occurrence {
+                name: ScopedName {
+                    qualifier: occurrence.name.qualifier.clone(),
+                    name: origin.name.name.clone(),
+                },
+                role: Role::Reference,
+                ..occurrence.clone()
+            };
+            let visible = resolver.resolve(&document_uri, &original).await;
+            if visible.iter().any(|declaration| declarations.contains(declaration)) {
+                return Err(collision(&document_uri));
+            }
+        }
+    }
+
+    let mut changes = HashMap::<lsp::Url, Vec<lsp::TextEdit>>::new();
+    for (reference_uri, reference) in references {
+        // only the final component of qualified references changes
+        let edit = lsp::TextEdit::new(reference.name_range(), new_name.clone());
+        changes.entry(reference_uri).or_default().push(edit);
+    }
+
+    Ok(Some(lsp::WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Language, Session};
+
+    async fn rename_at(
+        session: &Arc<Session>,
+        uri: &lsp::Url,
+        position: lsp::Position,
+        new_name: &str,
+    ) -> anyhow::Result<Option<lsp::WorkspaceEdit>> {
+        let params = lsp::RenameParams {
+            text_document_position: lsp::TextDocumentPositionParams::new(
+                lsp::TextDocumentIdentifier::new(uri.clone()),
+                position,
+            ),
+            new_name: new_name.into(),
+            work_done_progress_params: Default::default(),
+        };
+        rename(session.clone(), params).await
+    }
+
+    #[test]
+    fn names_are_validated_for_their_namespace() {
+        assert!(validate_name(Namespace::Relation, "R", &[]).is_ok());
+        assert!(validate_name(Namespace::Relation, "r", &[]).is_err());
+        assert!(validate_name(Namespace::Function, "F", &[]).is_err());
+        assert!(validate_name(Namespace::Type, "", &[]).is_err());
+        assert!(validate_name(Namespace::Type, "a-b", &[]).is_err());
+    }
+
+    #[test]
+    fn names_are_validated_against_the_keywords_of_each_language() {
+        assert!(validate_name(Namespace::Type, "commit", &[Language::DDlogDl]).is_ok());
+        assert!(validate_name(Namespace::Type, "commit", &[Language::DDlogDl, Language::DDlogDat]).is_err());
+        assert!(validate_name(Namespace::Type, "relation", &[Language::DDlogDl]).is_err());
+    }
+
+    #[tokio::test]
+    async fn references_in_importing_modules_are_renamed() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let lib = session
+            .insert_text("/program/lib.dl", "input relation A(x: u32)\n")
+            .await;
+        let main = session
+            .insert_text(
+                "/program/main.dl",
+                "import lib\noutput relation B(x: u32)\nB(x) :- lib::A(x).\n",
+            )
+            .await;
+
+        let edit = rename_at(&session, &lib, lsp::Position::new(0, 15), "C")
+            .await
+            .unwrap()
+            .unwrap();
+        let changes = edit.changes.unwrap();
+        let range = |line, start, end| lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, end));
+        assert_eq!(vec![lsp::TextEdit::new(range(0, 15, 16), "C".into())], changes[&lib]);
+        assert_eq!(vec![lsp::TextEdit::new(range(2, 13, 14), "C".into())], changes[&main]);
+    }
+
+    #[tokio::test]
+    async fn names_visible_alongside_the_renamed_declaration_collide() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let a = session.insert_text("/program/a.dl", "input relation A(x: u32)\n").await;
+        session.insert_text("/program/b.dl", "input relation C(x: u32)\n").await;
+        session
+            .insert_text(
+                "/program/main.dl",
+                "import a\nimport b\noutput relation O(x: u32)\nO(x) :- C(x).\n",
+            )
+            .await;
+
+        // `main` doesn't refer to `A`, but its `C` would become ambiguous with the renamed one
+        assert!(rename_at(&session, &a, lsp::Position::new(0, 15), "C").await.is_err());
+        assert!(rename_at(&session, &a, lsp::Position::new(0, 15), "D")
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,341 @@
+use crate::analysis::symbol::{context::Namespace, resolve::Resolver, ScopedName};
+use lsp_text::RopeExt;
+use std::{collections::HashMap, sync::Arc};
+
+mod dat;
+mod dl;
+
+/// The token types of the legend, in the order of their indices in [`TokenType`].
+const TOKEN_TYPES: &[lsp::SemanticTokenType] = &[
+    lsp::SemanticTokenType::STRUCT,
+    lsp::SemanticTokenType::TYPE,
+    lsp::SemanticTokenType::ENUM_MEMBER,
+    lsp::SemanticTokenType::PROPERTY,
+    lsp::SemanticTokenType::FUNCTION,
+    lsp::SemanticTokenType::MACRO,
+    lsp::SemanticTokenType::INTERFACE,
+    lsp::SemanticTokenType::VARIABLE,
+    lsp::SemanticTokenType::PARAMETER,
+    lsp::SemanticTokenType::TYPE_PARAMETER,
+    lsp::SemanticTokenType::new("decorator"),
+    lsp::SemanticTokenType::OPERATOR,
+];
+
+/// The token modifiers of the legend, in the order of their bits in [`modifier`].
+const TOKEN_MODIFIERS: &[lsp::SemanticTokenModifier] = &[
+    lsp::SemanticTokenModifier::DECLARATION,
+    lsp::SemanticTokenModifier::new("input"),
+    lsp::SemanticTokenModifier::new("output"),
+    lsp::SemanticTokenModifier::new("internal"),
+];
+
+/// The kinds of tokens which are classified (as indices into the legend).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TokenType {
+    Relation,
+    Type,
+    Constructor,
+    Field,
+    Function,
+    Transformer,
+    Index,
+    Variable,
+    Parameter,
+    TypeParameter,
+    Attribute,
+    Interpolation,
+}
+
+impl From<Namespace> for TokenType {
+    fn from(namespace: Namespace) -> Self {
+        match namespace {
+            Namespace::Constructor => TokenType::Constructor,
+            Namespace::Function => TokenType::Function,
+            Namespace::Index => TokenType::Index,
+            Namespace::Relation => TokenType::Relation,
+            Namespace::Transformer => TokenType::Transformer,
+            Namespace::Type => TokenType::Type,
+        }
+    }
+}
+
+/// The bits of the token modifiers (as indices into the legend).
+pub mod modifier {
+    pub const DECLARATION: u32 = 1 << 0;
+    pub const INPUT: u32 = 1 << 1;
+    pub const OUTPUT: u32 = 1 << 2;
+    pub const INTERNAL: u32 = 1 << 3;
+}
+
+/// A classified token of a document.
+#[derive(Clone, Debug)]
+pub struct Token {
+    range: lsp::Range,
+    token_type: TokenType,
+    modifiers: u32,
+    /// The name of the relation (if the token refers to one), used to determine its role.
+    relation: Option<ScopedName>,
+}
+
+impl Token {
+    fn new(range: lsp::Range, token_type: TokenType) -> Self {
+        Self {
+            range,
+            token_type,
+            modifiers: 0,
+            relation: None,
+        }
+    }
+
+    fn with_modifier(mut self, modifier: u32, enabled: bool) -> Self {
+        if enabled {
+            self.modifiers |= modifier;
+        }
+        self
+    }
+}
+
+/// The legend of the semantic tokens provided by the server.
+pub fn legend() -> lsp::SemanticTokensLegend {
+    lsp::SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Compute "textDocument/semanticTokens/full" for a given document.
+pub async fn semantic_tokens_full(
+    session: Arc<crate::core::Session>,
+    params: lsp::SemanticTokensParams,
+) -> anyhow::Result<Option<lsp::SemanticTokensResult>> {
+    let uri = &params.text_document.uri;
+    let tokens = semantic_tokens(&session, uri).await?;
+    Ok(Some(lsp::SemanticTokensResult::Tokens(tokens)))
+}
+
+/// Compute "textDocument/semanticTokens/full/delta" for a given document, relative to the tokens
+/// last computed for it.
+pub async fn semantic_tokens_full_delta(
+    session: Arc<crate::core::Session>,
+    params: lsp::SemanticTokensDeltaParams,
+) -> anyhow::Result<Option<lsp::SemanticTokensFullDeltaResult>> {
+    let uri = &params.text_document.uri;
+    let previous = session
+        .document_semantic_tokens
+        .get(uri)
+        .map(|tokens| tokens.value().clone())
+        .filter(|tokens| tokens.result_id.as_ref() == Some(&params.previous_result_id));
+    let tokens = semantic_tokens(&session, uri).await?;
+    let result = match previous {
+        Some(previous) => lsp::SemanticTokensFullDeltaResult::TokensDelta(lsp::SemanticTokensDelta {
+            result_id: tokens.result_id,
+            edits: edits(&previous.data, &tokens.data),
+        }),
+        None => lsp::SemanticTokensFullDeltaResult::Tokens(tokens),
+    };
+    Ok(Some(result))
+}
+
+// Compute the semantic tokens of a document and remember them (for later delta requests).
+async fn semantic_tokens(session: &crate::core::Session, uri: &lsp::Url) -> anyhow::Result<lsp::SemanticTokens> {
+    let mut tokens = {
+        let text = session.get_text(uri).await?.clone();
+        let content = text.get_content().await?;
+        let tree = session
+            .get_tree(uri)
+            .await?
+            .clone()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+        let tree = tree.lock().await;
+        let tokens = match text.language {
+            crate::core::Language::DDlogDat => self::dat::tokens(&content, &tree),
+            crate::core::Language::DDlogDl => self::dl::tokens(&content, &tree),
+        };
+        split_lines(&content, tokens)
+    };
+
+    // relations are distinguished by their role, which is only known from their declaration
+    let mut resolver = Resolver::new(session);
+    let mut roles = HashMap::new();
+    for token in tokens.iter_mut() {
+        let name = match &token.relation {
+            Some(name) => name,
+            None => continue,
+        };
+        if !roles.contains_key(name) {
+            let role = relation_role(session, &mut resolver, uri, name).await;
+            roles.insert(name.clone(), role);
+        }
+        token.modifiers |= roles[name];
+    }
+
+    tokens.sort_by_key(|token| (token.range.start.line, token.range.start.character));
+    tokens.dedup_by_key(|token| token.range.start);
+    let data = encode(&tokens);
+
+    let result_id = session
+        .document_semantic_tokens
+        .get(uri)
+        .and_then(|tokens| tokens.result_id.as_ref().and_then(|id| id.parse::<u64>().ok()))
+        .map_or(0, |id| id + 1);
+    let tokens = lsp::SemanticTokens {
+        result_id: Some(result_id.to_string()),
+        data,
+    };
+    session.document_semantic_tokens.insert(uri.clone(), tokens.clone());
+    Ok(tokens)
+}
+
+// Determine the modifier for the role (`input`, `output`, or internal) of a relation. Declarations
+// which can't be read are skipped, so that the relation is just not distinguished then.
+async fn relation_role(
+    session: &crate::core::Session,
+    resolver: &mut Resolver<'_>,
+    uri: &lsp::Url,
+    name: &ScopedName,
+) -> u32 {
+    use crate::core::language::dl::kind;
+    let kinds = &[kind::REL_ARGS, kind::REL_ELEM];
+    for declaration in resolver.resolve_name(uri, Namespace::Relation, name.clone()).await {
+        let role = declaration
+            .with_node(session, kinds, |content, node| {
+                let mut cursor = node.walk();
+                let role = node
+                    .named_children(&mut cursor)
+                    .find(|child| kind::REL_ROLE == child.kind_id());
+                role.map(|role| content.utf8_text_for_tree_sitter_node(&role).into_owned())
+            })
+            .await;
+        if let Ok(Some(role)) = role {
+            return match role.as_deref() {
+                Some("input") => modifier::INPUT,
+                Some("output") => modifier::OUTPUT,
+                _ => modifier::INTERNAL,
+            };
+        }
+    }
+    0
+}
+
+// Split the tokens spanning multiple lines (e.g., block comments or strings) into one token per
+// line, since tokens are encoded by their length (and not every client supports multiline tokens).
+fn split_lines(content: &ropey::Rope, tokens: Vec<Token>) -> Vec<Token> {
+    let mut split = vec![];
+    for token in tokens {
+        let (start, end) = (token.range.start, token.range.end);
+        if start.line >= end.line {
+            split.push(token);
+            continue;
+        }
+        for line in start.line ..= end.line {
+            let line_start = if line == start.line { start.character } else { 0 };
+            let line_end = if line == end.line {
+                end.character
+            } else {
+                let text = content.line(line as usize);
+                let text = text.chars().filter(|c| !['\n', '\r'].contains(c));
+                text.map(|c| c.len_utf16() as u32).sum()
+            };
+            if line_start < line_end {
+                let range = lsp::Range::new(lsp::Position::new(line, line_start), lsp::Position::new(line, line_end));
+                split.push(Token { range, ..token.clone() });
+            }
+        }
+    }
+    split
+}
+
+// Encode the (sorted, single-line) tokens relative to each other.
+fn encode(tokens: &[Token]) -> Vec<lsp::SemanticToken> {
+    let mut data = vec![];
+    let mut prev = lsp::Position::default();
+    for token in tokens {
+        let start = token.range.start;
+        let delta_line = start.line - prev.line;
+        let delta_start = if 0 == delta_line {
+            start.character - prev.character
+        } else {
+            start.character
+        };
+        data.push(lsp::SemanticToken {
+            delta_line,
+            delta_start,
+            length: token.range.end.character.saturating_sub(start.character),
+            token_type: token.token_type as u32,
+            token_modifiers_bitset: token.modifiers,
+        });
+        prev = start;
+    }
+    data
+}
+
+// Compute the edit turning the previous tokens into the current ones, by replacing everything
+// between their common prefix and suffix.
+fn edits(previous: &[lsp::SemanticToken], current: &[lsp::SemanticToken]) -> Vec<lsp::SemanticTokensEdit> {
+    let prefix = previous.iter().zip(current).take_while(|(a, b)| a == b).count();
+    let suffix = previous[prefix ..]
+        .iter()
+        .rev()
+        .zip(current[prefix ..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let deleted = previous.len() - prefix - suffix;
+    let inserted = &current[prefix .. current.len() - suffix];
+    if 0 == deleted && inserted.is_empty() {
+        return vec![];
+    }
+    // the edits index into the flattened data, where each token takes up 5 integers
+    vec![lsp::SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(delta_line: u32, delta_start: u32) -> lsp::SemanticToken {
+        lsp::SemanticToken {
+            delta_line,
+            delta_start,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn multiline_tokens_are_split_per_line() {
+        let content = ropey::Rope::from("a /* b\nc\n\nd */ e\n");
+        let range = |line, start, end| lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, end));
+        let comment = lsp::Range::new(lsp::Position::new(0, 2), lsp::Position::new(3, 4));
+        let tokens = vec![
+            Token::new(range(0, 0, 1), TokenType::Variable),
+            Token::new(comment, TokenType::Attribute),
+        ];
+        let ranges = split_lines(&content, tokens)
+            .into_iter()
+            .map(|token| token.range)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![range(0, 0, 1), range(0, 2, 6), range(1, 0, 1), range(3, 0, 4)],
+            ranges
+        );
+    }
+
+    #[test]
+    fn edits_replace_the_differing_middle() {
+        let previous = vec![token(0, 0), token(1, 0), token(1, 2)];
+        let current = vec![token(0, 0), token(2, 4), token(0, 3), token(1, 2)];
+        let expected = vec![lsp::SemanticTokensEdit {
+            start: 5,
+            delete_count: 5,
+            data: Some(vec![token(2, 4), token(0, 3)]),
+        }];
+        assert_eq!(expected, edits(&previous, &current));
+        assert!(edits(&current, &current).is_empty());
+    }
+}
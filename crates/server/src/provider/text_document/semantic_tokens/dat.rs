@@ -0,0 +1,181 @@
+use super::{modifier, Token, TokenType};
+use crate::{
+    analysis::symbol::{
+        context::{self, Namespace},
+        ScopedName,
+    },
+    core::{
+        language::{
+            dat::{
+                keyword,
+                kind,
+                visitor::{default, Visitor},
+            },
+            HasWalker,
+            NodeMove,
+        },
+        node::NodeWalker,
+    },
+};
+use ddlog_lsp_syntax::error::SyntaxError;
+use lsp_text::RopeExt;
+
+// The kinds of the nodes which are classified as (or contain) tokens.
+const TOKEN_KINDS: &[u16] = &[
+    kind::ATTRIBUTE,
+    kind::EXP_FIELD,
+    kind::INTERPOLATION,
+    kind::NAME_ARG,
+    kind::NAME_CONS,
+    kind::NAME_FIELD,
+    kind::NAME_FUNC,
+    kind::NAME_INDEX,
+    kind::NAME_REL,
+    kind::NAME_TYPE,
+    kind::NAME_VAR_TERM,
+];
+
+/// A visitor which records the nodes of the [`TOKEN_KINDS`] it successfully visits.
+struct TokenVisitor<'tree> {
+    walker: NodeWalker<'tree>,
+    nodes: Vec<tree_sitter::Node<'tree>>,
+}
+
+impl<'tree> TokenVisitor<'tree> {
+    fn new(node: tree_sitter::Node<'tree>) -> Self {
+        let walker = NodeWalker::new(crate::core::Language::DDlogDat, node);
+        let nodes = vec![];
+        Self { walker, nodes }
+    }
+
+    // Record the (innermost) node of the given kind enclosing the current node of the walker.
+    fn record(&mut self, kind: u16) {
+        let node =
+            std::iter::successors(Some(self.walker.node()), |node| node.parent()).find(|node| kind == node.kind_id());
+        self.nodes.extend(node);
+    }
+}
+
+impl<'tree> HasWalker<'tree> for TokenVisitor<'tree> {
+    fn walker(&mut self) -> &mut NodeWalker<'tree> {
+        &mut self.walker
+    }
+}
+
+macro_rules! record {
+    ($($visit:ident => $default:ident, $kind:ident;)*) => {
+        $(
+            fn $visit(&mut self, node_move: NodeMove) -> Result<(), SyntaxError<()>> {
+                default::$default(self, node_move)?;
+                self.record(kind::$kind);
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl<'tree> Visitor<'tree> for TokenVisitor<'tree> {
+    record! {
+        visit_attribute => attribute, ATTRIBUTE;
+        visit_exp_field => exp_field, EXP_FIELD;
+        visit_interpolation => interpolation, INTERPOLATION;
+        visit_name_arg => name_arg, NAME_ARG;
+        visit_name_cons => name_cons, NAME_CONS;
+        visit_name_field => name_field, NAME_FIELD;
+        visit_name_func => name_func, NAME_FUNC;
+        visit_name_index => name_index, NAME_INDEX;
+        visit_name_rel => name_rel, NAME_REL;
+        visit_name_type => name_type, NAME_TYPE;
+        visit_name_var_term => name_var_term, NAME_VAR_TERM;
+    }
+}
+
+/// Classify the tokens of a ".dat" document.
+pub fn tokens(content: &ropey::Rope, tree: &tree_sitter::Tree) -> Vec<Token> {
+    let mut nodes = vec![];
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for command in root.named_children(&mut cursor) {
+        if kind::COMMAND != command.kind_id() {
+            continue;
+        }
+        // commands with syntax errors can't be visited (completely), so just search them instead
+        if command.has_error() {
+            let mut work = vec![command];
+            while let Some(node) = work.pop() {
+                if TOKEN_KINDS.contains(&node.kind_id()) {
+                    nodes.push(node);
+                }
+                let mut cursor = node.walk();
+                work.extend(node.named_children(&mut cursor));
+            }
+        } else {
+            let mut visitor = TokenVisitor::new(command);
+            visitor.visit().ok();
+            nodes.extend(visitor.nodes);
+        }
+    }
+
+    let mut tokens = vec![];
+    for node in nodes {
+        classify(content, node, &mut tokens);
+    }
+    tokens
+}
+
+// Classify a recorded node as one or more tokens.
+fn classify(content: &ropey::Rope, node: tree_sitter::Node, tokens: &mut Vec<Token>) {
+    let range = |node: &tree_sitter::Node| content.tree_sitter_range_to_lsp_range(node.range());
+    let token = match node.kind_id() {
+        kind::ATTRIBUTE => match node.named_child(0) {
+            Some(name) => Token::new(range(&name), TokenType::Attribute),
+            None => return,
+        },
+        kind::EXP_FIELD => {
+            let field = node.named_child(node.named_child_count().saturating_sub(1));
+            match field.filter(|field| kind::EXP != field.kind_id()) {
+                Some(field) => Token::new(range(&field), TokenType::Field),
+                None => return,
+            }
+        },
+        kind::INTERPOLATION => {
+            let delimiters = [node.child(0), node.child(node.child_count().saturating_sub(1))];
+            let delimiters = delimiters.iter().flatten().filter(|delimiter| !delimiter.is_named());
+            tokens.extend(delimiters.map(|delimiter| Token::new(range(delimiter), TokenType::Interpolation)));
+            return;
+        },
+        // the fields of records given by name, e.g., `R(.a = 1)`
+        kind::NAME_ARG | kind::NAME_FIELD => Token::new(range(&node), TokenType::Field),
+        _ => match context::classify_dat(&node) {
+            Some((namespace, _)) => {
+                let mut token = Token::new(range(&node), namespace.into());
+                if Namespace::Relation == namespace {
+                    let name = content.utf8_text_for_tree_sitter_node(&node);
+                    token.relation = Some(ScopedName::parse(&name));
+                }
+                token
+            },
+            // `name_cons` is also used for the fields of records and constructors
+            None if kind::NAME_CONS == node.kind_id() => Token::new(range(&node), TokenType::Field),
+            None if kind::NAME_VAR_TERM == node.kind_id() => {
+                let bound = matches!(node.parent(), Some(parent) if is_declared_var(&parent));
+                Token::new(range(&node), TokenType::Variable).with_modifier(modifier::DECLARATION, bound)
+            },
+            None => return,
+        },
+    };
+    tokens.push(token);
+}
+
+// Whether a node declares a variable, i.e., `var x` or a variable pattern.
+fn is_declared_var(node: &tree_sitter::Node) -> bool {
+    match node.kind_id() {
+        kind::PAT_TERM_DECL_VAR => true,
+        kind::EXP_DECL_VAR => {
+            let mut cursor = node.walk();
+            let mut children = node.children(&mut cursor);
+            children.any(|child| keyword::VAR == child.kind_id())
+        },
+        _ => false,
+    }
+}
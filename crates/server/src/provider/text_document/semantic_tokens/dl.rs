@@ -0,0 +1,237 @@
+use super::{modifier, Token, TokenType};
+use crate::{
+    analysis::symbol::{
+        context::{self, Namespace, Role},
+        ScopedName,
+    },
+    core::{
+        language::{
+            dl::{
+                keyword,
+                kind,
+                symbol,
+                visitor::{default, Visitor},
+            },
+            HasWalker,
+            NodeMove,
+        },
+        node::NodeWalker,
+    },
+};
+use ddlog_lsp_syntax::error::SyntaxError;
+use lsp_text::RopeExt;
+use std::collections::HashSet;
+
+// The kinds of the nodes which are classified as (or contain) tokens.
+const TOKEN_KINDS: &[u16] = &[
+    kind::ATTRIBUTE,
+    kind::EXP_FIELD,
+    kind::INTERPOLATION,
+    kind::NAME_ARG,
+    kind::NAME_CONS,
+    kind::NAME_FIELD,
+    kind::NAME_FUNC,
+    kind::NAME_INDEX,
+    kind::NAME_REL,
+    kind::NAME_TRANS,
+    kind::NAME_TYPE,
+    kind::NAME_VAR_TERM,
+    kind::NAME_VAR_TYPE,
+];
+
+/// A visitor which records the nodes of the [`TOKEN_KINDS`] it successfully visits.
+struct TokenVisitor<'tree> {
+    walker: NodeWalker<'tree>,
+    nodes: Vec<tree_sitter::Node<'tree>>,
+}
+
+impl<'tree> TokenVisitor<'tree> {
+    fn new(node: tree_sitter::Node<'tree>) -> Self {
+        let walker = NodeWalker::new(crate::core::Language::DDlogDl, node);
+        let nodes = vec![];
+        Self { walker, nodes }
+    }
+
+    // Record the (innermost) node of the given kind enclosing the current node of the walker.
+    fn record(&mut self, kind: u16) {
+        let node =
+            std::iter::successors(Some(self.walker.node()), |node| node.parent()).find(|node| kind == node.kind_id());
+        self.nodes.extend(node);
+    }
+}
+
+impl<'tree> HasWalker<'tree> for TokenVisitor<'tree> {
+    fn walker(&mut self) -> &mut NodeWalker<'tree> {
+        &mut self.walker
+    }
+}
+
+macro_rules! record {
+    ($($visit:ident => $default:ident, $kind:ident;)*) => {
+        $(
+            fn $visit(&mut self, node_move: NodeMove) -> Result<(), SyntaxError<()>> {
+                default::$default(self, node_move)?;
+                self.record(kind::$kind);
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl<'tree> Visitor<'tree> for TokenVisitor<'tree> {
+    record! {
+        visit_attribute => attribute, ATTRIBUTE;
+        visit_exp_field => exp_field, EXP_FIELD;
+        visit_interpolation => interpolation, INTERPOLATION;
+        visit_name_arg => name_arg, NAME_ARG;
+        visit_name_cons => name_cons, NAME_CONS;
+        visit_name_field => name_field, NAME_FIELD;
+        visit_name_func => name_func, NAME_FUNC;
+        visit_name_index => name_index, NAME_INDEX;
+        visit_name_rel => name_rel, NAME_REL;
+        visit_name_trans => name_trans, NAME_TRANS;
+        visit_name_type => name_type, NAME_TYPE;
+        visit_name_var_term => name_var_term, NAME_VAR_TERM;
+        visit_name_var_type => name_var_type, NAME_VAR_TYPE;
+    }
+}
+
+/// Classify the tokens of a ".dl" document.
+pub fn tokens(content: &ropey::Rope, tree: &tree_sitter::Tree) -> Vec<Token> {
+    let mut nodes = vec![];
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for item in root.named_children(&mut cursor) {
+        if kind::ANNOTATED_ITEM != item.kind_id() {
+            continue;
+        }
+        // items with syntax errors can't be visited (completely), so just search them instead
+        if item.has_error() {
+            let mut work = vec![item];
+            while let Some(node) = work.pop() {
+                if TOKEN_KINDS.contains(&node.kind_id()) {
+                    nodes.push(node);
+                }
+                let mut cursor = node.walk();
+                work.extend(node.named_children(&mut cursor));
+            }
+        } else {
+            let mut visitor = TokenVisitor::new(item);
+            visitor.visit().ok();
+            nodes.extend(visitor.nodes);
+        }
+    }
+    // variables are bound by their first occurrence, so visit them in order
+    nodes.sort_by_key(|node| node.start_byte());
+
+    let mut rule_bindings = HashSet::new();
+    let mut tokens = vec![];
+    for node in nodes {
+        classify(content, node, &mut rule_bindings, &mut tokens);
+    }
+    tokens
+}
+
+// Classify a recorded node as one or more tokens.
+fn classify(
+    content: &ropey::Rope,
+    node: tree_sitter::Node,
+    rule_bindings: &mut HashSet<(u32, String)>,
+    tokens: &mut Vec<Token>,
+) {
+    let range = |node: &tree_sitter::Node| content.tree_sitter_range_to_lsp_range(node.range());
+    let parent = node.parent().map(|parent| parent.kind_id());
+    let token = match node.kind_id() {
+        kind::ATTRIBUTE => match node.named_child(0) {
+            Some(name) => Token::new(range(&name), TokenType::Attribute),
+            None => return,
+        },
+        kind::EXP_FIELD => {
+            let field = node.named_child(node.named_child_count().saturating_sub(1));
+            match field.filter(|field| kind::EXP != field.kind_id()) {
+                Some(field) => Token::new(range(&field), TokenType::Field),
+                None => return,
+            }
+        },
+        kind::INTERPOLATION => {
+            let delimiters = [node.child(0), node.child(node.child_count().saturating_sub(1))];
+            let delimiters = delimiters.iter().flatten().filter(|delimiter| !delimiter.is_named());
+            tokens.extend(delimiters.map(|delimiter| Token::new(range(delimiter), TokenType::Interpolation)));
+            return;
+        },
+        kind::NAME_ARG => {
+            let grandparent = node
+                .parent()
+                .and_then(|parent| parent.parent())
+                .map(|node| node.kind_id());
+            match (parent, grandparent) {
+                // relation arguments are the fields of its records
+                (Some(kind::ARG), Some(kind::REL_ARGS)) => {
+                    Token::new(range(&node), TokenType::Field).with_modifier(modifier::DECLARATION, true)
+                },
+                (Some(kind::ATOM_REC), _) => Token::new(range(&node), TokenType::Field),
+                _ => Token::new(range(&node), TokenType::Parameter).with_modifier(modifier::DECLARATION, true),
+            }
+        },
+        kind::NAME_FIELD => {
+            let declaration = Some(kind::FIELD) == parent;
+            Token::new(range(&node), TokenType::Field).with_modifier(modifier::DECLARATION, declaration)
+        },
+        kind::NAME_VAR_TYPE => Token::new(range(&node), TokenType::TypeParameter),
+        _ => match context::classify_dl(&node) {
+            Some((namespace, role)) => {
+                let declaration = Role::Declaration == role;
+                let mut token =
+                    Token::new(range(&node), namespace.into()).with_modifier(modifier::DECLARATION, declaration);
+                if Namespace::Relation == namespace {
+                    let name = content.utf8_text_for_tree_sitter_node(&node);
+                    token.relation = Some(ScopedName::parse(&name));
+                }
+                token
+            },
+            // transformer parameters
+            None if kind::NAME_TRANS == node.kind_id() => {
+                Token::new(range(&node), TokenType::Parameter).with_modifier(modifier::DECLARATION, true)
+            },
+            None if kind::NAME_VAR_TERM == node.kind_id() => {
+                let bound = is_bound(content, node, rule_bindings);
+                Token::new(range(&node), TokenType::Variable).with_modifier(modifier::DECLARATION, bound)
+            },
+            None => return,
+        },
+    };
+    tokens.push(token);
+}
+
+// Whether an occurrence of a variable binds it, i.e., it is declared with `var`, occurs within a
+// pattern, or is the first occurrence within an atom of the body of a rule.
+fn is_bound(content: &ropey::Rope, node: tree_sitter::Node, rule_bindings: &mut HashSet<(u32, String)>) -> bool {
+    if let Some(parent) = node.parent() {
+        let mut cursor = parent.walk();
+        let mut children = parent.children(&mut cursor);
+        if kind::EXP_DECL_VAR == parent.kind_id() && children.any(|child| keyword::VAR == child.kind_id()) {
+            return true;
+        }
+    }
+
+    let mut in_atom = false;
+    for ancestor in std::iter::successors(node.parent(), |node| node.parent()) {
+        match ancestor.kind_id() {
+            kind::PAT | kind::PAT_TERM_DECL_VAR => return true,
+            kind::ATOM => in_atom = true,
+            kind::RULE => {
+                let mut cursor = ancestor.walk();
+                let body_start = ancestor
+                    .children(&mut cursor)
+                    .find(|child| symbol::COLON_HYPHEN_MINUS == child.kind_id())
+                    .map(|arrow| arrow.end_byte());
+                let in_body = matches!(body_start, Some(start) if start <= node.start_byte());
+                let name = content.utf8_text_for_tree_sitter_node(&node).into_owned();
+                return in_body && in_atom && rule_bindings.insert((ancestor.start_byte(), name));
+            },
+            kind::ANNOTATED_ITEM => break,
+            _ => {},
+        }
+    }
+    false
+}
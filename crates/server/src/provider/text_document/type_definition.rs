@@ -0,0 +1,327 @@
+use crate::{
+    analysis::{
+        symbol::{
+            context::{self, Namespace},
+            resolve::{Declaration, Resolver},
+            ScopedName,
+        },
+        tree::ancestors,
+        types::{self, Member},
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::sync::Arc;
+
+// Where the type of the entity named at a position is written out.
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    // The types are written within the document itself.
+    Types(Vec<ScopedName>),
+    // The types are those of a member of a (relation or constructor) declaration.
+    Member(Namespace, ScopedName, Member),
+    // The type is the one declaring a constructor.
+    Constructor(ScopedName),
+}
+
+/// Compute "textDocument/typeDefinition" for a given document and position, i.e., the typedefs of
+/// the declared type of a variable, field, or relation argument (rather than its own definition).
+pub async fn type_definition(
+    session: Arc<crate::core::Session>,
+    params: lsp::request::GotoTypeDefinitionParams,
+) -> anyhow::Result<Option<lsp::request::GotoTypeDefinitionResponse>> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    if crate::core::Language::DDlogDl != session.get_text(uri).await?.language {
+        return Ok(None);
+    }
+
+    // Nodes can't be held across await points, so extract everything needed from the tree first.
+    let target = {
+        let text = session.get_text(uri).await?.clone();
+        let content = text.get_content().await?;
+        let tree = session
+            .get_tree(uri)
+            .await?
+            .clone()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+        let tree = tree.lock().await;
+        let position = content.lsp_position_to_core(params.text_document_position_params.position)?;
+        tree.root_node()
+            .named_descendant_for_byte_range(position.byte, position.byte)
+            .and_then(|node| ancestors(node).find(|node| NAME_KINDS.contains(&node.kind_id())))
+            .and_then(|name| {
+                let origin_selection_range = content.tree_sitter_range_to_lsp_range(name.range());
+                target(&content, name).map(|target| (target, origin_selection_range))
+            })
+    };
+    let (target, origin_selection_range) = match target {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+
+    let mut resolver = Resolver::new(&session);
+    let mut declarations = vec![];
+    match target {
+        Target::Types(names) => {
+            for name in names {
+                declarations.extend(resolver.resolve_name(uri, Namespace::Type, name).await);
+            }
+        },
+        Target::Member(namespace, name, member) => {
+            let kinds: &[u16] = match namespace {
+                Namespace::Constructor => &[dl::kind::CONS_REC],
+                _ => &[dl::kind::REL_ARGS, dl::kind::REL_ELEM],
+            };
+            for declaration in resolver.resolve_name(uri, namespace, name).await {
+                let names = declaration
+                    .with_node(&session, kinds, |content, node| {
+                        let node = types::member_type(content, node, &member);
+                        node.map(|node| types::type_names(content, node)).unwrap_or_default()
+                    })
+                    .await?
+                    .unwrap_or_default();
+                // the type names are written within the module of the declaration
+                for name in names {
+                    let resolved = resolver.resolve_name(&declaration.uri, Namespace::Type, name).await;
+                    declarations.extend(resolved);
+                }
+            }
+        },
+        Target::Constructor(name) => {
+            for declaration in resolver.resolve_name(uri, Namespace::Constructor, name).await {
+                let typedef = declaration
+                    .with_node(&session, &[dl::kind::TYPEDEF_NORMAL], |content, node| {
+                        let name = types::child_of_kind(node, &[dl::kind::NAME_TYPE])?;
+                        Some(Declaration {
+                            uri: declaration.uri.clone(),
+                            namespace: Namespace::Type,
+                            name: content.utf8_text_for_tree_sitter_node(&name).into(),
+                            range: content.tree_sitter_range_to_lsp_range(name.range()),
+                        })
+                    })
+                    .await?;
+                declarations.extend(typedef.flatten());
+            }
+        },
+    }
+
+    let mut links: Vec<lsp::LocationLink> = vec![];
+    for declaration in declarations {
+        let link = lsp::LocationLink {
+            origin_selection_range: Some(origin_selection_range),
+            target_uri: declaration.uri,
+            target_range: declaration.range,
+            target_selection_range: declaration.range,
+        };
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+    if links.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(lsp::request::GotoTypeDefinitionResponse::Link(links)))
+}
+
+// The kinds of the names whose type may be determined.
+const NAME_KINDS: &[u16] = &[
+    dl::kind::NAME_ARG,
+    dl::kind::NAME_CONS,
+    dl::kind::NAME_FIELD,
+    dl::kind::NAME_REL,
+    dl::kind::NAME_VAR_TERM,
+];
+
+// Determine where the type of a name is written out.
+fn target(content: &ropey::Rope, name: tree_sitter::Node) -> Option<Target> {
+    let parent = name.parent()?;
+    let text = content.utf8_text_for_tree_sitter_node(&name);
+    match (name.kind_id(), parent.kind_id()) {
+        // arguments (of relations, functions, or closures) and fields of constructors
+        (dl::kind::NAME_ARG, dl::kind::ARG | dl::kind::ARG_OPT_TYPE) | (dl::kind::NAME_FIELD, dl::kind::FIELD) => {
+            let node = types::child_of_kind(parent, &[dl::kind::TYPE_ATOM])?;
+            Some(Target::Types(types::type_names(content, node)))
+        },
+        // arguments of relations in atoms, i.e., `R(.arg = ..)`
+        (dl::kind::NAME_ARG, dl::kind::ATOM_REC) => {
+            let relation = types::child_of_kind(parent, &[dl::kind::NAME_REL])?;
+            let relation = ScopedName::parse(&content.utf8_text_for_tree_sitter_node(&relation));
+            Some(Target::Member(Namespace::Relation, relation, Member::Name(text.into())))
+        },
+        // fields of constructors in expressions and patterns, i.e., `C{.field = ..}`
+        (dl::kind::NAME_FIELD, dl::kind::EXP_CONS_REC | dl::kind::PAT_CONS_REC) => {
+            let constructor = types::child_of_kind(parent, &[dl::kind::NAME_CONS])?;
+            let constructor = ScopedName::parse(&content.utf8_text_for_tree_sitter_node(&constructor));
+            Some(Target::Member(
+                Namespace::Constructor,
+                constructor,
+                Member::Name(text.into()),
+            ))
+        },
+        (dl::kind::NAME_CONS, _) => match context::classify_dl(&name)? {
+            (Namespace::Constructor, _) => Some(Target::Constructor(ScopedName::parse(&text))),
+            _ => None,
+        },
+        // relations declared as `relation R[T]` (or referred to as such) have elements of type `T`
+        (dl::kind::NAME_REL, _) => match context::classify_dl(&name)? {
+            (Namespace::Relation, _) => Some(Target::Member(
+                Namespace::Relation,
+                ScopedName::parse(&text),
+                Member::Element,
+            )),
+            _ => None,
+        },
+        (dl::kind::NAME_VAR_TERM, _) if context::classify_dl(&name).is_none() => variable(content, name, &text),
+        _ => None,
+    }
+}
+
+// Determine where the type of a variable is written out, i.e., the argument of its enclosing
+// function, the relation argument it is first bound to in its enclosing rule, or its type
+// ascription.
+fn variable(content: &ropey::Rope, name: tree_sitter::Node, text: &str) -> Option<Target> {
+    for ancestor in ancestors(name) {
+        match ancestor.kind_id() {
+            dl::kind::FUNCTION_NORMAL => {
+                let member = Member::Name(text.into());
+                if let Some(node) = types::member_type(content, ancestor, &member) {
+                    return Some(Target::Types(types::type_names(content, node)));
+                }
+                return binding(content, ancestor, text);
+            },
+            dl::kind::RULE => return binding(content, ancestor, text),
+            dl::kind::ANNOTATED_ITEM => return None,
+            _ => {},
+        }
+    }
+    None
+}
+
+// Find the first binding (in order of appearance) of a variable within an item which determines
+// its type, i.e., an argument of an atom or an expression with a type ascription.
+fn binding(content: &ropey::Rope, item: tree_sitter::Node, text: &str) -> Option<Target> {
+    let mut work = vec![item];
+    while let Some(node) = work.pop() {
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+        work.extend(children.into_iter().rev());
+
+        if dl::kind::NAME_VAR_TERM != node.kind_id() || text != content.utf8_text_for_tree_sitter_node(&node) {
+            continue;
+        }
+        // the variable must make up the whole expression, i.e., `EXP > EXP_DECL_VAR > NAME_VAR_TERM`
+        let exp = node
+            .parent()
+            .filter(|parent| dl::kind::EXP_DECL_VAR == parent.kind_id())
+            .and_then(|parent| parent.parent())
+            .filter(|parent| dl::kind::EXP == parent.kind_id());
+        let (exp, parent) = match exp.and_then(|exp| exp.parent().map(|parent| (exp, parent))) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let relation = || {
+            let relation = types::child_of_kind(parent, &[dl::kind::NAME_REL])?;
+            Some(ScopedName::parse(&content.utf8_text_for_tree_sitter_node(&relation)))
+        };
+        let target = match parent.kind_id() {
+            dl::kind::ATOM_POS => {
+                let mut cursor = parent.walk();
+                let mut arguments = parent
+                    .named_children(&mut cursor)
+                    .filter(|child| dl::kind::EXP == child.kind_id());
+                let position = arguments.position(|argument| argument == exp);
+                position.and_then(|position| {
+                    Some(Target::Member(
+                        Namespace::Relation,
+                        relation()?,
+                        Member::Position(position),
+                    ))
+                })
+            },
+            dl::kind::ATOM_REC => {
+                let argument = exp
+                    .prev_named_sibling()
+                    .filter(|sibling| dl::kind::NAME_ARG == sibling.kind_id());
+                argument.and_then(|argument| {
+                    let argument = content.utf8_text_for_tree_sitter_node(&argument);
+                    Some(Target::Member(
+                        Namespace::Relation,
+                        relation()?,
+                        Member::Name(argument.into()),
+                    ))
+                })
+            },
+            dl::kind::ATOM_ELEM => {
+                relation().map(|relation| Target::Member(Namespace::Relation, relation, Member::Element))
+            },
+            dl::kind::EXP_TYPE => types::child_of_kind(parent, &[dl::kind::TYPE_ATOM])
+                .map(|node| Target::Types(types::type_names(content, node))),
+            _ => None,
+        };
+        if target.is_some() {
+            return target;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Determine the target of the name at the start of the first occurrence of `at` in the text.
+    fn target_at(text: &str, at: &str) -> Option<Target> {
+        let mut parser = tree_sitter::Parser::try_from(crate::core::Language::DDlogDl).unwrap();
+        let tree = parser.parse(text, None).unwrap().unwrap();
+        let content = ropey::Rope::from(text);
+        let byte = text.find(at).unwrap() as u32;
+        let node = tree.root_node().named_descendant_for_byte_range(byte, byte)?;
+        let name = ancestors(node).find(|node| NAME_KINDS.contains(&node.kind_id()))?;
+        target(&content, name)
+    }
+
+    fn name(text: &str) -> ScopedName {
+        ScopedName::parse(text)
+    }
+
+    #[test]
+    fn arguments_have_their_declared_types() {
+        let text = "input relation R(a: m::T)\n";
+        assert_eq!(Some(Target::Types(vec![name("m::T")])), target_at(text, "a:"));
+        let text = "function f(v: T): T { v }\n";
+        assert_eq!(Some(Target::Types(vec![name("T")])), target_at(text, "v }"));
+    }
+
+    #[test]
+    fn named_arguments_of_atoms_are_members_of_their_relation() {
+        let text = "R(x) :- S(.b = x).\n";
+        let expected = Target::Member(Namespace::Relation, name("S"), Member::Name("b".into()));
+        assert_eq!(Some(expected), target_at(text, "b ="));
+    }
+
+    #[test]
+    fn constructors_have_the_type_declaring_them() {
+        let text = "function f(): T { C{.a = 1} }\n";
+        assert_eq!(Some(Target::Constructor(name("C"))), target_at(text, "C{"));
+    }
+
+    #[test]
+    fn variables_have_the_type_of_their_first_binding() {
+        // the head doesn't bind `x` itself, since its argument is not just the variable
+        let text = "R(x + 1) :- S(y, x), U(x).\n";
+        let expected = Target::Member(Namespace::Relation, name("S"), Member::Position(1));
+        assert_eq!(Some(expected), target_at(text, "x), U"));
+
+        let text = "R(0) :- E[x].\n";
+        let expected = Target::Member(Namespace::Relation, name("E"), Member::Element);
+        assert_eq!(Some(expected), target_at(text, "x]"));
+
+        let text = "R(0) :- S(.a = x).\n";
+        let expected = Target::Member(Namespace::Relation, name("S"), Member::Name("a".into()));
+        assert_eq!(Some(expected), target_at(text, "x)"));
+
+        // variables without a binding which determines their type have none
+        assert_eq!(None, target_at("R(0) :- var x = 1.\n", "x ="));
+    }
+}
@@ -0,0 +1,307 @@
+//! Type hierarchy over typedefs, where the subtypes of a (union) typedef are its constructors, and
+//! the subtypes of a record constructor are the types of its fields.
+//!
+//! The requests of the type hierarchy (LSP 3.17) aren't known to the versions of `lsp-types` and
+//! `lspower` used here, so they are handled as custom requests (see `server::request`), and the
+//! capability is advertised as `experimental.typeHierarchyProvider` (rather than as
+//! `typeHierarchyProvider`) in the server capabilities. Clients have to opt in accordingly. The
+//! requests follow the shapes of the specification otherwise:
+//!
+//! - `textDocument/prepareTypeHierarchy` takes `TextDocumentPositionParams` and returns the items
+//!   of the typedef or constructor at the position;
+//! - `typeHierarchy/supertypes` and `typeHierarchy/subtypes` take `{ "item": TypeHierarchyItem }`
+//!   and return the related items.
+//!
+//! Items have the same shape as call hierarchy items, which are used to represent them. Their
+//! `kind` is `Constructor` for constructors and `Class` for typedefs, and is used (along with their
+//! `uri`, `name`, and `selectionRange`) to identify the declaration when the item is sent back, so
+//! clients must return items unchanged.
+
+use crate::{
+    analysis::{
+        symbol::{
+            context::Namespace,
+            resolve::{Declaration, Resolver},
+        },
+        tree::ancestors,
+        types,
+    },
+    core::language::dl,
+};
+use lsp_text::RopeExt;
+use std::sync::Arc;
+
+/// Compute "textDocument/prepareTypeHierarchy" for a given document and position.
+pub async fn prepare_type_hierarchy(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    let params: lsp::TextDocumentPositionParams = serde_json::from_value(params.unwrap_or_default())?;
+    let uri = &params.text_document.uri;
+
+    let mut resolver = Resolver::new(&session);
+    let index = resolver
+        .index(uri)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("could not index document for uri: {:#?}", uri))?;
+    let occurrence = match index.occurrence_at(params.position) {
+        Some(occurrence) if [Namespace::Constructor, Namespace::Type].contains(&occurrence.namespace) => {
+            occurrence.clone()
+        },
+        _ => return Ok(serde_json::Value::Null),
+    };
+
+    let mut items = vec![];
+    for declaration in resolver.resolve(uri, &occurrence).await {
+        items.push(item(&session, &declaration).await?);
+    }
+    if items.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    Ok(serde_json::to_value(items)?)
+}
+
+/// Compute "typeHierarchy/supertypes" for a given item, i.e., the typedef declaring a constructor,
+/// or the constructors with a field of a given type.
+pub async fn supertypes(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    let declaration = declaration(params)?;
+    let mut supertypes = vec![];
+    match declaration.namespace {
+        Namespace::Constructor => {
+            let typedef = declaration
+                .with_node(&session, &[dl::kind::TYPEDEF_NORMAL], |content, node| {
+                    let name = types::child_of_kind(node, &[dl::kind::NAME_TYPE])?;
+                    Some(named(&declaration.uri, Namespace::Type, content, name))
+                })
+                .await?;
+            supertypes.extend(typedef.flatten());
+        },
+        _ => {
+            let mut resolver = Resolver::new(&session);
+            for (uri, reference) in resolver.references(&[declaration], false).await {
+                let text = session.get_text(&uri).await?.clone();
+                let content = text.get_content().await?;
+                let tree = session
+                    .get_tree(&uri)
+                    .await?
+                    .clone()
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("could not open tree for uri: {:#?}", uri))?;
+                let tree = tree.lock().await;
+                let range = content.lsp_range_to_tree_sitter_range(reference.range)?;
+                // the reference must be within the type of a field, i.e., `C{.., field: T, ..}`
+                let constructor = tree
+                    .root_node()
+                    .named_descendant_for_byte_range(range.start_byte(), range.end_byte())
+                    .and_then(|node| ancestors(node).find(is_field))
+                    .and_then(|field| field.parent())
+                    .filter(|parent| dl::kind::CONS_REC == parent.kind_id())
+                    .and_then(|constructor| types::child_of_kind(constructor, &[dl::kind::NAME_CONS]))
+                    .map(|name| named(&uri, Namespace::Constructor, &content, name));
+                if let Some(constructor) = constructor {
+                    if !supertypes.contains(&constructor) {
+                        supertypes.push(constructor);
+                    }
+                }
+            }
+        },
+    }
+    items(&session, supertypes).await
+}
+
+/// Compute "typeHierarchy/subtypes" for a given item, i.e., the constructors of a (union) typedef,
+/// or the types of the fields of a record constructor.
+pub async fn subtypes(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    let declaration = declaration(params)?;
+    let mut subtypes = vec![];
+    match declaration.namespace {
+        Namespace::Constructor => {
+            let names = declaration
+                .with_node(&session, &[dl::kind::CONS_REC], |content, node| {
+                    let mut cursor = node.walk();
+                    let fields = node.named_children(&mut cursor).filter(is_field);
+                    let fields = fields.filter_map(|field| types::child_of_kind(field, &[dl::kind::TYPE_ATOM]));
+                    fields
+                        .flat_map(|node| types::type_names(content, node))
+                        .collect::<Vec<_>>()
+                })
+                .await?
+                .unwrap_or_default();
+            // the field types are written within the module of the constructor
+            let mut resolver = Resolver::new(&session);
+            for name in names {
+                for subtype in resolver.resolve_name(&declaration.uri, Namespace::Type, name).await {
+                    if !subtypes.contains(&subtype) {
+                        subtypes.push(subtype);
+                    }
+                }
+            }
+        },
+        _ => {
+            let constructors = declaration
+                .with_node(&session, &[dl::kind::TYPEDEF_NORMAL], |content, node| {
+                    let union = types::child_of_kind(node, &[dl::kind::TYPE])
+                        .and_then(|node| types::child_of_kind(node, &[dl::kind::TYPE_UNION]));
+                    let union = match union {
+                        Some(union) => union,
+                        None => return vec![],
+                    };
+                    let mut cursor = union.walk();
+                    let constructors = union
+                        .named_children(&mut cursor)
+                        .filter(|child| dl::kind::CONS == child.kind_id())
+                        .filter_map(|node| types::child_of_kind(node, &[dl::kind::CONS_POS, dl::kind::CONS_REC]))
+                        .filter_map(|node| types::child_of_kind(node, &[dl::kind::NAME_CONS]));
+                    constructors
+                        .map(|name| named(&declaration.uri, Namespace::Constructor, content, name))
+                        .collect::<Vec<_>>()
+                })
+                .await?;
+            subtypes.extend(constructors.unwrap_or_default());
+        },
+    }
+    items(&session, subtypes).await
+}
+
+fn is_field(node: &tree_sitter::Node) -> bool {
+    dl::kind::FIELD == node.kind_id()
+}
+
+// Construct the declaration for a `NAME_*` node.
+fn named(uri: &lsp::Url, namespace: Namespace, content: &ropey::Rope, name: tree_sitter::Node) -> Declaration {
+    Declaration {
+        uri: uri.clone(),
+        namespace,
+        name: content.utf8_text_for_tree_sitter_node(&name).into(),
+        range: content.tree_sitter_range_to_lsp_range(name.range()),
+    }
+}
+
+async fn items(session: &crate::core::Session, declarations: Vec<Declaration>) -> anyhow::Result<serde_json::Value> {
+    let mut items = vec![];
+    for declaration in declarations {
+        items.push(item(session, &declaration).await?);
+    }
+    Ok(serde_json::to_value(items)?)
+}
+
+// Construct the type hierarchy item for a declaration.
+async fn item(session: &crate::core::Session, declaration: &Declaration) -> anyhow::Result<lsp::CallHierarchyItem> {
+    let (kind, kinds): (lsp::SymbolKind, &[u16]) = match declaration.namespace {
+        Namespace::Constructor => (lsp::SymbolKind::CONSTRUCTOR, &[dl::kind::CONS_POS, dl::kind::CONS_REC]),
+        _ => (lsp::SymbolKind::CLASS, &[
+            dl::kind::TYPEDEF_EXTERN,
+            dl::kind::TYPEDEF_NORMAL,
+        ]),
+    };
+    let range = declaration
+        .with_node(session, kinds, |content, node| {
+            content.tree_sitter_range_to_lsp_range(node.range())
+        })
+        .await?;
+    Ok(lsp::CallHierarchyItem {
+        name: declaration.name.clone(),
+        kind,
+        tags: Default::default(),
+        detail: Default::default(),
+        uri: declaration.uri.clone(),
+        range: range.unwrap_or(declaration.range),
+        selection_range: declaration.range,
+        data: Default::default(),
+    })
+}
+
+// Recover the declaration from the item of the request parameters (see `item`).
+fn declaration(params: Option<serde_json::Value>) -> anyhow::Result<Declaration> {
+    let item = params
+        .and_then(|mut params| params.get_mut("item").map(serde_json::Value::take))
+        .unwrap_or_default();
+    let item: lsp::CallHierarchyItem = serde_json::from_value(item)?;
+    let namespace = match item.kind {
+        lsp::SymbolKind::CONSTRUCTOR => Namespace::Constructor,
+        _ => Namespace::Type,
+    };
+    Ok(Declaration {
+        uri: item.uri,
+        namespace,
+        name: item.name,
+        range: item.selection_range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Session;
+
+    const TEXT: &str = "typedef Shape = Circle{r: Length} | Square{side: u32}\ntypedef Length = u32\n";
+
+    fn range(line: u32, start: u32, end: u32) -> lsp::Range {
+        lsp::Range::new(lsp::Position::new(line, start), lsp::Position::new(line, end))
+    }
+
+    // The item of the typedef or constructor named at the position.
+    async fn prepare(session: &Arc<Session>, uri: &lsp::Url, line: u32, character: u32) -> serde_json::Value {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        });
+        let items = prepare_type_hierarchy(session.clone(), Some(params)).await.unwrap();
+        items[0].clone()
+    }
+
+    // The names and selection ranges of the items of a response.
+    fn named(items: serde_json::Value) -> Vec<(String, lsp::Range)> {
+        let items: Vec<lsp::CallHierarchyItem> = serde_json::from_value(items).unwrap();
+        let items = items.into_iter();
+        items.map(|item| (item.name, item.selection_range)).collect()
+    }
+
+    #[tokio::test]
+    async fn constructors_are_subtypes_of_their_typedef() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let uri = session.insert_text("/program/main.dl", TEXT).await;
+
+        let shape = prepare(&session, &uri, 0, 8).await;
+        let params = Some(serde_json::json!({ "item": shape }));
+        let expected = vec![
+            (String::from("Circle"), range(0, 16, 22)),
+            (String::from("Square"), range(0, 36, 42)),
+        ];
+        assert_eq!(expected, named(subtypes(session.clone(), params).await.unwrap()));
+
+        let circle = prepare(&session, &uri, 0, 16).await;
+        let params = Some(serde_json::json!({ "item": circle }));
+        let expected = vec![(String::from("Shape"), range(0, 8, 13))];
+        assert_eq!(expected, named(supertypes(session.clone(), params).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn field_types_are_subtypes_of_their_constructor() {
+        let session = Arc::new(Session::new(None).unwrap());
+        let uri = session.insert_text("/program/main.dl", TEXT).await;
+
+        let circle = prepare(&session, &uri, 0, 16).await;
+        let params = Some(serde_json::json!({ "item": circle }));
+        let expected = vec![(String::from("Length"), range(1, 8, 14))];
+        assert_eq!(expected, named(subtypes(session.clone(), params).await.unwrap()));
+        // primitive types aren't declared anywhere
+        let square = prepare(&session, &uri, 0, 36).await;
+        let params = Some(serde_json::json!({ "item": square }));
+        assert_eq!(
+            Vec::<(String, lsp::Range)>::new(),
+            named(subtypes(session.clone(), params).await.unwrap())
+        );
+
+        let length = prepare(&session, &uri, 1, 8).await;
+        let params = Some(serde_json::json!({ "item": length }));
+        let expected = vec![(String::from("Circle"), range(0, 16, 22))];
+        assert_eq!(expected, named(supertypes(session.clone(), params).await.unwrap()));
+    }
+}
@@ -1,10 +1,11 @@
+use crate::analysis::{dependency::DependencyGraph, imports::ImportGraph, symbol::resolve::Resolver};
 use std::{borrow::Borrow, sync::Arc};
 
 pub async fn did_change_workspace_folders(
     session: Arc<crate::core::Session>,
     params: lsp::DidChangeWorkspaceFoldersParams,
 ) -> anyhow::Result<()> {
-    session.remove_workspace_folders(params.event.removed);
+    session.remove_workspace_folders(params.event.removed).await?;
     session.insert_workspace_folders(params.event.added).await?;
     Ok(())
 }
@@ -30,3 +31,43 @@ pub async fn symbol(
     }
     Ok(Some(results))
 }
+
+/// Compute the custom "ddlog/dependencyGraph" request, i.e., the relation dependency graph of the
+/// program of a given document (or of every known module if no document is given).
+pub async fn dependency_graph(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    let uri = params
+        .as_ref()
+        .and_then(|params| params.pointer("/textDocument/uri"))
+        .and_then(serde_json::Value::as_str)
+        .map(lsp::Url::parse)
+        .transpose()?;
+    // ".dat" documents are replaced by their associated program (if any)
+    let main = match uri {
+        Some(uri) if uri.path().ends_with(".dat") => session.program(&uri).await,
+        uri => uri,
+    };
+    let graph = match main {
+        Some(main) => DependencyGraph::of_program(&session, &main).await?,
+        None => DependencyGraph::of_modules(&session, &session.document_uris()).await?,
+    };
+    Ok(graph.to_json())
+}
+
+/// Compute the custom "ddlog/importGraph" request, i.e., the imports between every known module.
+/// If a document is given, only its imports and the imports of it (by other modules) are included.
+pub async fn import_graph(
+    session: Arc<crate::core::Session>,
+    params: Option<serde_json::Value>,
+) -> anyhow::Result<serde_json::Value> {
+    let uri = params
+        .as_ref()
+        .and_then(|params| params.pointer("/textDocument/uri"))
+        .and_then(serde_json::Value::as_str)
+        .map(lsp::Url::parse)
+        .transpose()?;
+    let graph = ImportGraph::of_modules(&mut Resolver::new(&session), &session.document_uris()).await;
+    Ok(graph.to_json(uri.as_ref()))
+}
@@ -1,6 +1,20 @@
 use lspower::jsonrpc;
 use std::sync::Arc;
 
+/// The custom requests handled by the server (including those not yet supported by `lspower`).
+pub mod request {
+    /// Compute the relation dependency graph of a program, as `{ "nodes": [..], "edges": [..] }`.
+    pub const DEPENDENCY_GRAPH: &str = "ddlog/dependencyGraph";
+    /// Compute the module import graph of the workspace, as `{ "nodes": [..], "edges": [..] }`.
+    pub const IMPORT_GRAPH: &str = "ddlog/importGraph";
+    /// Prepare the type hierarchy (of typedefs and their constructors) at a position.
+    pub const PREPARE_TYPE_HIERARCHY: &str = "textDocument/prepareTypeHierarchy";
+    /// Compute the subtypes of a type hierarchy item.
+    pub const TYPE_HIERARCHY_SUBTYPES: &str = "typeHierarchy/subtypes";
+    /// Compute the supertypes of a type hierarchy item.
+    pub const TYPE_HIERARCHY_SUPERTYPES: &str = "typeHierarchy/supertypes";
+}
+
 pub struct Server {
     pub client: lspower::Client,
     pub session: Arc<crate::core::Session>,
@@ -14,16 +28,51 @@ impl Server {
 }
 
 pub fn capabilities() -> lsp::ServerCapabilities {
+    let call_hierarchy_provider = Some(lsp::CallHierarchyServerCapability::Simple(true));
+
+    let completion_provider = Some(lsp::CompletionOptions {
+        trigger_characters: Some(vec![String::from("."), String::from(":")]),
+        ..Default::default()
+    });
+
     let definition_provider = Some(lsp::OneOf::Right(lsp::DefinitionOptions {
         work_done_progress_options: Default::default(),
     }));
 
+    let document_formatting_provider = Some(lsp::OneOf::Left(true));
+
+    let document_range_formatting_provider = Some(lsp::OneOf::Left(true));
+
     let document_symbol_provider = Some(lsp::OneOf::Left(true));
 
+    let hover_provider = Some(lsp::HoverProviderCapability::Simple(true));
+
+    let references_provider = Some(lsp::OneOf::Left(true));
+
+    let rename_provider = Some(lsp::OneOf::Right(lsp::RenameOptions {
+        prepare_provider: Some(true),
+        work_done_progress_options: Default::default(),
+    }));
+
+    let semantic_tokens_provider = {
+        let options = lsp::SemanticTokensOptions {
+            legend: crate::provider::text_document::semantic_tokens_legend(),
+            full: Some(lsp::SemanticTokensFullOptions::Delta { delta: Some(true) }),
+            ..Default::default()
+        };
+        Some(lsp::SemanticTokensServerCapabilities::SemanticTokensOptions(options))
+    };
+
+    let type_definition_provider = Some(lsp::TypeDefinitionProviderCapability::Simple(true));
+
+    // the type hierarchy requests are handled as custom requests (see `request`)
+    let experimental = Some(serde_json::json!({ "typeHierarchyProvider": true }));
+
     let text_document_sync = {
         let options = lsp::TextDocumentSyncOptions {
             open_close: Some(true),
             change: Some(lsp::TextDocumentSyncKind::INCREMENTAL),
+            save: Some(lsp::TextDocumentSyncSaveOptions::Supported(true)),
             ..Default::default()
         };
         Some(lsp::TextDocumentSyncCapability::Options(options))
@@ -49,10 +98,20 @@ pub fn capabilities() -> lsp::ServerCapabilities {
 
     lsp::ServerCapabilities {
         text_document_sync,
+        call_hierarchy_provider,
+        completion_provider,
         definition_provider,
+        document_formatting_provider,
+        document_range_formatting_provider,
         document_symbol_provider,
+        hover_provider,
+        references_provider,
+        rename_provider,
+        semantic_tokens_provider,
+        type_definition_provider,
         workspace,
         workspace_symbol_provider,
+        experimental,
         ..Default::default()
     }
 }
@@ -61,6 +120,9 @@ pub fn capabilities() -> lsp::ServerCapabilities {
 impl lspower::LanguageServer for Server {
     async fn initialize(&self, params: lsp::InitializeParams) -> jsonrpc::Result<lsp::InitializeResult> {
         *self.session.client_capabilities.write().await = Some(params.capabilities);
+        if let Some(options) = &params.initialization_options {
+            *self.session.config.write().await = crate::core::Config::from_json(options);
+        }
         if let Some(workspace_folders) = params.workspace_folders {
             self.session
                 .insert_workspace_folders(workspace_folders)
@@ -78,6 +140,11 @@ impl lspower::LanguageServer for Server {
         let typ = lsp::MessageType::INFO;
         let message = "DDlog language server initialized!";
         self.client.log_message(typ, message).await;
+        let session = self.session.clone();
+        if let Err(error) = crate::handler::workspace::watch_files(session).await {
+            let message = format!("failed to watch the workspace folders: {:#}", error);
+            self.client.log_message(lsp::MessageType::ERROR, message).await;
+        }
     }
 
     async fn shutdown(&self) -> jsonrpc::Result<()> {
@@ -105,6 +172,26 @@ impl lspower::LanguageServer for Server {
             .unwrap()
     }
 
+    async fn did_save(&self, params: lsp::DidSaveTextDocumentParams) {
+        let session = self.session.clone();
+        crate::handler::text_document::did_save(session, params).await.unwrap()
+    }
+
+    async fn did_change_configuration(&self, params: lsp::DidChangeConfigurationParams) {
+        let session = self.session.clone();
+        let result = crate::handler::workspace::did_change_configuration(session, params).await;
+        result.unwrap();
+    }
+
+    async fn did_change_watched_files(&self, params: lsp::DidChangeWatchedFilesParams) {
+        let session = self.session.clone();
+        let result = crate::handler::workspace::did_change_watched_files(session, params).await;
+        if let Err(error) = result {
+            let message = format!("failed to update changed documents: {:#}", error);
+            self.client.log_message(lsp::MessageType::ERROR, message).await;
+        }
+    }
+
     async fn did_change_workspace_folders(&self, params: lsp::DidChangeWorkspaceFoldersParams) {
         let session = self.session.clone();
         let result = crate::handler::workspace::did_change_workspace_folders(session, params).await;
@@ -125,6 +212,127 @@ impl lspower::LanguageServer for Server {
         Ok(result.map_err(crate::core::IntoJsonRpcError)?)
     }
 
+    async fn completion(&self, params: lsp::CompletionParams) -> jsonrpc::Result<Option<lsp::CompletionResponse>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::completion(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn goto_type_definition(
+        &self,
+        params: lsp::request::GotoTypeDefinitionParams,
+    ) -> jsonrpc::Result<Option<lsp::request::GotoTypeDefinitionResponse>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::type_definition(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn formatting(&self, params: lsp::DocumentFormattingParams) -> jsonrpc::Result<Option<Vec<lsp::TextEdit>>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::formatting(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn range_formatting(
+        &self,
+        params: lsp::DocumentRangeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp::TextEdit>>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::range_formatting(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn hover(&self, params: lsp::HoverParams) -> jsonrpc::Result<Option<lsp::Hover>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::hover(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn references(&self, params: lsp::ReferenceParams) -> jsonrpc::Result<Option<Vec<lsp::Location>>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::references(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: lsp::CallHierarchyPrepareParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp::CallHierarchyItem>>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::prepare_call_hierarchy(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: lsp::CallHierarchyIncomingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp::CallHierarchyIncomingCall>>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::incoming_calls(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: lsp::CallHierarchyOutgoingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp::CallHierarchyOutgoingCall>>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::outgoing_calls(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: lsp::TextDocumentPositionParams,
+    ) -> jsonrpc::Result<Option<lsp::PrepareRenameResponse>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::prepare_rename(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn rename(&self, params: lsp::RenameParams) -> jsonrpc::Result<Option<lsp::WorkspaceEdit>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::rename(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: lsp::SemanticTokensParams,
+    ) -> jsonrpc::Result<Option<lsp::SemanticTokensResult>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::semantic_tokens_full(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: lsp::SemanticTokensDeltaParams,
+    ) -> jsonrpc::Result<Option<lsp::SemanticTokensFullDeltaResult>> {
+        let session = self.session.clone();
+        let result = crate::handler::text_document::semantic_tokens_full_delta(session, params).await;
+        Ok(result.map_err(crate::core::IntoJsonRpcError)?)
+    }
+
+    async fn request_else(
+        &self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> jsonrpc::Result<Option<serde_json::Value>> {
+        let session = self.session.clone();
+        let result = match method {
+            request::DEPENDENCY_GRAPH => crate::handler::workspace::dependency_graph(session, params).await,
+            request::IMPORT_GRAPH => crate::handler::workspace::import_graph(session, params).await,
+            request::PREPARE_TYPE_HIERARCHY => {
+                crate::handler::text_document::prepare_type_hierarchy(session, params).await
+            },
+            request::TYPE_HIERARCHY_SUBTYPES => crate::handler::text_document::subtypes(session, params).await,
+            request::TYPE_HIERARCHY_SUPERTYPES => crate::handler::text_document::supertypes(session, params).await,
+            _ => return Err(jsonrpc::Error::method_not_found()),
+        };
+        Ok(result.map(Some).map_err(crate::core::IntoJsonRpcError)?)
+    }
+
     async fn symbol(&self, params: lsp::WorkspaceSymbolParams) -> jsonrpc::Result<Option<Vec<lsp::SymbolInformation>>> {
         let session = self.session.clone();
         let result = crate::handler::workspace::symbol(session, params).await;
@@ -1,6 +1,8 @@
 use ddlog_lsp_languages::language::Language;
 use lsp_text::RopeExt;
 
+pub mod message;
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, PartialEq)]
 pub struct SyntaxError<D> {
@@ -8,6 +10,9 @@ pub struct SyntaxError<D> {
     range: tree_sitter::Range,
     r#type: SyntaxErrorType,
     data: D,
+    /// The kind and range of the top-level item the error occurred within (if known). This is
+    /// boxed since errors are returned (and propagated) throughout the visitors.
+    item: Option<Box<(u16, tree_sitter::Range)>>,
 }
 
 #[allow(missing_docs)]
@@ -19,6 +24,7 @@ impl<D> SyntaxError<D> {
             range,
             r#type,
             data,
+            item: None,
         }
     }
 
@@ -29,6 +35,7 @@ impl<D> SyntaxError<D> {
             range,
             r#type,
             data,
+            item: None,
         }
     }
 
@@ -39,6 +46,7 @@ impl<D> SyntaxError<D> {
             range,
             r#type,
             data,
+            item: None,
         }
     }
 
@@ -59,6 +67,7 @@ impl<D> SyntaxError<D> {
             range,
             r#type,
             data,
+            item: None,
         }
     }
 
@@ -69,95 +78,101 @@ impl<D> SyntaxError<D> {
             range,
             r#type,
             data,
+            item: None,
         }
     }
 
-    #[allow(clippy::field_reassign_with_default)]
-    pub fn to_lsp_diagnostic(&self, uri: &lsp::Url, content: &ropey::Rope) -> lsp::Diagnostic {
-        let range = content.tree_sitter_range_to_lsp_range(self.range);
-        let severity = Some(lsp::DiagnosticSeverity::ERROR);
+    pub fn unexpected_node_error(language: Language, range: tree_sitter::Range, data: D) -> Self {
+        let r#type = SyntaxErrorType::UnexpectedNodeError;
+        Self {
+            language,
+            range,
+            r#type,
+            data,
+            item: None,
+        }
+    }
+
+    /// Record the top-level item (by the kind of its declaration and its range) the error
+    /// occurred within, which is reported as related information.
+    pub fn with_item(mut self, kind: u16, range: tree_sitter::Range) -> Self {
+        self.item = Some(Box::new((kind, range)));
+        self
+    }
+
+    /// Render the error as a message describing the problem in terms of the language.
+    pub fn message(&self, content: &ropey::Rope) -> String {
+        let found = message::quote_text(content, self.range);
+        let found = found.unwrap_or_else(|| String::from("the end of the input"));
         match &self.r#type {
             SyntaxErrorType::ChoiceError { language, choices } => {
-                let language: tree_sitter::Language = (*language).into();
-                let choices = choices
-                    .iter()
-                    .map(|id| language.node_kind_for_id(*id).unwrap().into_owned())
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                let mut diagnostic = lsp::Diagnostic::default();
-                diagnostic.message = format!("syntax error: expected one of [{}]", choices);
-                diagnostic.range = range;
-                diagnostic.severity = severity;
-                diagnostic
-            },
-            SyntaxErrorType::WalkerDoneEarlyError => {
-                let mut diagnostic = lsp::Diagnostic::default();
-                diagnostic.message = String::from("syntax error: internal error (parsing terminated too early)");
-                diagnostic.range = range;
-                diagnostic.severity = severity;
-                diagnostic
+                let expected = message::describe_choices(*language, choices);
+                format!("syntax error: expected {}, found {}", expected, found)
             },
-            SyntaxErrorType::WalkerMoveError => {
-                let mut diagnostic = lsp::Diagnostic::default();
-                diagnostic.message = String::from("syntax error: internal error (failed to move to next node)");
-                diagnostic.range = range;
-                diagnostic.severity = severity;
-                diagnostic
+            SyntaxErrorType::WalkerDoneEarlyError | SyntaxErrorType::WalkerMoveError => {
+                String::from("syntax error: unexpected end of the input")
             },
             SyntaxErrorType::NodeMismatchError {
-                language,
-                dest_kind,
-                want_kind,
+                language, want_kind, ..
             } => {
-                let language: tree_sitter::Language = (*language).into();
-                let mut diagnostic = lsp::Diagnostic::default();
-                diagnostic.message = String::from("syntax node mismatch error");
-                diagnostic.range = range;
-                diagnostic.severity = severity;
-                diagnostic.related_information = {
-                    let dest_info = {
-                        let location = {
-                            let uri = uri.clone();
-                            let range = range;
-                            lsp::Location { uri, range }
-                        };
-                        let message = format!("found: {}", language.node_kind_for_id(*dest_kind).unwrap());
-                        lsp::DiagnosticRelatedInformation { location, message }
-                    };
-                    let want_info = {
-                        let location = {
-                            let uri = uri.clone();
-                            let range = range;
-                            lsp::Location { uri, range }
-                        };
-                        let message = format!("expected: {}", language.node_kind_for_id(*want_kind).unwrap());
-                        lsp::DiagnosticRelatedInformation { location, message }
-                    };
-                    Some(vec![dest_info, want_info])
-                };
-                diagnostic
+                let expected = message::describe_kind(*language, *want_kind);
+                format!("syntax error: expected {}, found {}", expected, found)
             },
             SyntaxErrorType::NodeMissingError { language, want_kind } => {
-                let language: tree_sitter::Language = (*language).into();
-                let mut diagnostic = lsp::Diagnostic::default();
-                diagnostic.message = String::from("SyntaxErrorType::NodeMissingError");
-                diagnostic.range = range;
-                diagnostic.severity = severity;
-                diagnostic.related_information = {
-                    let want_info = {
-                        let location = {
-                            let uri = uri.clone();
-                            let range = range;
-                            lsp::Location { uri, range }
-                        };
-                        let message = format!("expected: {}", language.node_kind_for_id(*want_kind).unwrap());
-                        lsp::DiagnosticRelatedInformation { location, message }
-                    };
-                    Some(vec![want_info])
-                };
-                diagnostic
+                let expected = message::describe_kind(*language, *want_kind);
+                format!("syntax error: missing {}", expected)
             },
+            SyntaxErrorType::UnexpectedNodeError => format!("syntax error: unexpected {}", found),
+        }
+    }
+
+    pub fn to_lsp_diagnostic(&self, uri: &lsp::Url, content: &ropey::Rope) -> lsp::Diagnostic {
+        let range = content.tree_sitter_range_to_lsp_range(self.range);
+        let severity = Some(lsp::DiagnosticSeverity::ERROR);
+        let message = self.message(content);
+        let related_information = self.item.as_deref().map(|&(kind, item_range)| {
+            let location = {
+                let uri = uri.clone();
+                let range = content.tree_sitter_range_to_lsp_range(item_range);
+                lsp::Location { uri, range }
+            };
+            let message = format!("while parsing {}", message::describe_kind(self.language, kind));
+            vec![lsp::DiagnosticRelatedInformation { location, message }]
+        });
+        lsp::Diagnostic {
+            range,
+            severity,
+            message,
+            related_information,
+            ..Default::default()
+        }
+    }
+}
+
+impl SyntaxError<()> {
+    /// Collect errors for the tree-sitter `ERROR` and `MISSING` nodes within a subtree.
+    ///
+    /// Only the outermost `ERROR` nodes are reported, since anything nested within them is already
+    /// covered by their range.
+    pub fn collect_from_tree(language: Language, node: tree_sitter::Node) -> Vec<Self> {
+        let mut errors = vec![];
+        let mut work = vec![node];
+        while let Some(node) = work.pop() {
+            if node.is_error() {
+                errors.push(Self::unexpected_node_error(language, node.range(), ()));
+                continue;
+            }
+            if node.is_missing() {
+                errors.push(Self::node_missing_error(language, node.range(), node.kind_id(), ()));
+                continue;
+            }
+            if node.has_error() {
+                let mut cursor = node.walk();
+                let children = node.children(&mut cursor).collect::<Vec<_>>();
+                work.extend(children.into_iter().rev());
+            }
         }
+        errors
     }
 }
 
@@ -179,6 +194,7 @@ pub enum SyntaxErrorType {
         language: Language,
         want_kind: u16,
     },
+    UnexpectedNodeError,
 }
 
 impl std::fmt::Debug for SyntaxErrorType {
@@ -215,6 +231,7 @@ impl std::fmt::Debug for SyntaxErrorType {
                     .field("want_kind", &language.node_kind_for_id(*want_kind).unwrap())
                     .finish()
             },
+            SyntaxErrorType::UnexpectedNodeError => f.debug_tuple("UnexpectedNodeError").finish(),
         }
     }
 }
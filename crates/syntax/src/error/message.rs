@@ -0,0 +1,210 @@
+//! User-facing descriptions of grammar node kinds, used for rendering syntax errors.
+
+use ddlog_lsp_languages::language::Language;
+
+// The maximum number of characters of source text quoted in a message.
+const MAX_QUOTED_CHARS: usize = 32;
+
+// Concepts for node kinds which are matched exactly (after collapsing `_branch_n` suffixes).
+#[rustfmt::skip]
+const CONCEPTS: &[(&str, &str)] = &[
+    ("ROOT",                "a declaration or rule"),
+    ("annotated_item",      "a declaration or rule"),
+    ("item",                "a declaration or rule"),
+    ("apply",               "a transformer application"),
+    ("arg",                 "an argument"),
+    ("arg_opt_type",        "an argument"),
+    ("arg_trans",           "a transformer argument"),
+    ("attribute",           "an attribute"),
+    ("attributes",          "an attribute"),
+    ("command",             "a command"),
+    ("field",               "a field"),
+    ("import",              "an import"),
+    ("interpolation",       "a string interpolation"),
+    ("index",               "an index declaration"),
+    ("key_primary",         "a primary key"),
+    ("misc_pat0",           "a pattern"),
+    ("module_alias",        "a module alias"),
+    ("module_path",         "a module path"),
+    ("name_arg",            "an argument name"),
+    ("name_cons",           "a constructor name"),
+    ("name_field",          "a field name"),
+    ("name_func",           "a function name"),
+    ("name_index",          "an index name"),
+    ("name_rel",            "a relation name"),
+    ("name_trans",          "a transformer name"),
+    ("name_type",           "a type name"),
+    ("name_var_term",       "a variable name"),
+    ("name_var_type",       "a type variable"),
+    ("rel_role",            "a relation role (`input`, `output`, or `internal`)"),
+    ("rel_semantics",       "a relation kind (`relation`, `stream`, or `multiset`)"),
+    ("rule_end",            "the end of the rule (`.`)"),
+    ("serde_encoding",      "a serialization format"),
+    ("transformer",         "a transformer declaration"),
+    ("updates",             "an update command"),
+    ("updates_end",         "the end of the updates (`;`)"),
+];
+
+// Concepts for families of node kinds which share a common prefix.
+#[rustfmt::skip]
+const PREFIX_CONCEPTS: &[(&str, &str)] = &[
+    ("atom",                "an atom"),
+    ("comment",             "a comment"),
+    ("cons",                "a constructor"),
+    ("escape_sequence",     "an escape sequence"),
+    ("exp",                 "an expression"),
+    ("function",            "a function declaration"),
+    ("ident",               "an identifier"),
+    ("lit_num",             "a number"),
+    ("lit_string",          "a string"),
+    ("lit",                 "a literal"),
+    ("name",                "a name"),
+    ("pat",                 "a pattern"),
+    ("record",              "a value"),
+    ("rel",                 "a relation declaration"),
+    ("rhs",                 "a rule body clause"),
+    ("rule",                "a rule"),
+    ("statement",           "a statement"),
+    ("string",              "a string"),
+    ("typedef",             "a type definition"),
+    ("type",                "a type"),
+    ("val",                 "a value"),
+    ("word",                "an identifier"),
+];
+
+// The kind name with any `_branch` or `_branch_n` suffix removed.
+fn collapse_branch(name: &str) -> &str {
+    match name.find("_branch") {
+        Some(index) => &name[.. index],
+        None => name,
+    }
+}
+
+// Whether `name` is `prefix` itself or a kind within the `prefix_` family.
+fn has_prefix(name: &str, prefix: &str) -> bool {
+    name == prefix || matches!(name.strip_prefix(prefix), Some(rest) if rest.starts_with('_'))
+}
+
+/// Describe a node kind in terms of the language concept it stands for, e.g., "an expression".
+pub fn describe_kind(language: Language, kind: u16) -> String {
+    let grammar: tree_sitter::Language = language.into();
+    let name = match grammar.node_kind_for_id(kind) {
+        Some(name) => name,
+        None => return String::from("something else"),
+    };
+
+    // anonymous nodes are tokens, which are best described by themselves
+    if !grammar.node_kind_is_named(kind) {
+        return format!("`{}`", name);
+    }
+
+    describe_named_kind(language, &name)
+}
+
+// Describe a named node kind (by its name) in terms of the language concept it stands for, falling
+// back to the name of the kind itself.
+fn describe_named_kind(language: Language, name: &str) -> String {
+    let name = collapse_branch(name);
+    if let Some((_, concept)) = CONCEPTS.iter().find(|(kind, _)| *kind == name) {
+        return String::from(*concept);
+    }
+    if let Some((_, concept)) = PREFIX_CONCEPTS.iter().find(|(prefix, _)| has_prefix(name, prefix)) {
+        return String::from(*concept);
+    }
+    match language {
+        // ".dat" commands are named after their keyword, e.g., `start` or `insert_or_update`
+        Language::DDlogDat => format!("{} `{}` command", article(name), name),
+        Language::DDlogDl => {
+            let words = name.replace('_', " ");
+            format!("{} {}", article(&words), words)
+        },
+    }
+}
+
+// The indefinite article for a word.
+fn article(word: &str) -> &'static str {
+    if word.starts_with(|c: char| "aeiou".contains(c)) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+/// Describe a list of alternative node kinds, e.g., "an expression or a pattern".
+///
+/// Kinds which describe the same concept (such as the branches of a single rule) are only
+/// mentioned once.
+pub fn describe_choices(language: Language, kinds: &[u16]) -> String {
+    let mut concepts = Vec::<String>::new();
+    for kind in kinds {
+        let concept = describe_kind(language, *kind);
+        if !concepts.contains(&concept) {
+            concepts.push(concept);
+        }
+    }
+    match concepts.as_slice() {
+        [] => String::from("something else"),
+        [concept] => concept.clone(),
+        [init @ .., last] => format!("{}, or {}", init.join(", "), last),
+    }
+}
+
+/// Quote the source text of a range, shortened to its first line and a bounded length.
+///
+/// Returns `None` if the range is empty.
+pub fn quote_text(content: &ropey::Rope, range: tree_sitter::Range) -> Option<String> {
+    let start = content.byte_to_char(range.start_byte() as usize);
+    let end = content.byte_to_char(range.end_byte() as usize);
+    let text = content.slice(start .. end).to_string();
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let mut quoted = line.chars().take(MAX_QUOTED_CHARS).collect::<String>();
+    if quoted.len() < line.len() || line.len() < text.trim().len() {
+        quoted.push('…');
+    }
+    Some(format!("`{}`", quoted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concepts_are_phrases_with_an_article() {
+        for (_, concept) in CONCEPTS.iter().chain(PREFIX_CONCEPTS) {
+            let article = concept.split(' ').next().unwrap_or_default();
+            assert!(["a", "an", "the"].contains(&article), "{}", concept);
+        }
+    }
+
+    #[test]
+    fn kinds_are_described_by_their_concept() {
+        for (kind, concept) in CONCEPTS {
+            assert_eq!(*concept, describe_named_kind(Language::DDlogDl, kind));
+        }
+        // suffixes of the branches of a rule are ignored
+        assert_eq!("an argument", describe_named_kind(Language::DDlogDl, "arg_branch_2"));
+    }
+
+    #[test]
+    fn families_of_kinds_are_described_by_their_prefix() {
+        assert_eq!("an expression", describe_named_kind(Language::DDlogDl, "exp_add"));
+        assert_eq!("an expression", describe_named_kind(Language::DDlogDl, "exp"));
+        // longer prefixes are listed (and thus matched) first
+        assert_eq!("a number", describe_named_kind(Language::DDlogDl, "lit_num_hex"));
+        assert_eq!("a literal", describe_named_kind(Language::DDlogDl, "lit_bool"));
+        // prefixes only match whole components of the name
+        assert_eq!("a types", describe_named_kind(Language::DDlogDl, "types"));
+        // exact matches take precedence over prefixes
+        assert_eq!("a relation name", describe_named_kind(Language::DDlogDl, "name_rel"));
+    }
+
+    #[test]
+    fn unknown_kinds_are_described_by_their_name() {
+        assert_eq!("a `start` command", describe_named_kind(Language::DDlogDat, "start"));
+        assert_eq!(
+            "an `insert_or_update` command",
+            describe_named_kind(Language::DDlogDat, "insert_or_update")
+        );
+        assert_eq!("an outer thing", describe_named_kind(Language::DDlogDl, "outer_thing"));
+    }
+}
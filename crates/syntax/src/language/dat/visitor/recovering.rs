@@ -0,0 +1,102 @@
+use crate::{
+    error::SyntaxError,
+    language::dat::{
+        kind,
+        visitor::{validating::ValidatingVisitor, Visitor},
+    },
+};
+use ddlog_lsp_languages::language::Language;
+
+/// A visitor which recovers from syntax errors by resynchronizing at the next top-level
+/// `command` node, so that every error in the document is reported (rather than just the first).
+pub struct RecoveringVisitor<'tree> {
+    language: Language,
+    node: tree_sitter::Node<'tree>,
+}
+
+impl<'tree> RecoveringVisitor<'tree> {
+    #[allow(missing_docs)]
+    pub fn new(language: Language, node: tree_sitter::Node<'tree>) -> Self {
+        Self { language, node }
+    }
+
+    /// Validate each top-level item separately and collect all of the errors.
+    ///
+    /// Items containing tree-sitter `ERROR` or `MISSING` nodes are reported in terms of those
+    /// nodes, since validating such items would only produce a (less precise) node mismatch.
+    pub fn visit(&mut self) -> Vec<SyntaxError<()>> {
+        let mut errors = vec![];
+        let mut cursor = self.node.walk();
+        for node in self.node.children(&mut cursor) {
+            if node.is_extra() && !node.has_error() {
+                continue;
+            }
+            if kind::COMMAND == node.kind_id() {
+                let item_kind = declaration_kind(node);
+                let item_errors = if node.has_error() {
+                    SyntaxError::collect_from_tree(self.language, node)
+                } else {
+                    let mut visitor = ValidatingVisitor::new(self.language, node);
+                    visitor.visit().err().into_iter().collect()
+                };
+                let item_errors = item_errors.into_iter();
+                errors.extend(item_errors.map(|error| error.with_item(item_kind, node.range())));
+            } else if node.has_error() {
+                errors.extend(SyntaxError::collect_from_tree(self.language, node));
+            } else {
+                let want_kind = kind::COMMAND;
+                let error =
+                    SyntaxError::node_mismatch_error(self.language, node.range(), node.kind_id(), want_kind, ());
+                errors.push(error);
+            }
+        }
+        errors
+    }
+}
+
+// The kind of the specific command within a `command`, falling back to the kind of the command
+// itself if it is malformed.
+fn declaration_kind(node: tree_sitter::Node) -> u16 {
+    node.named_child(0).map_or(node.kind_id(), |command| command.kind_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The lines of the reported errors, along with the lines of the commands they were reported
+    // within.
+    fn error_lines(text: &str) -> Vec<(u32, Option<u32>)> {
+        let mut parser = ddlog_lsp_languages::parser::dat().unwrap();
+        let tree = parser.parse(text, None).unwrap().unwrap();
+        let content = ropey::Rope::from(text);
+        let uri = lsp::Url::parse("file:///main.dat").unwrap();
+        let mut visitor = RecoveringVisitor::new(Language::DDlogDat, tree.root_node());
+        let mut lines = visitor
+            .visit()
+            .iter()
+            .map(|error| {
+                let diagnostic = error.to_lsp_diagnostic(&uri, &content);
+                let information = diagnostic.related_information.unwrap_or_default();
+                let item = information
+                    .first()
+                    .map(|information| information.location.range.start.line);
+                (diagnostic.range.start.line, item)
+            })
+            .collect::<Vec<_>>();
+        lines.dedup();
+        lines
+    }
+
+    #[test]
+    fn errors_of_each_command_are_reported() {
+        let text = "start;\ninsert R(1);\ninsert R(,);\ndump R;\ninsert R(1 2);\ncommit;\n";
+        assert_eq!(vec![(2, Some(2)), (4, Some(4))], error_lines(text));
+    }
+
+    #[test]
+    fn commands_following_an_error_are_parsed() {
+        let text = "insert R(,);\ninsert R(1);\ncommit;\n";
+        assert_eq!(vec![(0, Some(0))], error_lines(text));
+    }
+}
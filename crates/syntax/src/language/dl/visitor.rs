@@ -7,6 +7,9 @@ use crate::{
 #[allow(missing_docs)]
 pub mod default;
 
+#[allow(missing_docs)]
+pub mod recovering;
+
 #[allow(missing_docs)]
 pub mod validating;
 
@@ -0,0 +1,109 @@
+use crate::{
+    error::SyntaxError,
+    language::dl::{
+        kind,
+        visitor::{validating::ValidatingVisitor, Visitor},
+    },
+};
+use ddlog_lsp_languages::language::Language;
+
+/// A visitor which recovers from syntax errors by resynchronizing at the next top-level
+/// `annotated_item` node, so that every error in the document is reported (rather than just the
+/// first).
+pub struct RecoveringVisitor<'tree> {
+    language: Language,
+    node: tree_sitter::Node<'tree>,
+}
+
+impl<'tree> RecoveringVisitor<'tree> {
+    #[allow(missing_docs)]
+    pub fn new(language: Language, node: tree_sitter::Node<'tree>) -> Self {
+        Self { language, node }
+    }
+
+    /// Validate each top-level item separately and collect all of the errors.
+    ///
+    /// Items containing tree-sitter `ERROR` or `MISSING` nodes are reported in terms of those
+    /// nodes, since validating such items would only produce a (less precise) node mismatch.
+    pub fn visit(&mut self) -> Vec<SyntaxError<()>> {
+        let mut errors = vec![];
+        let mut cursor = self.node.walk();
+        for node in self.node.children(&mut cursor) {
+            if node.is_extra() && !node.has_error() {
+                continue;
+            }
+            if kind::ANNOTATED_ITEM == node.kind_id() {
+                let item_kind = declaration_kind(node);
+                let item_errors = if node.has_error() {
+                    SyntaxError::collect_from_tree(self.language, node)
+                } else {
+                    let mut visitor = ValidatingVisitor::new(self.language, node);
+                    visitor.visit().err().into_iter().collect()
+                };
+                let item_errors = item_errors.into_iter();
+                errors.extend(item_errors.map(|error| error.with_item(item_kind, node.range())));
+            } else if node.has_error() {
+                errors.extend(SyntaxError::collect_from_tree(self.language, node));
+            } else {
+                let want_kind = kind::ANNOTATED_ITEM;
+                let error =
+                    SyntaxError::node_mismatch_error(self.language, node.range(), node.kind_id(), want_kind, ());
+                errors.push(error);
+            }
+        }
+        errors
+    }
+}
+
+// The kind of the declaration (or rule) within an `annotated_item`, falling back to the kind of
+// the item itself if it is malformed.
+fn declaration_kind(node: tree_sitter::Node) -> u16 {
+    let mut cursor = node.walk();
+    let item = node
+        .named_children(&mut cursor)
+        .find(|child| kind::ITEM == child.kind_id());
+    item.and_then(|item| item.named_child(0))
+        .map_or(node.kind_id(), |declaration| declaration.kind_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The lines of the reported errors, along with the lines of the items they were reported within.
+    fn error_lines(text: &str) -> Vec<(u32, Option<u32>)> {
+        let mut parser = ddlog_lsp_languages::parser::dl().unwrap();
+        let tree = parser.parse(text, None).unwrap().unwrap();
+        let content = ropey::Rope::from(text);
+        let uri = lsp::Url::parse("file:///main.dl").unwrap();
+        let mut visitor = RecoveringVisitor::new(Language::DDlogDl, tree.root_node());
+        let mut lines = visitor
+            .visit()
+            .iter()
+            .map(|error| {
+                let diagnostic = error.to_lsp_diagnostic(&uri, &content);
+                let information = diagnostic.related_information.unwrap_or_default();
+                let item = information
+                    .first()
+                    .map(|information| information.location.range.start.line);
+                (diagnostic.range.start.line, item)
+            })
+            .collect::<Vec<_>>();
+        lines.dedup();
+        lines
+    }
+
+    #[test]
+    fn errors_of_each_item_are_reported() {
+        let text = "relation R(x: u32)\nrelation S(x: )\nrelation T(x: u32)\nfunction f(): u32 { 1 + }\nR(1).\n";
+        assert_eq!(vec![(1, Some(1)), (3, Some(3))], error_lines(text));
+    }
+
+    #[test]
+    fn items_following_an_error_are_parsed() {
+        let text = "relation S(x: )\nrelation R(x: u32)\nR(1).\n";
+        assert_eq!(vec![(0, Some(0))], error_lines(text));
+        let text = "relation S(x: )\nrelation R(x: u32)\nR(1) :- S(,).\n";
+        assert_eq!(vec![(0, Some(0)), (2, Some(2))], error_lines(text));
+    }
+}